@@ -6,6 +6,17 @@ fn main() -> std::io::Result<()> {
 
     tonic_build::configure()
         .type_attribute(".", "#[derive(serde::Serialize, serde::Deserialize)]")
+        // fields added after data was written, old json has no value of them
+        .field_attribute(
+            ".core.Field.FulltextOption.synonym_expand",
+            "#[serde(default)]",
+        )
+        .field_attribute(".core.Dict.reload_interval_secs", "#[serde(default)]")
+        .field_attribute(".core.Field.source", "#[serde(default)]")
+        .field_attribute(".core.Field.array", "#[serde(default)]")
+        .field_attribute(".core.Schema.validation", "#[serde(default)]")
+        .field_attribute(".core.Field.version", "#[serde(default)]")
+        .boxed(".calmserver.SearchRequest.query.cql")
         .protoc_arg("--experimental_allow_proto3_optional")
         .out_dir("proto/rust_pb/src/")
         .compile_protos(&proto_files, &[proto_dir])
@@ -1,12 +1,8 @@
-use std::{
-    collections::{HashMap, HashSet},
-    io::BufRead,
-    sync::Arc,
-};
+use std::{collections::HashSet, io::BufRead, time::Duration};
 
+use itertools::Itertools;
 use proto;
 use proto::core::field::FulltextOption;
-use itertools::Itertools;
 
 use crate::util::{CoreError, CoreResult};
 
@@ -18,21 +14,70 @@ pub fn stopwords(f: &FulltextOption) -> CoreResult<HashSet<String>> {
     }
 }
 
-pub fn synonyms(f: &FulltextOption) -> CoreResult<HashMap<String, Arc<Vec<String>>>> {
-    let mut result = HashMap::default();
+/// the shortest poll interval of Api and File dicts, None if no dict need to be polled
+pub fn reload_interval(f: &FulltextOption) -> Option<Duration> {
+    [&f.stopwords, &f.synonyms]
+        .into_iter()
+        .flatten()
+        .filter(|d| d.protocol() != proto::core::dict::Protocol::Json)
+        .filter(|d| d.reload_interval_secs > 0)
+        .map(|d| Duration::from_secs(d.reload_interval_secs as u64))
+        .min()
+}
+
+/// A synonym rule line.
+/// `a\tb\tc` (or `a, b, c`) is an equivalence rule, every phrase is expanded to all of them.
+/// `a\tb => c` is a one-way rule, `a` and `b` are replaced by `c`.
+/// a phrase may contain many words, e.g. `new york\tnyc`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SynonymLine {
+    pub from: Vec<String>,
+    pub to: Vec<String>,
+}
+
+pub fn synonyms(f: &FulltextOption) -> CoreResult<Vec<SynonymLine>> {
+    let mut result = Vec::new();
     if let Some(dict) = &f.synonyms {
         let lines = load(dict)?;
-        for line in lines {
-            let value = Arc::new(line.split('\t').map(ToString::to_string).collect_vec());
-
-            for v in value.iter() {
-                result.insert(v.clone(), value.clone());
+        for line in lines.iter().sorted() {
+            if let Some(line) = parse_synonym(line) {
+                result.push(line);
             }
         }
     }
     Ok(result)
 }
 
+fn parse_synonym(line: &str) -> Option<SynonymLine> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+
+    let split = |s: &str| {
+        s.split(['\t', ','])
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(ToString::to_string)
+            .collect_vec()
+    };
+
+    let (from, to) = match line.split_once("=>") {
+        Some((from, to)) => (split(from), split(to)),
+        None => {
+            let phrases = split(line);
+            (phrases.clone(), phrases)
+        }
+    };
+
+    if from.is_empty() || to.is_empty() {
+        log::warn!("synonym line:{:?} is invalid, ignore it", line);
+        return None;
+    }
+
+    Some(SynonymLine { from, to })
+}
+
 fn load(dict: &proto::core::Dict) -> CoreResult<HashSet<String>> {
     match dict.protocol() {
         proto::core::dict::Protocol::Json => {
@@ -101,3 +146,31 @@ fn load(dict: &proto::core::Dict) -> CoreResult<HashSet<String>> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_synonym() {
+        assert_eq!(
+            parse_synonym("new york\tnyc"),
+            Some(SynonymLine {
+                from: vec!["new york".to_string(), "nyc".to_string()],
+                to: vec!["new york".to_string(), "nyc".to_string()],
+            })
+        );
+
+        assert_eq!(
+            parse_synonym("usa, united states => america"),
+            Some(SynonymLine {
+                from: vec!["usa".to_string(), "united states".to_string()],
+                to: vec!["america".to_string()],
+            })
+        );
+
+        assert_eq!(parse_synonym(""), None);
+        assert_eq!(parse_synonym("# comment"), None);
+        assert_eq!(parse_synonym("a =>"), None);
+    }
+}
@@ -1,15 +1,21 @@
 use std::{
-    collections::{HashMap, HashSet},
-    sync::Arc,
+    collections::HashSet,
+    time::{Duration, Instant},
 };
 
 use itertools::Itertools;
-use proto::core::field::{fulltext_option, FulltextOption};
+use proto::core::field::{
+    fulltext_option::{self, SynonymExpand},
+    FulltextOption,
+};
 use rust_stemmers::Stemmer;
+use synonym::Synonyms;
 
 use crate::util::CoreResult;
 
 mod dict;
+pub(crate) mod normalizer;
+mod synonym;
 mod tokenizer_standard;
 mod tokenizer_whitespace;
 
@@ -21,14 +27,23 @@ pub struct Token {
     pub name: String,
     pub attrs: Option<Vec<TokenAttr>>,
     pub index: usize,
+    // byte offsets of the token in the original text
+    pub start: usize,
+    pub end: usize,
 }
 
 impl Token {
     pub fn new(name: &str) -> Token {
+        Self::with_offset(name, 0)
+    }
+
+    pub fn with_offset(name: &str, start: usize) -> Token {
         Token {
             name: name.to_string(),
             attrs: None,
             index: 0,
+            start,
+            end: start + name.len(),
         }
     }
 }
@@ -41,8 +56,13 @@ pub struct Analyzer {
     tokenizer: Box<dyn Tokenizer + Send + Sync + 'static>,
     lowercase: bool,
     stopwords: HashSet<String>,
-    synonyms: HashMap<String, Arc<Vec<String>>>,
+    synonyms: Synonyms,
+    synonym_expand: SynonymExpand,
     stemmer: Option<Stemmer>,
+    // checksum of everything that changes the indexed tokens
+    index_digest: u32,
+    reload_interval: Option<Duration>,
+    loaded_at: Instant,
 }
 
 impl Analyzer {
@@ -52,7 +72,11 @@ impl Analyzer {
             lowercase: true,
             stemmer: None,
             stopwords: HashSet::new(),
-            synonyms: HashMap::new(),
+            synonyms: Synonyms::default(),
+            synonym_expand: SynonymExpand::Query,
+            index_digest: 0,
+            reload_interval: None,
+            loaded_at: Instant::now(),
         }
     }
 
@@ -82,27 +106,77 @@ impl Analyzer {
         };
 
         let stopwords = dict::stopwords(op)?;
+        let synonym_lines = dict::synonyms(op)?;
 
-        let synonyms = dict::synonyms(op)?;
+        // synonyms only change the indexed tokens when expanded at index time
+        let index_digest = {
+            let mut hasher = crc32fast::Hasher::new();
+            hasher.update(&op.tokenizer.to_le_bytes());
+            hasher.update(&[lowercase as u8, stemmer.is_some() as u8]);
+            stopwords
+                .iter()
+                .sorted()
+                .for_each(|w| hasher.update(format!("{}\n", w).as_bytes()));
+            if op.synonym_expand() == SynonymExpand::Index {
+                synonym_lines
+                    .iter()
+                    .for_each(|l| hasher.update(format!("{:?}\n", l).as_bytes()));
+            }
+            hasher.finalize()
+        };
 
-        Ok(Self {
+        let mut analyzer = Self {
             tokenizer,
             lowercase,
             stemmer,
             stopwords,
-            synonyms,
-        })
+            synonyms: Synonyms::default(),
+            synonym_expand: op.synonym_expand(),
+            index_digest,
+            reload_interval: dict::reload_interval(op),
+            loaded_at: Instant::now(),
+        };
+
+        // synonym phrases go through the same pipeline as the text
+        let mut synonyms = Synonyms::default();
+        for line in synonym_lines {
+            let analyze = |phrases: &Vec<String>| {
+                phrases
+                    .iter()
+                    .map(|p| analyzer.analyze(p))
+                    .collect::<Vec<_>>()
+            };
+            synonyms.add(analyze(&line.from), analyze(&line.to));
+        }
+        analyzer.synonyms = synonyms;
+
+        Ok(analyzer)
     }
 
-    pub fn analyzer_index(&self, text: &str) -> Vec<Token> {
-        let result = if self.lowercase {
-            self.tokenizer.tokenize(&text.to_lowercase())
-        } else {
-            self.tokenizer.tokenize(text)
-        };
+    /// checksum of tokenizer, filters and dicts used at index time,
+    /// segments indexed with a different digest are stale and need to be rebuilt
+    pub fn index_digest(&self) -> u32 {
+        self.index_digest
+    }
+
+    /// an Api or File dict is due to be polled again
+    pub fn reload_due(&self) -> bool {
+        self.reload_interval
+            .is_some_and(|interval| self.loaded_at.elapsed() >= interval)
+    }
 
-        result
+    /// tokenize, lowercase, remove stopwords and stem the text
+    fn analyze(&self, text: &str) -> Vec<Token> {
+        // lowercase tokens rather than text, so offsets point to the original text
+        self.tokenizer
+            .tokenize(text)
             .into_iter()
+            .map(|mut t| {
+                if self.lowercase {
+                    t.name = t.name.to_lowercase();
+                }
+                t
+            })
             .filter(|t| !self.stopwords.contains(&t.name))
             .enumerate()
             .map(|(i, mut t)| {
@@ -118,41 +192,129 @@ impl Analyzer {
             .collect()
     }
 
-    pub fn analyzer_query(&self, text: &str) -> Vec<Token> {
-        let tokens = self.tokenizer.tokenize(text);
-        let mut result = Vec::with_capacity(tokens.len());
-        for (i, mut t) in tokens
-            .into_iter()
-            .filter(|t| !self.stopwords.contains(&t.name))
-            .enumerate()
-        {
-            if let Some(synonyms) = self.synonyms.get(&t.name) {
-                for s in synonyms.iter() {
-                    let mut t = t.clone();
-                    if let Some(stemmer) = &self.stemmer {
-                        let new_str = stemmer.stem(&t.name);
-                        if t.name != new_str {
-                            t.name = new_str.to_string();
-                        }
-                    } else {
-                        t.name.clone_from(s);
-                    }
-                    t.index = i;
-                    result.push(t);
-                }
-            } else {
-                if let Some(stemmer) = &self.stemmer {
-                    let new_str = stemmer.stem(&t.name);
-                    if t.name != new_str {
-                        t.name = new_str.to_string();
-                    }
-                }
-                t.index = i;
-            }
+    pub fn analyzer_index(&self, text: &str) -> Vec<Token> {
+        let tokens = self.analyze(text);
+        if self.synonym_expand == SynonymExpand::Index && !self.synonyms.is_empty() {
+            self.synonyms.expand_index(tokens)
+        } else {
+            tokens
+        }
+    }
+
+    /// analyze query text, return token paths.
+    /// without synonyms there is only one path, every path can be used as a phrase
+    pub fn analyzer_query(&self, text: &str) -> Vec<Vec<Token>> {
+        let tokens = self.analyze(text);
+        if self.synonym_expand == SynonymExpand::Query && !self.synonyms.is_empty() {
+            self.synonyms.expand_query(tokens)
+        } else {
+            vec![tokens]
+        }
+    }
+}
+
+/// document length of analyzed tokens, stacked synonyms share the position so not counted
+pub(crate) fn position_len(tokens: &[Token]) -> usize {
+    tokens.iter().map(|t| t.index + 1).max().unwrap_or(0)
+}
 
-            result.push(t);
+#[cfg(test)]
+mod tests {
+    use proto::core::{dict::Protocol, Dict};
+
+    use super::*;
+
+    fn option(synonyms: &[&str], expand: SynonymExpand, filters: Vec<i32>) -> FulltextOption {
+        FulltextOption {
+            tokenizer: fulltext_option::Tokenizer::Standard as i32,
+            filters,
+            stopwords: None,
+            synonyms: Some(Dict {
+                name: "synonyms".to_string(),
+                protocol: Protocol::Json as i32,
+                value: serde_json::to_string(synonyms).unwrap(),
+                reload_interval_secs: 0,
+            }),
+            synonym_expand: expand as i32,
         }
+    }
 
-        result
+    fn names(tokens: &[Token]) -> Vec<(String, usize)> {
+        tokens
+            .iter()
+            .filter(|t| !t.name.trim().is_empty())
+            .map(|t| (t.name.clone(), t.index))
+            .collect()
+    }
+
+    #[test]
+    fn test_synonym_index() {
+        let lowercase = vec![fulltext_option::Filter::Lowercase as i32];
+        let analyzer =
+            Analyzer::new(&option(&["new york,nyc"], SynonymExpand::Index, lowercase)).unwrap();
+
+        let tokens = analyzer.analyzer_index("I love New York");
+        let tokens = names(&tokens);
+        assert!(tokens.contains(&("new".to_string(), 4)));
+        assert!(tokens.contains(&("york".to_string(), 6)));
+        // stacked on both ends of the phrase
+        assert!(tokens.contains(&("nyc".to_string(), 4)));
+        assert!(tokens.contains(&("nyc".to_string(), 6)));
+
+        // index time expansion, query is not expanded
+        assert_eq!(analyzer.analyzer_query("nyc").len(), 1);
+    }
+
+    #[test]
+    fn test_synonym_query() {
+        let lowercase = vec![fulltext_option::Filter::Lowercase as i32];
+        let analyzer = Analyzer::new(&option(
+            &["new york,nyc", "big => large"],
+            SynonymExpand::Query,
+            lowercase,
+        ))
+        .unwrap();
+
+        let paths = analyzer
+            .analyzer_query("big New York")
+            .iter()
+            .map(|p| names(p))
+            .collect_vec();
+        assert_eq!(paths.len(), 2);
+        assert_eq!(
+            paths[0],
+            vec![
+                ("large".to_string(), 0),
+                ("new".to_string(), 2),
+                ("york".to_string(), 4)
+            ]
+        );
+        assert_eq!(
+            paths[1],
+            vec![("large".to_string(), 0), ("nyc".to_string(), 2)]
+        );
+
+        // one-way rule only works from left to right
+        assert_eq!(analyzer.analyzer_query("large").len(), 1);
+    }
+
+    #[test]
+    fn test_synonym_stemmer() {
+        let filters = vec![
+            fulltext_option::Filter::Lowercase as i32,
+            fulltext_option::Filter::Stemmer as i32,
+        ];
+        let analyzer =
+            Analyzer::new(&option(&["running,jogging"], SynonymExpand::Query, filters)).unwrap();
+
+        let paths = analyzer
+            .analyzer_query("Running")
+            .iter()
+            .map(|p| names(p))
+            .collect_vec();
+        assert_eq!(
+            paths,
+            vec![vec![("run".to_string(), 0)], vec![("jog".to_string(), 0)]]
+        );
     }
 }
@@ -0,0 +1,98 @@
+//! normalizers of string fields, a value is still one term after it is normalized,
+//! so exact match can ignore case, accents and width of chars
+
+use icu_normalizer::{ComposingNormalizerBorrowed, DecomposingNormalizerBorrowed};
+use proto::core::{
+    field::{self, keyword_option::Normalizer, KeywordOption},
+    Field,
+};
+
+pub(crate) fn keyword_option(field: &Field) -> Option<&KeywordOption> {
+    match &field.option {
+        Some(field::Option::Keyword(k)) if !k.normalizers.is_empty() => Some(k),
+        _ => None,
+    }
+}
+
+/// normalizers are applied in order of the option
+pub(crate) fn normalize(value: &str, option: &KeywordOption) -> String {
+    let mut value = value.to_string();
+    for n in option.normalizers.iter() {
+        value = match Normalizer::try_from(*n) {
+            Ok(Normalizer::Lowercase) => value.to_lowercase(),
+            Ok(Normalizer::AsciiFolding) => ascii_folding(&value),
+            Ok(Normalizer::Trim) => value.trim().to_string(),
+            Ok(Normalizer::Nfkc) => ComposingNormalizerBorrowed::new_nfkc()
+                .normalize(&value)
+                .into_owned(),
+            Err(_) => {
+                log::warn!("unknown normalizer:{}, ignore it", n);
+                value
+            }
+        };
+    }
+    value
+}
+
+// accents are removed after decomposing, letters without decomposition are mapped
+fn ascii_folding(value: &str) -> String {
+    if value.is_ascii() {
+        return value.to_string();
+    }
+    let mut folded = String::with_capacity(value.len());
+    for c in DecomposingNormalizerBorrowed::new_nfd()
+        .normalize(value)
+        .chars()
+    {
+        match c {
+            // combining diacritical marks
+            '\u{300}'..='\u{36f}' => {}
+            'ß' => folded.push_str("ss"),
+            'Æ' => folded.push_str("AE"),
+            'æ' => folded.push_str("ae"),
+            'Œ' => folded.push_str("OE"),
+            'œ' => folded.push_str("oe"),
+            'Þ' => folded.push_str("TH"),
+            'þ' => folded.push_str("th"),
+            'Ø' => folded.push('O'),
+            'ø' => folded.push('o'),
+            'Đ' | 'Ð' => folded.push('D'),
+            'đ' | 'ð' => folded.push('d'),
+            'Ł' => folded.push('L'),
+            'ł' => folded.push('l'),
+            'ı' => folded.push('i'),
+            c => folded.push(c),
+        }
+    }
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        let option = |normalizers: &[Normalizer]| KeywordOption {
+            normalizers: normalizers.iter().map(|n| *n as i32).collect(),
+        };
+
+        let lowercase = option(&[Normalizer::Lowercase]);
+        assert_eq!(normalize("Alice SMITH", &lowercase), "alice smith");
+
+        let all = option(&[
+            Normalizer::Nfkc,
+            Normalizer::Trim,
+            Normalizer::AsciiFolding,
+            Normalizer::Lowercase,
+        ]);
+        assert_eq!(normalize("  Crème Brûlée ", &all), "creme brulee");
+        assert_eq!(normalize("Straße Łódź", &all), "strasse lodz");
+        // full width chars are the ascii ones after nfkc
+        assert_eq!(normalize("ＡＢＣ１２３", &all), "abc123");
+        assert_eq!(normalize("ﬁle", &option(&[Normalizer::Nfkc])), "file");
+
+        // only trim
+        assert_eq!(normalize(" Crème ", &option(&[Normalizer::Trim])), "Crème");
+    }
+}
@@ -0,0 +1,188 @@
+use std::{collections::HashMap, sync::Arc};
+
+use itertools::Itertools;
+
+use super::Token;
+
+/// max token paths a query can be expanded to by synonyms
+const MAX_SYNONYM_PATHS: usize = 64;
+
+struct Rule {
+    // analyzed token names of the matched phrase, whitespace tokens are skipped
+    from: Vec<String>,
+    // keep the matched tokens, false for one-way rule which replace them
+    keep: bool,
+    // analyzed alternatives, token index is relative to the alternative
+    to: Arc<Vec<Vec<Token>>>,
+}
+
+/// Synonym rules keyed by the first token of the matched phrase
+#[derive(Default)]
+pub(crate) struct Synonyms {
+    rules: HashMap<String, Vec<Rule>>,
+}
+
+fn is_blank(t: &Token) -> bool {
+    t.name.trim().is_empty()
+}
+
+impl Synonyms {
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// add a rule, `from` and `to` are phrases already analyzed
+    pub fn add(&mut self, from: Vec<Vec<Token>>, to: Vec<Vec<Token>>) {
+        let to = to.into_iter().filter(|t| !t.is_empty()).collect_vec();
+
+        for phrase in from {
+            let names = phrase
+                .iter()
+                .filter(|t| !is_blank(t))
+                .map(|t| t.name.clone())
+                .collect_vec();
+            if names.is_empty() {
+                continue;
+            }
+
+            let same = |alt: &Vec<Token>| {
+                alt.iter()
+                    .filter(|t| !is_blank(t))
+                    .map(|t| &t.name)
+                    .eq(names.iter())
+            };
+
+            let keep = to.iter().any(same);
+            let alternatives = to.iter().filter(|t| !same(t)).cloned().collect_vec();
+            if alternatives.is_empty() && keep {
+                continue;
+            }
+
+            self.rules.entry(names[0].clone()).or_default().push(Rule {
+                from: names,
+                keep,
+                to: Arc::new(alternatives),
+            });
+        }
+    }
+
+    /// find the longest rule matched at tokens[i], return the end(exclusive) of the match
+    fn find(&self, tokens: &[Token], i: usize) -> Option<(usize, &Rule)> {
+        let rules = self.rules.get(&tokens[i].name)?;
+
+        let mut result: Option<(usize, &Rule)> = None;
+        'rule: for rule in rules {
+            let mut j = i;
+            for (k, name) in rule.from.iter().enumerate() {
+                if k > 0 {
+                    while j < tokens.len() && is_blank(&tokens[j]) {
+                        j += 1;
+                    }
+                }
+                if j >= tokens.len() || tokens[j].name != *name {
+                    continue 'rule;
+                }
+                j += 1;
+            }
+            if result.as_ref().is_none_or(|(end, _)| j > *end) {
+                result = Some((j, rule));
+            }
+        }
+        result
+    }
+
+    /// expand tokens for indexing.
+    /// alternatives are stacked on the matched positions, aligned both to the start
+    /// and to the end of the match, so phrases crossing either boundary still match.
+    pub fn expand_index(&self, tokens: Vec<Token>) -> Vec<Token> {
+        let mut result = Vec::with_capacity(tokens.len());
+        let mut i = 0;
+        while i < tokens.len() {
+            let (end, rule) = match self.find(&tokens, i) {
+                Some(v) => v,
+                None => {
+                    result.push(tokens[i].clone());
+                    i += 1;
+                    continue;
+                }
+            };
+
+            let (start_pos, end_pos) = (tokens[i].index, tokens[end - 1].index);
+            let (start, end_offset) = (tokens[i].start, tokens[end - 1].end);
+
+            if rule.keep {
+                result.extend_from_slice(&tokens[i..end]);
+            }
+
+            for alt in rule.to.iter() {
+                let len = alt.len();
+                for (j, t) in alt.iter().enumerate() {
+                    let head = start_pos + j;
+                    let tail = (end_pos + 1 + j).saturating_sub(len);
+                    for index in [head, tail].into_iter().unique() {
+                        let mut t = t.clone();
+                        t.index = index;
+                        // alternatives cover the whole matched phrase in the text
+                        (t.start, t.end) = (start, end_offset);
+                        result.push(t);
+                    }
+                }
+            }
+
+            i = end;
+        }
+
+        result.sort_by_key(|t| t.index);
+        result
+    }
+
+    /// expand tokens for query, return every path of the token graph,
+    /// tokens in a path have sequential index so that phrase query works on any path
+    pub fn expand_query(&self, tokens: Vec<Token>) -> Vec<Vec<Token>> {
+        let mut slots: Vec<Vec<Vec<Token>>> = Vec::with_capacity(tokens.len());
+
+        let mut i = 0;
+        while i < tokens.len() {
+            match self.find(&tokens, i) {
+                Some((end, rule)) => {
+                    let mut slot = Vec::with_capacity(rule.to.len() + 1);
+                    if rule.keep {
+                        slot.push(tokens[i..end].to_vec());
+                    }
+                    slot.extend(rule.to.iter().cloned());
+                    slots.push(slot);
+                    i = end;
+                }
+                None => {
+                    slots.push(vec![vec![tokens[i].clone()]]);
+                    i += 1;
+                }
+            }
+        }
+
+        let mut paths: Vec<Vec<Token>> = vec![Vec::with_capacity(tokens.len())];
+        for slot in slots {
+            if slot.len() == 1 {
+                paths.iter_mut().for_each(|p| p.extend_from_slice(&slot[0]));
+                continue;
+            }
+            paths = paths
+                .iter()
+                .flat_map(|p| {
+                    slot.iter().map(move |alt| {
+                        let mut p = p.clone();
+                        p.extend_from_slice(alt);
+                        p
+                    })
+                })
+                .take(MAX_SYNONYM_PATHS)
+                .collect();
+        }
+
+        for path in paths.iter_mut() {
+            path.iter_mut().enumerate().for_each(|(i, t)| t.index = i);
+        }
+
+        paths
+    }
+}
@@ -13,7 +13,9 @@ impl StandardTokenizer {
 
 impl Tokenizer for StandardTokenizer {
     fn tokenize(&self, text: &str) -> Vec<Token> {
-        text.split_word_bounds().map(Token::new).collect()
+        text.split_word_bound_indices()
+            .map(|(i, s)| Token::with_offset(s, i))
+            .collect()
     }
 }
 
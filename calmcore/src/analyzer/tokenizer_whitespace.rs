@@ -11,7 +11,9 @@ impl WhitespaceTokenizer {
 
 impl Tokenizer for WhitespaceTokenizer {
     fn tokenize(&self, text: &str) -> Vec<Token> {
-        text.split_whitespace().map(Token::new).collect()
+        text.split_whitespace()
+            .map(|s| Token::with_offset(s, s.as_ptr() as usize - text.as_ptr() as usize))
+            .collect()
     }
 }
 
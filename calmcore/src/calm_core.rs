@@ -28,10 +28,15 @@
 
 use std::{
     collections::HashMap,
+    path::PathBuf,
     sync::{Arc, Mutex, RwLock},
 };
 
 use crate::{
+    persist::{
+        schema::SchemaStore,
+        snapshot::{self, SnapshotManifest},
+    },
     util::{CoreError, CoreResult},
     *,
 };
@@ -52,6 +57,8 @@ pub struct Config {
     pub segment_max_size: usize,
     // segment persist interval default:3600
     pub flush_interval_secs: u64,
+    // interval of polling dicts of analyzers default:6
+    pub analyzer_interval_secs: u64,
 }
 
 impl Config {
@@ -60,6 +67,7 @@ impl Config {
             data_path: data_path.to_string(),
             segment_max_size: 1_000_000,
             flush_interval_secs: 3600,
+            analyzer_interval_secs: 6,
         }
     }
 
@@ -72,6 +80,11 @@ impl Config {
         self.flush_interval_secs = flush_interval_secs;
         self
     }
+
+    pub fn analyzer_interval_secs(mut self, analyzer_interval_secs: u64) -> Self {
+        self.analyzer_interval_secs = analyzer_interval_secs;
+        self
+    }
 }
 
 /// The CalmCore is the core engine for data indexing and storage
@@ -86,16 +99,13 @@ impl CalmCore {
     /// Create a new CalmCore instance
     /// # Arguments
     /// * `data_path` - The path to store the data
-    /// * `model` - The model of the meta manager，if cluster model you need impl MetaManager to your own
     /// # Example
-    /// ```rust
-    /// use calm_core::CalmCore;
-    /// use proto::core::field;
-    /// use proto::core::Schema;
-    /// use calm_core::MetaModel;
-    /// use calm_core::util::CoreResult;
+    /// ```rust,no_run
+    /// use calmcore::CalmCore;
+    /// use calmcore::util::CoreResult;
     /// fn main() -> CoreResult<()> {
-    ///    let core = CalmCore::new("data", MetaModel::Local)?;
+    ///    let core = CalmCore::new("data")?;
+    ///    Ok(())
     /// }
     /// ```
     /// # Errors
@@ -108,7 +118,11 @@ impl CalmCore {
             CoreError::Existed(format!("create dir:{} error:{:?}", conf.data_path, e))
         })?;
 
-        let job = Job::new(conf.segment_max_size, conf.flush_interval_secs);
+        let job = Job::new(
+            conf.segment_max_size,
+            conf.flush_interval_secs,
+            conf.analyzer_interval_secs,
+        );
 
         Ok(CalmCore {
             data_path: conf.data_path,
@@ -178,6 +192,8 @@ impl CalmCore {
 
         drop(lock);
 
+        self.job.add_engine(engine.clone());
+
         Ok(engine)
     }
 
@@ -196,24 +212,19 @@ impl CalmCore {
         }
     }
 
+    /// the engine is closed after the core and jobs release it, loading it again waits
+    /// until it is closed
     pub fn release_engine(&self, engine_name: &str) -> CoreResult<()> {
-        let engine = { self.engine_map.write().unwrap().get(engine_name).cloned() };
+        let lock = self.engine_lock.lock().unwrap();
 
-        if engine.is_none() {
+        let Some(engine) = self.engine_map.write().unwrap().remove(engine_name) else {
             return Ok(());
-        }
-
-        let engine = engine.unwrap();
-        if engine.is_closeing() {
-            return Err(CoreError::Duplicated(format!(
-                "engine:{:?} is closeing please wait",
-                engine_name
-            )));
-        }
+        };
+        self.job.remove_engine(engine_name);
 
         engine.close();
 
-        self.engine_map.write().unwrap().remove(engine_name);
+        drop(lock);
 
         Ok(())
     }
@@ -221,6 +232,97 @@ impl CalmCore {
     pub fn list_engine(&self) -> CoreResult<Vec<String>> {
         Ok(self.engine_map.read().unwrap().keys().cloned().collect())
     }
+
+    /// restore a snapshot as a new engine
+    /// # Arguments
+    /// * `snapshot_path` - The dir of the snapshot, made by `Engine::snapshot`
+    /// * `name` - The name of the engine, it may be not the name of snapshot
+    /// # Errors
+    /// * If the engine exist, return CoreError::Existed
+    /// * If the snapshot is not finished, return CoreError::NotExisted
+    pub fn restore_engine(&self, snapshot_path: &str, name: &str) -> CoreResult<Arc<Engine>> {
+        let lock = self.engine_lock.lock().unwrap();
+
+        let path = PathBuf::from(&self.data_path).join(name);
+        if self.engine_map.read().unwrap().contains_key(name) || path.exists() {
+            return Err(CoreError::Existed(format!(
+                "engine name:{:?} is existed",
+                name
+            )));
+        }
+
+        let snapshot_path = PathBuf::from(snapshot_path);
+        snapshot::read_manifest(&snapshot_path)?;
+
+        let restore = || -> CoreResult<Arc<Engine>> {
+            snapshot::link_dir(&snapshot_path.join("schema"), &path.join("schema"))?;
+            snapshot::link_dir(&snapshot_path.join("segments"), &path.join("segments"))?;
+
+            let schema_store = SchemaStore::new(&path)?;
+            let mut schema = schema_store.read_schema()?;
+            schema.name = name.to_string();
+            schema_store.write_schema(&schema)?;
+
+            Ok(Arc::new(Engine::open(&self.data_path, name)?))
+        };
+
+        let engine = match restore() {
+            Ok(engine) => engine,
+            Err(e) => {
+                let _ = std::fs::remove_dir_all(&path);
+                return Err(e);
+            }
+        };
+
+        self.engine_map
+            .write()
+            .unwrap()
+            .insert(name.to_string(), engine.clone());
+
+        drop(lock);
+
+        self.job.add_engine(engine.clone());
+
+        Ok(engine)
+    }
+
+    /// snapshot the engine to `_snapshots/[name]` in data path
+    pub fn snapshot_engine(&self, name: &str) -> CoreResult<SnapshotManifest> {
+        let engine = self.get_engine(name)?;
+        let path = self
+            .snapshots_path(name)
+            .join(util::now_millis().to_string());
+        engine.snapshot(&path.to_string_lossy())
+    }
+
+    /// snapshots of the engine in data path, the oldest first
+    pub fn list_snapshots(&self, name: &str) -> CoreResult<Vec<SnapshotManifest>> {
+        snapshot::list(&self.snapshots_path(name))
+    }
+
+    /// remove old snapshots of the engine in data path and keep the latest ones,
+    /// return the removed
+    pub fn prune_snapshots(&self, name: &str, keep: usize) -> CoreResult<Vec<SnapshotManifest>> {
+        let mut snapshots = self.list_snapshots(name)?;
+        let removed = snapshots.len().saturating_sub(keep);
+        let removed = snapshots.drain(..removed).collect::<Vec<_>>();
+        for s in removed.iter() {
+            std::fs::remove_dir_all(&s.path)?;
+        }
+        Ok(removed)
+    }
+
+    fn snapshots_path(&self, name: &str) -> PathBuf {
+        PathBuf::from(&self.data_path)
+            .join(snapshot::SNAPSHOTS)
+            .join(name)
+    }
+}
+
+impl Drop for CalmCore {
+    fn drop(&mut self) {
+        self.job.stop();
+    }
 }
 
 #[cfg(test)]
@@ -228,11 +330,12 @@ mod tests {
 
     use proto::core::{field, Field, Schema};
 
-    use crate::CalmCore;
+    use crate::{test_util::TestDir, CalmCore};
 
     #[test]
     fn test_calm_core() {
-        let core = CalmCore::new("data").unwrap();
+        let test_dir = TestDir::new("calm_core");
+        let core = CalmCore::new(test_dir.path_str()).unwrap();
 
         let schema = Schema {
             name: "test".to_string(),
@@ -245,22 +348,107 @@ mod tests {
                             name,
                             r#type: r#type as i32,
                             option,
+                            source: String::new(),
+                            array: false,
+                            version: 0,
                         },
                     )
                 })
                 .collect(),
             metadata: None,
             schemaless: false,
+            dynamic: None,
+            validation: 0,
         };
 
         let engine = core.create_engine(schema).unwrap();
+        drop(engine);
 
         let engine = core.load_engine("test").unwrap();
+        drop(engine);
 
         core.release_engine("test").unwrap();
+        assert!(core.get_engine("test").is_err());
 
         let engine = core.load_engine("test").unwrap();
+        drop(engine);
 
         core.release_engine("test").unwrap();
     }
+
+    #[test]
+    fn test_snapshot_restore() {
+        let test_dir = TestDir::new("snapshot");
+        let core = CalmCore::new(test_dir.path_str()).unwrap();
+
+        let schema = crate::easy_schema(
+            "test",
+            vec![("name".to_string(), field::Type::String, None)],
+        );
+        let engine = core.create_engine(schema).unwrap();
+        let write = |engine: &crate::Engine, name: &str| {
+            engine
+                .mutate_json(
+                    crate::ActionType::Append,
+                    name.to_string(),
+                    format!(r#"{{"name": "{}"}}"#, name).as_bytes(),
+                    None,
+                )
+                .unwrap();
+        };
+        let names = |engine: &crate::Engine| {
+            let mut names = engine
+                .sql("SELECT * FROM test")
+                .unwrap()
+                .hits
+                .into_iter()
+                .map(|h| h.record.unwrap().name)
+                .collect::<Vec<_>>();
+            names.sort();
+            names
+        };
+
+        write(&engine, "r1");
+        engine.persist().unwrap();
+        write(&engine, "r2");
+        let manifest = core.snapshot_engine("test").unwrap();
+        assert_eq!(manifest.segments.len(), 2);
+        write(&engine, "r3");
+
+        // records written after snapshot are not restored
+        let restored = core
+            .restore_engine(&manifest.path.to_string_lossy(), "restored")
+            .unwrap();
+        assert_eq!(restored.scope().schema.name, "restored");
+        assert_eq!(names(&restored), vec!["r1", "r2"]);
+        assert_eq!(names(&engine), vec!["r1", "r2", "r3"]);
+        write(&restored, "r4");
+        assert_eq!(names(&restored), vec!["r1", "r2", "r4"]);
+        assert!(core
+            .restore_engine(&manifest.path.to_string_lossy(), "restored")
+            .is_err());
+
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        core.snapshot_engine("test").unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        let latest = core.snapshot_engine("test").unwrap();
+        assert_eq!(core.list_snapshots("test").unwrap().len(), 3);
+
+        let removed = core.prune_snapshots("test", 1).unwrap();
+        assert_eq!(removed.len(), 2);
+        assert_eq!(removed[0].created_at, manifest.created_at);
+        let snapshots = core.list_snapshots("test").unwrap();
+        assert_eq!(snapshots.len(), 1);
+        assert_eq!(snapshots[0].path, latest.path);
+
+        // the latest snapshot has records written before it
+        assert_eq!(
+            names(
+                &core
+                    .restore_engine(&latest.path.to_string_lossy(), "latest")
+                    .unwrap()
+            ),
+            vec!["r1", "r2", "r3"]
+        );
+    }
 }
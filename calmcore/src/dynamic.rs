@@ -0,0 +1,171 @@
+//! dynamic mapping of schemaless engine, a key of record is added as a field
+//! the first time it is seen, its type is inferred by the value
+
+use proto::core::{field, DynamicOption, Field};
+use serde_json::Value;
+
+use crate::{
+    util::{CoreError, CoreResult},
+    Scope,
+};
+
+/// default chars of a string to be text
+pub const DEFAULT_TEXT_MIN_CHARS: usize = 64;
+
+/// fields of keys not in scope, keys of objects are dotted paths.
+/// a value conflicting with the type of its field is an error
+pub(crate) fn infer_fields(
+    scope: &Scope,
+    json: &Value,
+    option: &DynamicOption,
+) -> CoreResult<Vec<Field>> {
+    let mut fields = Vec::new();
+    if let Value::Object(obj) = json {
+        for (key, value) in obj {
+            infer(scope, key, value, option, &mut fields)?;
+        }
+    }
+    Ok(fields)
+}
+
+fn infer(
+    scope: &Scope,
+    name: &str,
+    value: &Value,
+    option: &DynamicOption,
+    fields: &mut Vec<Field>,
+) -> CoreResult<()> {
+    // `_all` and other names start with `_` are not user fields
+    if name.starts_with('_') || value.is_null() {
+        return Ok(());
+    }
+
+    let field = scope
+        .get_field(name)
+        .map(|f| f.r#type())
+        .or_else(|| fields.iter().find(|f| f.name == name).map(|f| f.r#type()));
+    if let Some(r#type) = field {
+        if !compatible(r#type, value) {
+            return Err(CoreError::InvalidParam(format!(
+                "field:{} type:{} conflicts with value:{}",
+                name,
+                r#type.as_str_name(),
+                value
+            )));
+        }
+        return Ok(());
+    }
+
+    match value {
+        Value::Object(obj) => {
+            for (key, v) in obj {
+                infer(scope, &format!("{}.{}", name, key), v, option, fields)?;
+            }
+        }
+        Value::Array(arr) => {
+            for v in arr {
+                infer(scope, name, v, option, fields)?;
+            }
+        }
+        v => {
+            let r#type = match v {
+                Value::Bool(_) => field::Type::Bool,
+                Value::Number(n) if n.is_f64() || option.int_as_float => field::Type::Float,
+                Value::Number(_) => field::Type::Int,
+                Value::String(s) if s.chars().count() >= text_min_chars(option) => {
+                    field::Type::Text
+                }
+                _ => field::Type::String,
+            };
+            log::info!("dynamic field:{} type:{}", name, r#type.as_str_name());
+            fields.push(Field {
+                name: name.to_string(),
+                r#type: r#type as i32,
+                option: None,
+                source: String::new(),
+                array: false,
+                version: 0,
+            });
+        }
+    }
+    Ok(())
+}
+
+fn text_min_chars(option: &DynamicOption) -> usize {
+    match option.text_min_chars {
+        0 => DEFAULT_TEXT_MIN_CHARS,
+        n => n as usize,
+    }
+}
+
+/// if the value can be indexed by the field type, an array matches if all elements match
+fn compatible(r#type: field::Type, value: &Value) -> bool {
+    use field::Type::*;
+    match (r#type, value) {
+        (_, Value::Null) | (Geo, _) => true,
+        (Vector, Value::Array(arr)) => arr.iter().all(|v| v.is_number()),
+        (Nested, Value::Object(_)) => true,
+        (Nested, Value::Array(arr)) => arr.iter().all(|v| v.is_object()),
+        (Vector | Nested, _) => false,
+        (t, Value::Array(arr)) => arr.iter().all(|v| compatible(t, v)),
+        (Bool, Value::Bool(_)) => true,
+        (Int, Value::Number(n)) => n.is_i64() || n.is_u64(),
+        (Float, Value::Number(_)) => true,
+        (Date, Value::String(_) | Value::Number(_)) => true,
+        (String | Text | Bytes, Value::String(_)) => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::Arc};
+
+    use proto::core::field::Type;
+
+    use super::*;
+
+    #[test]
+    fn test_infer_fields() {
+        let schema = crate::easy_schema("test", vec![("age".to_string(), Type::Int, None)]);
+        let scope = Scope {
+            user_fields: std::sync::RwLock::new(
+                schema
+                    .fields
+                    .iter()
+                    .map(|(k, v)| (k.clone(), Arc::new(v.clone())))
+                    .collect::<HashMap<_, _>>(),
+            ),
+            schema,
+        };
+        let option = DynamicOption {
+            text_min_chars: 10,
+            int_as_float: false,
+        };
+        let infer = |json: &str| {
+            infer_fields(&scope, &serde_json::from_str(json).unwrap(), &option).map(|fs| {
+                fs.into_iter()
+                    .map(|f| (f.name.clone(), f.r#type()))
+                    .collect::<HashMap<_, _>>()
+            })
+        };
+
+        let fields = infer(
+            r#"{"age": 3, "ok": true, "price": 1.5, "count": [1, 2], "tag": "a", "body": "a long string", "author": {"name": "tom"}, "comments": [{"user": "bob"}, {"user": "ann"}], "_all": "x", "none": null}"#,
+        )
+        .unwrap();
+        assert_eq!(fields.len(), 7);
+        assert_eq!(fields["ok"], Type::Bool);
+        assert_eq!(fields["price"], Type::Float);
+        assert_eq!(fields["count"], Type::Int);
+        assert_eq!(fields["tag"], Type::String);
+        assert_eq!(fields["body"], Type::Text);
+        assert_eq!(fields["author.name"], Type::String);
+        assert_eq!(fields["comments.user"], Type::String);
+
+        // conflicts with the schema or the first element
+        assert!(infer(r#"{"age": "old"}"#).is_err());
+        assert!(infer(r#"{"age": 1.5}"#).is_err());
+        assert!(infer(r#"{"tags": [1, "a"]}"#).is_err());
+    }
+}
@@ -6,15 +6,17 @@ use std::sync::{Arc, RwLock};
 use itertools::Itertools;
 use proto::core::{Field, Query, QueryResult, Record, Schema};
 
+use crate::index_store::seacher::cache::FilterCache;
 use crate::index_store::seacher::Searcher;
 use crate::index_store::segment::SegmentReader;
 use crate::index_store::StoreInfo;
 use crate::job::Job;
 use crate::persist::schema::SchemaStore;
+use crate::persist::snapshot::{self, SnapshotManifest};
 use crate::protocols::sql;
 use crate::store::Store;
 use crate::util::{CoreError, CoreResult};
-use crate::{Action, ActionType, RecordWrapper, Scope};
+use crate::{dynamic, Action, ActionType, RecordWrapper, Scope};
 
 /// Engine is the core of the database, it is responsible for managing the data and the indexes.
 /// It provides methods to insert, update, delete, search and get records.
@@ -27,6 +29,7 @@ pub struct Engine {
     pub store: Store,
     schema_store: SchemaStore,
     is_closeing: AtomicBool,
+    filter_cache: Arc<FilterCache>,
 }
 
 impl Engine {
@@ -89,6 +92,7 @@ impl Engine {
             store: Store::new(scope, path)?,
             schema_store,
             is_closeing: AtomicBool::new(false),
+            filter_cache: Arc::new(FilterCache::default()),
         })
     }
 
@@ -130,8 +134,7 @@ impl Engine {
         json: &[u8],
         marker: Option<String>,
     ) -> CoreResult<CoreError> {
-        let record = RecordWrapper::new(
-            &self.scope,
+        let record = self.record_wrapper(
             Record {
                 name,
                 data: json.to_vec(),
@@ -167,12 +170,43 @@ impl Engine {
         self.mutate_records(
             records
                 .into_iter()
-                .map(|v| Action::into_recordwrapper(v, &self.scope))
+                .map(|v| {
+                    let action = v.action_type();
+                    self.record_wrapper(v.to_record(), action)
+                })
                 .collect_vec(),
             marker,
         )
     }
 
+    /// fields of the record are added first when schema is schemaless
+    fn record_wrapper(&self, record: Record, action: ActionType) -> RecordWrapper {
+        if self.scope.schema.schemaless && action != ActionType::Delete {
+            if let Err(e) = self.dynamic_mapping(&record.data) {
+                return RecordWrapper::with_error(record, action, e);
+            }
+        }
+        RecordWrapper::new(&self.scope, record, action)
+    }
+
+    /// add unknown keys of the record as fields by their values,
+    /// a value conflicting with the type of its field is an error of the record
+    fn dynamic_mapping(&self, data: &[u8]) -> CoreResult<()> {
+        if data.is_empty() {
+            return Ok(());
+        }
+        let json = serde_json::from_slice(data)?;
+        let option = self.scope.schema.dynamic.unwrap_or_default();
+        let fields = dynamic::infer_fields(&self.scope, &json, &option)?;
+        if fields.is_empty() {
+            return Ok(());
+        }
+        self.store.add_index_fields(fields, |user_fields| {
+            self.schema_store
+                .write_user_schema(user_fields.values().map(|v| (**v).clone()).collect())
+        })
+    }
+
     /// Get a record by name from the Engine.
     /// Arguments:
     /// - `record_name` - The name of the record.
@@ -187,8 +221,20 @@ impl Engine {
     /// - `QueryResult` - The result of the search.
     pub fn search(&self, req: Query) -> CoreResult<QueryResult> {
         let query = sql::pbquery_to_query(self.scope(), req)?;
-        let searcher = Searcher::new(self.store.segment_readers());
-        searcher.search_query(query)
+        self.searcher().search_query(query)
+    }
+
+    /// Count records matched by Query struct, only filters are evaluated and
+    /// hits are not fetched, so it is faster than search for total_hits.
+    pub fn count(&self, req: Query) -> CoreResult<u64> {
+        let query = sql::pbquery_to_query(self.scope(), req)?;
+        self.searcher().count(query)
+    }
+
+    /// If any record matched by Query struct, it stops at the first matched segment.
+    pub fn exists(&self, req: Query) -> CoreResult<bool> {
+        let query = sql::pbquery_to_query(self.scope(), req)?;
+        self.searcher().exists(query)
     }
 
     /// Search by SQL string
@@ -198,8 +244,12 @@ impl Engine {
     /// - `QueryResult` - The result of the search.
     pub fn sql(&self, sql: &str) -> CoreResult<QueryResult> {
         let query = sql::sql_to_query(self.scope(), sql)?;
-        let searcher = Searcher::new(self.store.segment_readers());
-        searcher.search_query(query)
+        self.searcher().search_query(query)
+    }
+
+    fn searcher(&self) -> Searcher {
+        Searcher::with_cache(self.store.segment_readers(), self.filter_cache.clone())
+            .with_scope(self.scope.clone())
     }
 
     /// Add a new index field to the Engine. you can add your own index fields, the field name must not existd in schema field,
@@ -217,30 +267,37 @@ impl Engine {
             )));
         }
 
-        let mut user_fields = self.scope.user_fields.write().unwrap();
-
-        if user_fields.contains_key(&name) {
-            return Err(CoreError::InvalidParam(format!(
-                "field:{:?} exist in user schema{:?}",
-                field, self.scope.schema,
-            )));
-        }
-
-        user_fields.insert(name.clone(), Arc::new(field));
+        {
+            let user_fields = self.scope.user_fields.read().unwrap();
 
-        self.schema_store
-            .write_user_schema(user_fields.iter().map(|(_, v)| (**v).clone()).collect())?;
+            if user_fields.contains_key(&name) {
+                return Err(CoreError::InvalidParam(format!(
+                    "field:{:?} exist in user schema{:?}",
+                    field, self.scope.schema,
+                )));
+            }
 
-        match self.store.new_current_segment() {
-            Ok(_) => Ok(()),
-            Err(e) => {
-                log::error!("add index field error:{:?}", e);
-                user_fields.remove(name.as_str());
-                self.schema_store
-                    .write_user_schema(user_fields.iter().map(|(_, v)| (**v).clone()).collect())?;
-                Err(CoreError::Internal("add index field error".to_string()))
+            // a multi-field indexes the value of source in another way, it can not be
+            // the source of another multi-field
+            if let Some(source) = user_fields.get(&field.source) {
+                if !source.source.is_empty() {
+                    return Err(CoreError::InvalidParam(format!(
+                        "field:{:?} source:{:?} is a multi-field too",
+                        name, field.source
+                    )));
+                }
             }
         }
+
+        // the current segment indexes it from now on, locks are taken by store in order
+        self.store.add_index_fields(vec![field], |user_fields| {
+            self.schema_store
+                .write_user_schema(user_fields.values().map(|v| (**v).clone()).collect())
+        })?;
+
+        // a field with the same name may be indexed in another way
+        self.filter_cache.clear();
+        Ok(())
     }
 
     /// Delete an index field from the Engine.
@@ -280,6 +337,9 @@ impl Engine {
         self.schema_store
             .write_user_schema(user_fields.iter().map(|(_, v)| (**v).clone()).collect())?;
 
+        // a field with the same name may be indexed in another way
+        self.filter_cache.clear();
+
         match self.store.new_current_segment() {
             Ok(_) => Ok(()),
             Err(e) => {
@@ -292,6 +352,93 @@ impl Engine {
         }
     }
 
+    /// Alter an index field to new options or type, the field must exist in user schema.
+    /// The new version of the field is indexed from `_source` of all segments in background,
+    /// queries use the old version until it is done and then switch to the new one at once.
+    /// The old version is kept in the field history of schema.
+    /// Arguments:
+    /// - `field` - The new definition of the field.
+    ///   Return:
+    /// - `JoinHandle` - join it to wait for the reindex and get its result.
+    pub fn alter_index_field(
+        self: &Arc<Self>,
+        field: Field,
+    ) -> CoreResult<std::thread::JoinHandle<CoreResult<()>>> {
+        let name = field.name.clone();
+
+        if name.starts_with("_") {
+            return Err(CoreError::InvalidParam(format!(
+                "field:{:?} can not start with _",
+                name,
+            )));
+        }
+
+        use proto::core::field::Type::*;
+        if matches!(field.r#type(), Geo | Vector) {
+            return Err(CoreError::InvalidParam(format!(
+                "field:{:?} type:{} can not be altered",
+                name,
+                field.r#type().as_str_name()
+            )));
+        }
+
+        if let Some(source) = self.scope.get_field(&field.source) {
+            if !source.source.is_empty() {
+                return Err(CoreError::InvalidParam(format!(
+                    "field:{:?} source:{:?} is a multi-field too",
+                    name, field.source
+                )));
+            }
+        }
+
+        let field = self.store.begin_alter(field)?;
+
+        let engine = self.clone();
+        Ok(std::thread::spawn(move || {
+            let result = engine.store.build_index_field(field.clone()).and_then(|_| {
+                // the user schema is the switch on disk, so it is written at last
+                engine.store.switch_index_field(field, |user_fields, old| {
+                    engine.schema_store.append_field_history((*old).clone())?;
+                    engine
+                        .schema_store
+                        .write_user_schema(user_fields.values().map(|v| (**v).clone()).collect())
+                })
+            });
+
+            match result {
+                Ok(old) => {
+                    engine.filter_cache.clear();
+                    if let Err(e) = engine.store.drop_index_field(&old) {
+                        log::error!("drop index of old field:{:?} error:{:?}", old, e);
+                    }
+                    Ok(())
+                }
+                Err(e) => {
+                    log::error!("alter index field:{:?} error:{:?}", name, e);
+                    engine.store.abort_alter(&name);
+                    Err(e)
+                }
+            }
+        }))
+    }
+
+    /// All versions of the field, the old ones first and the last is in use.
+    pub fn field_history(&self, name: &str) -> CoreResult<Vec<Field>> {
+        let current = self.scope.get_field(name).ok_or_else(|| {
+            CoreError::NotExisted(format!("field:{:?} not exist in user schema", name))
+        })?;
+
+        let mut history = self
+            .schema_store
+            .read_field_history()?
+            .fields
+            .into_iter()
+            .filter(|f| f.name == name)
+            .collect_vec();
+        history.push((*current).clone());
+        Ok(history)
+    }
+
     /// Persist the Engine data to disk.
     /// make new current segment and persist hot segment in freezed list to warm segment.
     /// This will write the data to the source db and update the index db.
@@ -301,8 +448,108 @@ impl Engine {
         Job::persist(self.clone(), true)
     }
 
+    /// Snapshot the Engine to dest without stopping writes.
+    /// The hot segments are persisted first, then files of all warm segments are hard linked
+    /// to dest, with schema and a manifest. Records written after the persist are not in it.
+    /// Arguments:
+    /// - `dest` - The dir of the snapshot, it must not exist.
+    ///   Return:
+    /// - `SnapshotManifest` - The segments in the snapshot.
+    pub fn snapshot(self: &Arc<Engine>, dest: &str) -> CoreResult<SnapshotManifest> {
+        let dest = PathBuf::from(dest);
+        if dest.exists() {
+            return Err(CoreError::Existed(format!(
+                "snapshot path:{:?} already exist",
+                dest
+            )));
+        }
+
+        self.persist()?;
+
+        // warm segments and the user schema are not changed in the meantime, an altered
+        // field is switched and its index is written under the lock too
+        let lock = crate::job::PERSIST_LOCK.lock().unwrap();
+        let mut segments = Vec::new();
+        for reader in self.segment_readers() {
+            if let SegmentReader::Warm(w) = reader {
+                let name = format!("{}-{}", w.start(), w.end());
+                snapshot::link_dir(
+                    &self.store.base_path().join("segments").join(&name),
+                    &dest.join("segments").join(&name),
+                )?;
+                segments.push((w.start(), w.end()));
+            }
+        }
+        std::fs::create_dir_all(dest.join("segments"))?;
+
+        let schema_store = SchemaStore::new(&dest)?;
+        schema_store.write_schema(&self.scope.schema)?;
+        schema_store.write_field_history(&self.schema_store.read_field_history()?)?;
+        schema_store.write_user_schema(
+            self.scope
+                .user_fields
+                .read()
+                .unwrap()
+                .values()
+                .map(|v| (**v).clone())
+                .collect(),
+        )?;
+        drop(lock);
+
+        segments.sort();
+        let manifest = SnapshotManifest {
+            engine: self.scope.schema.name.clone(),
+            created_at: crate::util::now_millis(),
+            segments,
+            path: dest.clone(),
+        };
+        snapshot::write_manifest(&dest, &manifest)?;
+        Ok(manifest)
+    }
+
     pub fn info(&self) -> CoreResult<StoreInfo> {
-        self.store.info()
+        let mut info = self.store.info()?;
+        info.filter_cache = self.filter_cache.info();
+        Ok(info)
+    }
+
+    /// Set the size limit in bytes of the filter cache, 0 disables it.
+    /// Filter bitmaps are shared by queries, entries of disk segments are valid forever,
+    /// entries of the hot segment are out of date once records are written or deleted.
+    pub fn set_filter_cache_limit(&self, bytes: usize) {
+        self.filter_cache.set_limit(bytes);
+    }
+
+    /// Reload the stopword and synonym dicts of all fulltext fields.
+    /// Queries use the new dicts at once, records written after are indexed with them.
+    /// Return the fields whose index time dicts changed, segments indexed before are
+    /// listed as stale in `SegmentInfo.stale_fields` and need to be rebuilt.
+    pub fn reload_analyzers(&self) -> CoreResult<Vec<String>> {
+        self.reload_fields(|_| true)
+    }
+
+    /// reload fields whose Api or File dicts reach the poll interval, it is called by job
+    pub(crate) fn poll_analyzers(&self) -> CoreResult<Vec<String>> {
+        let analyzers = self.store.analyzers();
+        self.reload_fields(|name| analyzers.get(name).is_some_and(|a| a.reload_due()))
+    }
+
+    fn reload_fields(&self, filter: impl Fn(&str) -> bool) -> CoreResult<Vec<String>> {
+        let fields = self
+            .scope
+            .user_fields
+            .read()
+            .unwrap()
+            .values()
+            .filter(|f| f.r#type() == proto::core::field::Type::Text && filter(&f.index_name()))
+            .cloned()
+            .collect_vec();
+
+        if fields.is_empty() {
+            return Ok(vec![]);
+        }
+
+        self.store.reload_analyzers(fields)
     }
 }
 
@@ -322,18 +569,15 @@ impl Engine {
 
 #[cfg(test)]
 mod tests {
-    use crate::Engine;
+    use crate::{
+        test_util::{append, test_engine, TestDir},
+        Engine,
+    };
     use proto::core::{field::Type as FieldType, Field, Schema};
-    use std::{collections::HashMap, path::PathBuf, sync::Arc};
-
-    fn setup_test_engine() -> Arc<Engine> {
-        // Create a temporary directory for testing
-        let test_dir = PathBuf::from("./calmcore_test");
-        let _ = std::fs::remove_dir_all(&test_dir); // Clean up any previous test data
-        std::fs::create_dir_all(&test_dir).unwrap();
+    use std::{collections::HashMap, sync::Arc};
 
-        // Define schema
-        let schema = Schema {
+    fn test_schema() -> Schema {
+        Schema {
             name: "test_schema".to_string(),
             metadata: Default::default(), // Add missing metadata field
             fields: {
@@ -344,6 +588,9 @@ mod tests {
                         name: "id".to_string(),
                         r#type: FieldType::Int as i32,
                         option: None,
+                        source: String::new(),
+                        array: false,
+                        version: 0,
                     },
                 );
                 map.insert(
@@ -352,6 +599,9 @@ mod tests {
                         name: "name".to_string(),
                         r#type: FieldType::String as i32,
                         option: None,
+                        source: String::new(),
+                        array: false,
+                        version: 0,
                     },
                 );
                 map.insert(
@@ -360,22 +610,20 @@ mod tests {
                         name: "age".to_string(),
                         r#type: FieldType::Int as i32,
                         option: None,
+                        source: String::new(),
+                        array: false,
+                        version: 0,
                     },
                 );
                 map
             },
             schemaless: false,
-        };
-
-        // Create and return engine
-        Arc::new(Engine::create(test_dir.to_str().unwrap(), schema).unwrap())
+            dynamic: None,
+            validation: 0,
+        }
     }
 
-    #[test]
-    fn test_insert_and_query() {
-        let engine = setup_test_engine();
-
-        // Insert test data
+    fn insert_test_data(engine: &Engine) {
         for i in 0..100 {
             let test_data = format!(
                 r#"{{
@@ -388,14 +636,14 @@ mod tests {
                 20 + (i % 10)
             );
 
-            engine
-                .mutate_json(
-                    crate::ActionType::Append,
-                    format!("record{}", i),
-                    test_data.as_bytes(),
-                )
-                .unwrap();
+            append(engine, &format!("record{}", i), &test_data);
         }
+    }
+
+    #[test]
+    fn test_insert_and_query() {
+        let (_test_dir, engine) = test_engine("query", test_schema());
+        insert_test_data(&engine);
 
         // Test simple query
         let sql = "SELECT id, name FROM test_space WHERE age = 25 limit 5";
@@ -416,39 +664,15 @@ mod tests {
         let sql = "SELECT id, name FROM test_space WHERE age >= 30";
         let result = engine.sql(sql).unwrap();
         assert_eq!(result.total_hits, 0);
-
-        // Clean up
-        std::fs::remove_dir_all("./calmcore_test").unwrap();
     }
 
     #[test]
     fn test_insert_and_query_score() {
-        let engine = setup_test_engine();
-
-        // Insert test data
-        for i in 0..100 {
-            let test_data = format!(
-                r#"{{
-                "id": {},
-                "name": "User{}",
-                "age": {}
-            }}"#,
-                i,
-                i,
-                20 + (i % 10)
-            );
-
-            engine
-                .mutate_json(
-                    crate::ActionType::Append,
-                    format!("record{}", i),
-                    test_data.as_bytes(),
-                )
-                .unwrap();
-        }
+        let (_test_dir, engine) = test_engine("score", test_schema());
+        insert_test_data(&engine);
 
         // Test simple query
-        let sql = "SELECT id, name FROM test_space WHERE  (name = fn('User25', score=100)  or name = fn('User26', score=50)) order by _score asc  limit 5";
+        let sql = "SELECT id, name FROM test_space WHERE  (name = score('User25', score=100)  or name = score('User26', score=50)) order by _score asc  limit 5";
         let result = engine.sql(sql).unwrap();
 
         println!("{:?}---{:?}", result.total_hits, result.hits.len());
@@ -460,8 +684,1123 @@ mod tests {
                 String::from_utf8(hi.record.as_ref().unwrap().data.clone()).unwrap()
             );
         }
+        assert_eq!(result.total_hits, 2);
+        assert!(result.hits[0].score < result.hits[1].score);
+    }
+
+    #[test]
+    fn test_reload_analyzers() {
+        use proto::core::{
+            dict::Protocol,
+            field::{fulltext_option, FulltextOption, Option as FieldOption},
+            Dict,
+        };
+
+        let test_dir = TestDir::new("reload");
+
+        let dict_path = test_dir.join("synonyms.txt");
+        std::fs::write(&dict_path, "").unwrap();
+
+        let schema = Schema {
+            name: "test_schema".to_string(),
+            metadata: Default::default(),
+            fields: HashMap::from([(
+                "content".to_string(),
+                Field {
+                    name: "content".to_string(),
+                    r#type: FieldType::Text as i32,
+                    option: Some(FieldOption::Fulltext(FulltextOption {
+                        tokenizer: fulltext_option::Tokenizer::Standard as i32,
+                        filters: vec![fulltext_option::Filter::Lowercase as i32],
+                        stopwords: None,
+                        synonyms: Some(Dict {
+                            name: "synonyms".to_string(),
+                            protocol: Protocol::File as i32,
+                            value: dict_path.to_string_lossy().to_string(),
+                            reload_interval_secs: 0,
+                        }),
+                        synonym_expand: fulltext_option::SynonymExpand::Index as i32,
+                    })),
+                    source: String::new(),
+                    array: false,
+                    version: 0,
+                },
+            )]),
+            schemaless: false,
+            dynamic: None,
+            validation: 0,
+        };
+
+        let engine = Engine::create(test_dir.to_str().unwrap(), schema).unwrap();
+        let write = |name: &str, content: &str| {
+            append(&engine, name, &format!(r#"{{"content": "{}"}}"#, content));
+        };
+        let sql = "SELECT * FROM test WHERE content = text('dog')";
+
+        write("r1", "quick fox");
+        assert_eq!(engine.sql(sql).unwrap().total_hits, 0);
+
+        // nothing changed
+        assert!(engine.reload_analyzers().unwrap().is_empty());
+
+        std::fs::write(&dict_path, "fox,dog").unwrap();
+        assert_eq!(engine.reload_analyzers().unwrap(), vec!["content"]);
+
+        // old record was indexed without the synonym, its segment is stale
+        let stale = engine
+            .info()
+            .unwrap()
+            .segments
+            .into_iter()
+            .filter(|s| !s.stale_fields.is_empty())
+            .count();
+        assert_eq!(stale, 1);
+        assert_eq!(engine.sql(sql).unwrap().total_hits, 0);
+
+        write("r2", "lazy fox");
+        assert_eq!(engine.sql(sql).unwrap().total_hits, 1);
+    }
+
+    #[test]
+    fn test_function_score() {
+        let schema = crate::easy_schema(
+            "test",
+            vec![
+                ("title".to_string(), FieldType::Text, None),
+                ("likes".to_string(), FieldType::Int, None),
+            ],
+        );
+        let (_test_dir, engine) = test_engine("function_score", schema);
+        for (name, data) in [
+            ("r1", r#"{"title": "rust", "likes": 100, "price": 90}"#),
+            (
+                "r2",
+                r#"{"title": "rust rust book", "likes": 1, "price": 130}"#,
+            ),
+            ("r3", r#"{"title": "go", "likes": 0, "price": 101}"#),
+        ] {
+            append(&engine, name, data);
+        }
+
+        let hits = |sql: &str| {
+            engine
+                .sql(sql)
+                .unwrap()
+                .hits
+                .into_iter()
+                .map(|h| (h.record.unwrap().name, h.score))
+                .collect::<Vec<_>>()
+        };
+        let names = |sql: &str| hits(sql).into_iter().map(|h| h.0).collect::<Vec<_>>();
+
+        for _ in 0..2 {
+            assert_eq!(
+                names(
+                    "SELECT * FROM test WHERE title = 'rust' ORDER BY _score * log(1 + likes) DESC"
+                ),
+                vec!["r1", "r2"]
+            );
+            assert_eq!(
+                names("SELECT * FROM test WHERE title = 'rust' ORDER BY _score / (1 + likes) DESC"),
+                vec!["r2", "r1"]
+            );
+
+            // price is not an index field, it is read from source
+            let result =
+                hits("SELECT * FROM test ORDER BY gauss(price, origin=100, scale=10) DESC");
+            assert_eq!(
+                result.iter().map(|h| h.0.as_str()).collect::<Vec<_>>(),
+                vec!["r3", "r1", "r2"]
+            );
+            assert!((result[1].1 - 0.5).abs() < 1e-6);
+
+            let random = names("SELECT * FROM test ORDER BY random_score(seed=7) DESC");
+            assert_eq!(random.len(), 3);
+            assert_eq!(
+                random,
+                names("SELECT * FROM test ORDER BY random_score(seed=7) DESC")
+            );
+
+            engine.persist().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_explain() {
+        let schema = crate::easy_schema(
+            "test",
+            vec![
+                ("age".to_string(), FieldType::Int, None),
+                ("content".to_string(), FieldType::Text, None),
+            ],
+        );
+        let (_test_dir, engine) = test_engine("explain", schema);
+        for (name, data) in [
+            ("r1", r#"{"age": 10, "content": "rust search engine"}"#),
+            ("r2", r#"{"age": 20, "content": "rust rust"}"#),
+            ("r3", r#"{"age": 30, "content": "go"}"#),
+        ] {
+            append(&engine, name, data);
+        }
+
+        for _ in 0..2 {
+            let result = engine
+                .sql("EXPLAIN SELECT * FROM test WHERE content = score('rust', boost=2) AND age > 5 ORDER BY _score DESC")
+                .unwrap();
+            assert_eq!(result.hits.len(), 2);
+            for hit in result.hits.iter() {
+                let e = hit.explanation.as_ref().unwrap();
+                assert!((e.value - hit.score).abs() < 1e-6);
+                let text = format!("{:?}", e);
+                assert!(
+                    text.contains("rust") && text.contains("in content: idf="),
+                    "{}",
+                    text
+                );
+                assert!(text.contains("boost 2 times"), "{}", text);
+            }
+
+            let profile = result.profile.unwrap();
+            for phase in ["plan", "filter", "stream", "topn", "fetch"] {
+                assert!(profile.timings.contains_key(phase), "{}", phase);
+            }
+            let plans = profile
+                .segments
+                .iter()
+                .filter_map(|s| s.plan.as_ref())
+                .filter(|p| p.value > 0.0)
+                .collect::<Vec<_>>();
+            assert_eq!(plans.len(), 1);
+            assert_eq!(plans[0].value, 2.0);
+
+            // the same term is got from cache
+            let result = engine
+                .sql("EXPLAIN SELECT * FROM test WHERE age = 10 OR age = 10")
+                .unwrap();
+            let profile = result.profile.unwrap();
+            assert!(profile.segments.iter().any(|s| s.cache_hits > 0));
+
+            // no explain no profile
+            let result = engine.sql("SELECT * FROM test WHERE age > 5").unwrap();
+            assert!(result.profile.is_none());
+            assert!(result.hits.iter().all(|h| h.explanation.is_none()));
+
+            engine.persist().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_filter_cache() {
+        let schema = crate::easy_schema("test", vec![("age".to_string(), FieldType::Int, None)]);
+        let (_test_dir, engine) = test_engine("filter_cache", schema);
+        let write = |name: &str, age: i32| {
+            append(&engine, name, &format!(r#"{{"age": {}}}"#, age));
+        };
+        let count = |sql: &str| engine.sql(sql).unwrap().total_hits;
+        let stats = || {
+            let info = engine.info().unwrap().filter_cache;
+            (info.hits, info.misses)
+        };
+
+        write("r1", 10);
+        write("r2", 20);
+        engine.persist().unwrap();
+        write("r3", 30);
+
+        let sql = "SELECT * FROM test WHERE age > 5";
+        assert_eq!(count(sql), 3);
+        let (hits, misses) = stats();
+        assert_eq!(count(sql), 3);
+        // both segments are got from cache
+        assert_eq!(stats(), (hits + 2, misses));
+
+        // hot segment changed, only the disk one is still valid
+        write("r4", 40);
+        assert_eq!(count(sql), 4);
+        assert_eq!(stats(), (hits + 3, misses + 1));
+
+        // opt out
+        assert_eq!(count("SELECT SQL_NO_CACHE * FROM test WHERE age > 5"), 4);
+        assert_eq!(stats(), (hits + 3, misses + 1));
+
+        let info = engine.info().unwrap().filter_cache;
+        assert!(info.entries > 0 && info.size_bytes > 0);
+        assert!(info.hit_rate > 0.0);
+
+        engine.set_filter_cache_limit(0);
+        assert_eq!(engine.info().unwrap().filter_cache.entries, 0);
+        assert_eq!(count(sql), 4);
+        assert_eq!(engine.info().unwrap().filter_cache.entries, 0);
+    }
+
+    #[test]
+    fn test_count() {
+        let schema = crate::easy_schema(
+            "test",
+            vec![
+                ("age".to_string(), FieldType::Int, None),
+                ("content".to_string(), FieldType::Text, None),
+            ],
+        );
+        let (_test_dir, engine) = test_engine("count", schema);
+        let write = |name: &str, age: i32, content: &str| {
+            append(
+                &engine,
+                name,
+                &format!(r#"{{"age": {}, "content": "{}"}}"#, age, content),
+            );
+        };
+        let query = |q: &str| proto::core::Query {
+            query: q.to_string(),
+            ..Default::default()
+        };
+
+        write("r1", 10, "rust");
+        write("r2", 20, "go");
+        engine.persist().unwrap();
+        write("r3", 30, "rust book");
+
+        assert_eq!(engine.count(query("")).unwrap(), 3);
+        assert_eq!(engine.count(query("age > 15")).unwrap(), 2);
+        assert_eq!(engine.count(query("content = 'rust'")).unwrap(), 2);
+        assert_eq!(engine.count(query("age > 100")).unwrap(), 0);
+
+        assert!(engine.exists(query("age = 30")).unwrap());
+        assert!(engine.exists(query("age = 10")).unwrap());
+        assert!(!engine.exists(query("age = 40")).unwrap());
+
+        let result = engine
+            .sql("SELECT COUNT(*) FROM test WHERE content = 'rust' AND age < 20")
+            .unwrap();
+        assert_eq!(result.total_hits, 1);
+        assert!(result.hits.is_empty());
+        assert_eq!(
+            engine.sql("SELECT count(*) FROM test").unwrap().total_hits,
+            3
+        );
+    }
+
+    #[test]
+    fn test_total_hits() {
+        use proto::core::query_result::Relation;
+
+        let schema = crate::easy_schema(
+            "test",
+            vec![
+                ("age".to_string(), FieldType::Int, None),
+                ("content".to_string(), FieldType::Text, None),
+            ],
+        );
+        let (_test_dir, engine) = test_engine("total_hits", schema);
+        let write = |name: &str, age: i32, content: &str| {
+            append(
+                &engine,
+                name,
+                &format!(r#"{{"age": {}, "content": "{}"}}"#, age, content),
+            );
+        };
+        let total = |sql: &str| {
+            let result = engine.sql(sql).unwrap();
+            (result.total_hits, result.total_hits_relation())
+        };
+
+        write("r1", 10, "quick brown fox");
+        write("r2", 20, "brown quick fox");
+        write("r3", 30, "quick brown dog");
+        engine.persist().unwrap();
+        write("r4", 40, "lazy dog");
+        write("r5", 50, "quick fox brown");
+
+        // all tokens are in r1 r2 r3 r5, but the phrase is only in r1 r3
+        let phrase = "content = phrase('quick brown')";
+        for sql in [
+            format!("SELECT * FROM test WHERE {}", phrase),
+            format!("SELECT * FROM test WHERE {} LIMIT 1", phrase),
+            format!(
+                "SELECT * FROM test WHERE {} ORDER BY age DESC LIMIT 1",
+                phrase
+            ),
+        ] {
+            assert_eq!(total(&sql), (2, Relation::Eq), "{}", sql);
+        }
+        let query = proto::core::Query {
+            query: phrase.to_string(),
+            ..Default::default()
+        };
+        assert_eq!(engine.count(query.clone()).unwrap(), 2);
+        assert!(engine.exists(query).unwrap());
+
+        // total does not depend on limit or order by
+        for sql in [
+            "SELECT * FROM test WHERE age > 0 LIMIT 1",
+            "SELECT * FROM test WHERE age > 0 ORDER BY age LIMIT 1",
+            "SELECT * FROM test LIMIT 1",
+            "SELECT * FROM test ORDER BY age LIMIT 1",
+        ] {
+            assert_eq!(total(sql), (5, Relation::Eq), "{}", sql);
+        }
+
+        // lower bound
+        for sql in [
+            "SELECT track_total_hits(2), * FROM test WHERE age > 0 LIMIT 1",
+            "SELECT track_total_hits(2), * FROM test WHERE age > 0 ORDER BY age LIMIT 1",
+            "SELECT track_total_hits(2), * FROM test LIMIT 1",
+        ] {
+            let result = engine.sql(sql).unwrap();
+            assert_eq!(result.hits.len(), 1);
+            assert_eq!(
+                (result.total_hits, result.total_hits_relation()),
+                (2, Relation::Gte),
+                "{}",
+                sql
+            );
+        }
+        assert_eq!(
+            total("SELECT track_total_hits(5), * FROM test WHERE age > 0 LIMIT 1"),
+            (5, Relation::Eq)
+        );
+        assert_eq!(
+            total("SELECT track_total_hits(10), * FROM test WHERE age > 0 LIMIT 1"),
+            (5, Relation::Eq)
+        );
+
+        let result = engine
+            .search(proto::core::Query {
+                query: "age > 0".to_string(),
+                limit: 1,
+                track_total_hits: 3,
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(
+            (result.total_hits, result.total_hits_relation()),
+            (3, Relation::Gte)
+        );
+    }
+
+    #[test]
+    fn test_collapse() {
+        let schema = crate::easy_schema(
+            "test",
+            vec![
+                ("age".to_string(), FieldType::Int, None),
+                ("city".to_string(), FieldType::String, None),
+            ],
+        );
+        let (_test_dir, engine) = test_engine("collapse", schema);
+        let write = |name: &str, data: &str| {
+            append(&engine, name, data);
+        };
+        let names = |hits: &[proto::core::Hit]| {
+            hits.iter()
+                .map(|h| h.record.as_ref().unwrap().name.clone())
+                .collect::<Vec<_>>()
+        };
+
+        write("r1", r#"{"age": 10, "city": "beijing"}"#);
+        write("r2", r#"{"age": 30, "city": "shanghai"}"#);
+        write("r3", r#"{"age": 20, "city": "beijing"}"#);
+        engine.persist().unwrap();
+        write("r4", r#"{"age": 40, "city": "beijing"}"#);
+        write("r5", r#"{"age": 5, "city": "shanghai"}"#);
+        write("r6", r#"{"age": 50}"#);
+
+        let result = engine.sql("SELECT * FROM test COLLAPSE BY city").unwrap();
+        assert_eq!(names(&result.hits), vec!["r1", "r2", "r6"]);
+        assert_eq!(result.total_hits, 6);
+        assert!(result.hits.iter().all(|h| h.inner_hits.is_empty()));
+
+        let result = engine
+            .sql("SELECT * FROM test WHERE age < 45 COLLAPSE BY city INNER_HITS 2 ORDER BY age LIMIT 1, 10")
+            .unwrap();
+        assert_eq!(names(&result.hits), vec!["r1"]);
+        assert_eq!(names(&result.hits[0].inner_hits), vec!["r1", "r3"]);
+        assert_eq!(result.total_hits, 5);
+
+        let result = engine
+            .search(proto::core::Query {
+                query: "age > 0".to_string(),
+                order_by: vec!["age asc".to_string()],
+                limit: 10,
+                collapse: Some(proto::core::CollapseOption {
+                    field: "city".to_string(),
+                    inner_hits: 5,
+                }),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(names(&result.hits), vec!["r5", "r1", "r6"]);
+        assert_eq!(names(&result.hits[0].inner_hits), vec!["r5", "r2"]);
+        assert_eq!(names(&result.hits[1].inner_hits), vec!["r1", "r3", "r4"]);
+    }
+
+    #[test]
+    fn test_more_like_this() {
+        let schema = crate::easy_schema(
+            "test",
+            vec![
+                ("title".to_string(), FieldType::Text, None),
+                ("body".to_string(), FieldType::Text, None),
+            ],
+        );
+        let (_test_dir, engine) = test_engine("more_like_this", schema);
+        let write = |name: &str, title: &str, body: &str| {
+            append(
+                &engine,
+                name,
+                &format!(r#"{{"title": "{}", "body": "{}"}}"#, title, body),
+            );
+        };
+        let names = |sql: &str| {
+            engine
+                .sql(sql)
+                .unwrap()
+                .hits
+                .into_iter()
+                .map(|h| h.record.unwrap().name)
+                .collect::<Vec<_>>()
+        };
+
+        write("r1", "rust search engine", "fast full text search in rust");
+        write("r2", "rust web server", "async web framework");
+        write("r3", "cooking pasta", "boil water and add salt");
+        engine.persist().unwrap();
+        write(
+            "r4",
+            "search engine internals",
+            "inverted index and bm25 search",
+        );
+        write("r5", "gardening", "water the plants");
+
+        // the liked record is excluded, records sharing terms are found
+        let hits = names(
+            "SELECT * FROM test WHERE more_like_this(like='r1', fields=['title','body']) ORDER BY _score DESC",
+        );
+        assert_eq!(hits.len(), 2);
+        assert!(!hits.contains(&"r1".to_string()));
+        assert_eq!(hits[0], "r4");
+        assert!(hits.contains(&"r2".to_string()));
+
+        // only the best term
+        let hits = names(
+            "SELECT * FROM test WHERE more_like_this(like='r1', fields=['title','body'], max_query_terms=1)",
+        );
+        assert_eq!(hits, vec!["r4"]);
+
+        // free text
+        let hits = names("SELECT * FROM test WHERE more_like_this(like='boil some water')");
+        assert_eq!(hits, vec!["r3", "r5"]);
+
+        // with filters and count
+        let hits = names(
+            "SELECT * FROM test WHERE more_like_this(like='r1') AND title = 'web' ORDER BY _score DESC",
+        );
+        assert_eq!(hits, vec!["r2"]);
+        let count = engine
+            .count(proto::core::Query {
+                query: "more_like_this(like='r4', fields='title')".to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(count, 1);
+    }
+
+    #[test]
+    fn test_dotted_path() {
+        let schema = crate::easy_schema(
+            "test",
+            vec![
+                ("author.name".to_string(), FieldType::String, None),
+                ("author.age".to_string(), FieldType::Int, None),
+                ("comments.text".to_string(), FieldType::Text, None),
+                ("comments.user".to_string(), FieldType::String, None),
+            ],
+        );
+        let (_test_dir, engine) = test_engine("dotted_path", schema);
+        let write = |name: &str, data: &str| {
+            append(&engine, name, data);
+        };
+        let names = |sql: &str| {
+            engine
+                .sql(sql)
+                .unwrap()
+                .hits
+                .into_iter()
+                .map(|h| h.record.unwrap().name)
+                .collect::<Vec<_>>()
+        };
+
+        write(
+            "r1",
+            r#"{"author": {"name": "tom", "age": 30}, "comments": [{"user": "ann", "text": "great book"}, {"user": "bob", "text": "boring"}]}"#,
+        );
+        write(
+            "r2",
+            r#"{"author": {"name": "jerry", "age": 20}, "comments": [{"user": "bob", "text": "funny story"}]}"#,
+        );
+        engine.persist().unwrap();
+        write("r3", r#"{"author": {"name": "tom", "age": 40}}"#);
+
+        assert_eq!(
+            names("SELECT * FROM test WHERE author.age > 25 ORDER BY author.age"),
+            vec!["r1", "r3"]
+        );
+        assert_eq!(
+            names("SELECT * FROM test ORDER BY author.age LIMIT 1"),
+            vec!["r2"]
+        );
+        assert_eq!(
+            names("SELECT * FROM test COLLAPSE BY author.name ORDER BY author.age"),
+            vec!["r2", "r1"]
+        );
+
+        let result = engine
+            .sql("SELECT author.name FROM test WHERE author.age < 25")
+            .unwrap();
+        let data: serde_json::Value =
+            serde_json::from_slice(&result.hits[0].record.as_ref().unwrap().data).unwrap();
+        assert_eq!(data, serde_json::json!({"author": {"name": "jerry"}}));
+    }
+
+    #[test]
+    fn test_schemaless() {
+        let mut schema =
+            crate::easy_schema("test", vec![("age".to_string(), FieldType::Int, None)]);
+        schema.schemaless = true;
+        schema.dynamic = Some(proto::core::DynamicOption {
+            text_min_chars: 16,
+            int_as_float: false,
+        });
+        let (test_dir, engine) = test_engine("schemaless", schema);
+        let write = |name: &str, data: &str| {
+            engine
+                .mutate_json(
+                    crate::ActionType::Append,
+                    name.to_string(),
+                    data.as_bytes(),
+                    None,
+                )
+                .unwrap()
+        };
+        let names = |engine: &Engine, sql: &str| {
+            let mut names = engine
+                .sql(sql)
+                .unwrap()
+                .hits
+                .into_iter()
+                .map(|h| h.record.unwrap().name)
+                .collect::<Vec<_>>();
+            names.sort();
+            names
+        };
+
+        assert!(write("r1", r#"{"age": 1, "color": "red"}"#).is_ok());
+        engine.persist().unwrap();
+        // new fields after the segment is written
+        assert!(write(
+            "r2",
+            r#"{"age": 2, "color": "blue", "price": 9.5, "author": {"name": "tom"}, "body": "a long text about rust search"}"#,
+        )
+        .is_ok());
+
+        let field = |name: &str| engine.scope().get_field(name).map(|f| f.r#type());
+        assert_eq!(field("color"), Some(FieldType::String));
+        assert_eq!(field("price"), Some(FieldType::Float));
+        assert_eq!(field("author.name"), Some(FieldType::String));
+        assert_eq!(field("body"), Some(FieldType::Text));
+
+        assert_eq!(
+            names(&engine, "SELECT * FROM test WHERE color = 'red'"),
+            vec!["r1"]
+        );
+        assert_eq!(
+            names(&engine, "SELECT * FROM test WHERE price > 1"),
+            vec!["r2"]
+        );
+        assert_eq!(
+            names(&engine, "SELECT * FROM test WHERE author.name = 'tom'"),
+            vec!["r2"]
+        );
+        assert_eq!(
+            names(&engine, "SELECT * FROM test WHERE body = 'rust'"),
+            vec!["r2"]
+        );
+
+        // conflicting types are errors of records
+        assert!(!write("r3", r#"{"age": "old"}"#).is_ok());
+        assert!(!write("r4", r#"{"color": 1}"#).is_ok());
+        assert!(!write("r5", r#"{"tags": ["a", 1]}"#).is_ok());
+        assert!(field("tags").is_none());
+
+        // fields are in user schema
+        engine.persist().unwrap();
+        drop(engine);
+        let engine = Engine::open(test_dir.to_str().unwrap(), "test").unwrap();
+        assert_eq!(
+            names(
+                &engine,
+                "SELECT * FROM test WHERE price > 1 AND author.name = 'tom'"
+            ),
+            vec!["r2"]
+        );
+    }
+
+    #[test]
+    fn test_query_disk_segment() {
+        let sub = |name: &str, r#type: FieldType| Field {
+            name: name.to_string(),
+            r#type: r#type as i32,
+            ..Default::default()
+        };
+        let schema = crate::easy_schema(
+            "test",
+            vec![
+                ("name".to_string(), FieldType::String, None),
+                ("age".to_string(), FieldType::Int, None),
+                ("content".to_string(), FieldType::Text, None),
+                ("author.name".to_string(), FieldType::String, None),
+                (
+                    "variants".to_string(),
+                    FieldType::Nested,
+                    Some(proto::core::field::Option::Nested(
+                        proto::core::field::NestedOption {
+                            fields: vec![
+                                sub("color", FieldType::String),
+                                sub("size", FieldType::Int),
+                            ],
+                        },
+                    )),
+                ),
+            ],
+        );
+        let (_test_dir, engine) = test_engine("disk_segment", schema);
+        for (name, data) in [
+            (
+                "r1",
+                r#"{"name": "Apple", "age": 10, "content": "quick brown fox", "author": {"name": "tom"}, "variants": [{"color": "red", "size": 1}, {"color": "blue", "size": 2}]}"#,
+            ),
+            (
+                "r2",
+                r#"{"name": "apricot", "age": 20, "content": "brown quick fox", "variants": {"color": "red", "size": 2}}"#,
+            ),
+            (
+                "r3",
+                r#"{"name": "banana", "content": "lazy dog", "author": {"name": "tom"}}"#,
+            ),
+        ] {
+            append(&engine, name, data);
+        }
+        let names = |filter: &str| {
+            let mut names = engine
+                .sql(&format!("SELECT * FROM test WHERE {}", filter))
+                .unwrap()
+                .hits
+                .into_iter()
+                .map(|h| h.record.unwrap().name)
+                .collect::<Vec<_>>();
+            names.sort();
+            names
+        };
+
+        // queries of plan are checked on memory segments, they are the same on disk
+        let cases = [
+            ("content = fuzzy('quikc', distance=1)", vec!["r1", "r2"]),
+            ("content = phrase('quick brown')", vec!["r1"]),
+            ("name LIKE 'ap%'", vec!["r2"]),
+            ("content = prefix('bro')", vec!["r1", "r2"]),
+            ("age IS NULL", vec!["r3"]),
+            ("NOT age = 10", vec!["r2", "r3"]),
+            (
+                "FILTER(author.name = 'tom') AND content = 'fox'",
+                vec!["r1"],
+            ),
+            ("nested(variants, color = 'red' AND size = 2)", vec!["r2"]),
+        ];
+        for persisted in [false, true] {
+            for (filter, expected) in cases.iter() {
+                assert_eq!(names(filter), *expected, "{} {}", filter, persisted);
+            }
+            engine.persist().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_date() {
+        let schema = crate::easy_schema(
+            "test",
+            vec![
+                ("title".to_string(), FieldType::Text, None),
+                (
+                    "created".to_string(),
+                    FieldType::Date,
+                    Some(proto::core::field::Option::Date(
+                        proto::core::field::DateOption {
+                            formats: vec!["%Y/%m/%d".to_string()],
+                            time_zone: "+08:00".to_string(),
+                        },
+                    )),
+                ),
+            ],
+        );
+        let (_test_dir, engine) = test_engine("date", schema);
+        let write = |name: &str, data: &str| {
+            append(&engine, name, data);
+        };
+        let names = |sql: &str| {
+            engine
+                .sql(sql)
+                .unwrap()
+                .hits
+                .into_iter()
+                .map(|h| h.record.unwrap().name)
+                .collect::<Vec<_>>()
+        };
+        let buckets = |sql: &str| {
+            engine
+                .sql(sql)
+                .unwrap()
+                .buckets
+                .into_iter()
+                .map(|b| (b.key_as_string, b.doc_count))
+                .collect::<Vec<_>>()
+        };
+
+        write(
+            "r1",
+            r#"{"title": "rust", "created": "2024-01-01T10:00:00Z"}"#,
+        );
+        write("r2", r#"{"title": "rust", "created": 1704153600000}"#);
+        write("r3", r#"{"title": "go", "created": "2024/01/02"}"#);
+        engine.persist().unwrap();
+        write(
+            "r4",
+            r#"{"title": "go", "created": "2024-01-03T01:00:00+08:00"}"#,
+        );
+        write("r5", r#"{"title": "rust", "created": "2024-02-10"}"#);
+
+        // literals without zone are in the zone of field
+        assert_eq!(
+            names("SELECT * FROM test WHERE created >= '2024-01-02' ORDER BY created"),
+            vec!["r3", "r2", "r4", "r5"]
+        );
+        assert_eq!(
+            names("SELECT * FROM test WHERE created BETWEEN '2024-01-01T00:00:00Z' AND '2024-01-01T00:00:00Z||+1d'"),
+            vec!["r1", "r2", "r3"]
+        );
+        assert_eq!(
+            names("SELECT * FROM test WHERE created = 1704153600000"),
+            vec!["r2"]
+        );
+        assert_eq!(
+            names("SELECT * FROM test WHERE created < 'now-7d/d'").len(),
+            5
+        );
+        assert!(names("SELECT * FROM test WHERE created > 'now-7d'").is_empty());
+        assert!(engine
+            .sql("SELECT * FROM test WHERE created > 'yesterday'")
+            .is_err());
+
+        let day = |d: &str, n| (d.to_string(), n);
+        assert_eq!(
+            buckets("SELECT date_histogram(created, '1d', '+08:00'), * FROM test"),
+            vec![
+                day("2024-01-01T00:00:00.000+08:00", 1),
+                day("2024-01-02T00:00:00.000+08:00", 2),
+                day("2024-01-03T00:00:00.000+08:00", 1),
+                day("2024-02-10T00:00:00.000+08:00", 1),
+            ]
+        );
+        assert_eq!(
+            buckets("SELECT date_histogram(created, '1d'), * FROM test"),
+            vec![
+                day("2024-01-01T00:00:00.000Z", 2),
+                day("2024-01-02T00:00:00.000Z", 2),
+                day("2024-02-09T00:00:00.000Z", 1),
+            ]
+        );
+        // records of text query are got by stream
+        assert_eq!(
+            buckets("SELECT date_histogram(created, '1M'), * FROM test WHERE title = 'rust'"),
+            vec![
+                day("2024-01-01T00:00:00.000Z", 2),
+                day("2024-02-01T00:00:00.000Z", 1),
+            ]
+        );
+
+        let result = engine
+            .search(proto::core::Query {
+                query: "created < '2024-02-01'".to_string(),
+                limit: 1,
+                date_histogram: Some(proto::core::DateHistogram {
+                    field: "created".to_string(),
+                    interval: "1w".to_string(),
+                    time_zone: String::new(),
+                }),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(result.hits.len(), 1);
+        assert_eq!(result.buckets.len(), 1);
+        assert_eq!(result.buckets[0].key_as_string, "2024-01-01T00:00:00.000Z");
+        assert_eq!(result.buckets[0].doc_count, 4);
+
+        assert!(engine
+            .sql("SELECT date_histogram(title, '1d'), * FROM test")
+            .is_err());
+        assert!(engine
+            .sql("SELECT date_histogram(created, '1x'), * FROM test")
+            .is_err());
+    }
+
+    #[test]
+    fn test_multi_fields() {
+        let multi = |name: &str, r#type: FieldType, source: &str| Field {
+            name: name.to_string(),
+            r#type: r#type as i32,
+            option: None,
+            source: source.to_string(),
+            array: false,
+            version: 0,
+        };
+        let mut schema =
+            crate::easy_schema("test", vec![("title".to_string(), FieldType::Text, None)]);
+        schema.fields.insert(
+            "title.raw".to_string(),
+            multi("title.raw", FieldType::String, "title"),
+        );
+        let (test_dir, engine) = test_engine("multi_fields", schema);
+        let write = |name: &str, title: &str| {
+            append(&engine, name, &format!(r#"{{"title": "{}"}}"#, title));
+        };
+        let names = |sql: &str| {
+            engine
+                .sql(sql)
+                .unwrap()
+                .hits
+                .into_iter()
+                .map(|h| h.record.unwrap().name)
+                .collect::<Vec<_>>()
+        };
+
+        write("r1", "rust in action");
+        write("r2", "programming rust");
+        engine.persist().unwrap();
+        write("r3", "rust in action");
+        write("r4", "go in action");
+
+        // one value, two indexes
+        assert_eq!(
+            names("SELECT * FROM test WHERE title = 'rust' ORDER BY title.raw"),
+            vec!["r2", "r1", "r3"]
+        );
+        assert_eq!(
+            names("SELECT * FROM test WHERE title.raw = 'rust in action'"),
+            vec!["r1", "r3"]
+        );
+        assert!(names("SELECT * FROM test WHERE title.raw = 'rust'").is_empty());
+        assert_eq!(
+            names("SELECT * FROM test WHERE title.raw LIKE '%action' COLLAPSE BY title.raw"),
+            vec!["r1", "r4"]
+        );
+
+        // an added multi-field indexes records written after it
+        engine
+            .add_index_field(multi("title.len", FieldType::Int, "len"))
+            .unwrap();
+        assert!(engine
+            .add_index_field(multi("title.raw2", FieldType::String, "title.raw"))
+            .is_err());
+        engine
+            .add_index_field(multi("title.words", FieldType::Text, "title"))
+            .unwrap();
+        write("r5", "rust for rustaceans");
+        assert_eq!(
+            names("SELECT * FROM test WHERE title.words = 'rustaceans'"),
+            vec!["r5"]
+        );
+        assert!(names("SELECT * FROM test WHERE title.words = 'action'").is_empty());
+
+        // added multi-fields are in user schema
+        engine.persist().unwrap();
+        drop(engine);
+        let engine = Engine::open(test_dir.to_str().unwrap(), "test").unwrap();
+        let result = engine
+            .sql("SELECT * FROM test WHERE title.words = 'rustaceans'")
+            .unwrap();
+        assert_eq!(result.hits[0].record.as_ref().unwrap().name, "r5");
+    }
+
+    #[test]
+    fn test_bytes_and_array() {
+        let mut schema = crate::easy_schema(
+            "test",
+            vec![
+                ("hash".to_string(), FieldType::Bytes, None),
+                ("file.hash".to_string(), FieldType::Bytes, None),
+                ("tags".to_string(), FieldType::String, None),
+                ("sizes".to_string(), FieldType::Int, None),
+            ],
+        );
+        schema.set_validation(proto::core::schema::Validation::Coerce);
+        schema.fields.get_mut("tags").unwrap().array = true;
+        schema.fields.get_mut("sizes").unwrap().array = true;
+        let (_test_dir, engine) = test_engine("bytes", schema);
+        let write = |name: &str, data: &str| {
+            engine
+                .mutate_json(
+                    crate::ActionType::Append,
+                    name.to_string(),
+                    data.as_bytes(),
+                    None,
+                )
+                .unwrap()
+        };
+        let names = |sql: &str| {
+            let mut names = engine
+                .sql(sql)
+                .unwrap()
+                .hits
+                .into_iter()
+                .map(|h| h.record.unwrap().name)
+                .collect::<Vec<_>>();
+            names.sort();
+            names
+        };
+
+        // `YWJj` is abc, `AP8B` is [0, 255, 1]
+        assert!(write(
+            "r1",
+            r#"{"hash": "YWJj", "tags": ["a", "b"], "sizes": [1, 2]}"#
+        )
+        .is_ok());
+        engine.persist().unwrap();
+        assert!(write(
+            "r2",
+            r#"{"hash": "AP8B", "file": {"hash": "YWJj"}, "tags": ["b"], "sizes": []}"#
+        )
+        .is_ok());
 
-        // Clean up
-        std::fs::remove_dir_all("./calmcore_test").unwrap();
+        assert_eq!(names("SELECT * FROM test WHERE hash = 'YWJj'"), vec!["r1"]);
+        assert_eq!(names("SELECT * FROM test WHERE hash = 'AP8B'"), vec!["r2"]);
+        assert_eq!(
+            names("SELECT * FROM test WHERE file.hash = 'YWJj'"),
+            vec!["r2"]
+        );
+        assert_eq!(
+            names("SELECT * FROM test WHERE tags = 'b'"),
+            vec!["r1", "r2"]
+        );
+        assert_eq!(names("SELECT * FROM test WHERE sizes = 2"), vec!["r1"]);
+
+        // invalid base64, not array and wrong element are errors of records
+        assert!(!write("r3", r#"{"hash": "not base64!"}"#).is_ok());
+        assert!(!write("r4", r#"{"tags": "a"}"#).is_ok());
+        assert!(!write("r5", r#"{"sizes": [1, "big"]}"#).is_ok());
+        assert!(!write("r6", r#"{"tags": [["a"]]}"#).is_ok());
+        assert_eq!(names("SELECT * FROM test WHERE tags = 'a'"), vec!["r1"]);
+
+        // payload is stored as it is
+        let result = engine
+            .mutate(
+                vec![crate::Action::Append(proto::core::Record {
+                    name: "r7".to_string(),
+                    data: br#"{"tags": ["blob"]}"#.to_vec(),
+                    payload: vec![0, 1, 2, 255],
+                    ..Default::default()
+                })],
+                None,
+            )
+            .unwrap();
+        assert!(result[0].is_ok());
+        let result = engine
+            .sql("SELECT * FROM test WHERE tags = 'blob'")
+            .unwrap();
+        assert_eq!(
+            result.hits[0].record.as_ref().unwrap().payload,
+            vec![0, 1, 2, 255]
+        );
+
+        // and read from `_payload` of the warm segment
+        engine.persist().unwrap();
+        let result = engine
+            .sql("SELECT * FROM test WHERE tags = 'blob'")
+            .unwrap();
+        assert_eq!(
+            result.hits[0].record.as_ref().unwrap().payload,
+            vec![0, 1, 2, 255]
+        );
+        assert!(engine.get(&"r1".to_string()).unwrap().payload.is_empty());
+    }
+
+    #[test]
+    fn test_alter_index_field() {
+        let schema =
+            crate::easy_schema("test", vec![("size".to_string(), FieldType::String, None)]);
+        let (test_dir, engine) = test_engine("alter", schema);
+        let write = |engine: &Engine, name: &str, size: &str| {
+            append(engine, name, &format!(r#"{{"size": "{}"}}"#, size));
+        };
+        let names = |engine: &Engine, sql: &str| {
+            engine
+                .sql(sql)
+                .unwrap()
+                .hits
+                .into_iter()
+                .map(|h| h.record.unwrap().name)
+                .collect::<Vec<_>>()
+        };
+
+        // a warm, a freezed hot and the current segment
+        write(&engine, "r1", "10");
+        write(&engine, "r2", "9");
+        engine.persist().unwrap();
+        write(&engine, "r3", "100");
+        engine.store.new_current_segment().unwrap();
+        write(&engine, "r4", "20");
+
+        let sql = "SELECT * FROM test ORDER BY size";
+        assert_eq!(names(&engine, sql), vec!["r1", "r3", "r4", "r2"]);
+
+        let field = Field {
+            name: "size".to_string(),
+            r#type: FieldType::Int as i32,
+            ..Default::default()
+        };
+        // the reindex waits for the lock, so the field is still altering
+        let lock = crate::job::PERSIST_LOCK.lock().unwrap();
+        let handle = engine.alter_index_field(field.clone()).unwrap();
+        assert!(engine.alter_index_field(field).is_err());
+        write(&engine, "r5", "3");
+        drop(lock);
+        handle.join().unwrap().unwrap();
+
+        assert_eq!(names(&engine, sql), vec!["r5", "r2", "r1", "r4", "r3"]);
+        assert_eq!(
+            names(&engine, "SELECT * FROM test WHERE size < 15 ORDER BY size"),
+            vec!["r5", "r2", "r1"]
+        );
+
+        // index of the old version is removed from the warm segment
+        let warm = test_dir.join("test").join("segments").join("1-2");
+        assert!(warm.join("size@1").exists());
+        assert!(!warm.join("size").exists());
+        assert!(!warm.join(crate::persist::EXISTS).join("size").exists());
+
+        // the new version is in user schema and the old one in history
+        engine.persist().unwrap();
+        drop(engine);
+        let engine = Arc::new(Engine::open(test_dir.to_str().unwrap(), "test").unwrap());
+        assert_eq!(
+            names(&engine, "SELECT * FROM test WHERE size > 15 ORDER BY size"),
+            vec!["r4", "r3"]
+        );
+        let history = engine.field_history("size").unwrap();
+        assert_eq!(
+            history
+                .iter()
+                .map(|f| (f.r#type(), f.version))
+                .collect::<Vec<_>>(),
+            vec![(FieldType::String, 0), (FieldType::Int, 1)]
+        );
     }
 }
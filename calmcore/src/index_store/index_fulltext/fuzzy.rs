@@ -0,0 +1,102 @@
+/// bounded edit distance to a term, adjacent transpositions count as one edit.
+/// it is computed row by row of dynamic programming, a text is rejected as soon as
+/// no cell of a row is within the max distance, so most terms are given up after few chars.
+pub(crate) struct EditDistance {
+    chars: Vec<char>,
+    max: u32,
+}
+
+impl EditDistance {
+    pub fn new(term: &str, max: u32) -> Self {
+        Self {
+            chars: term.chars().collect(),
+            max,
+        }
+    }
+
+    /// edit distance to text, None if it is over the max distance
+    pub fn distance(&self, text: &str) -> Option<u32> {
+        let n = self.chars.len();
+
+        let mut prev: Vec<u32> = Vec::new();
+        let mut row: Vec<u32> = (0..=n as u32).collect();
+        let mut last_char: Option<char> = None;
+
+        for (j, c) in text.chars().enumerate() {
+            let mut next = Vec::with_capacity(n + 1);
+            next.push(j as u32 + 1);
+            for i in 1..=n {
+                let cost = (self.chars[i - 1] != c) as u32;
+                let mut d = (row[i - 1] + cost).min(row[i] + 1).min(next[i - 1] + 1);
+                if i > 1 && Some(self.chars[i - 1]) == last_char && self.chars[i - 2] == c {
+                    d = d.min(prev[i - 2] + 1);
+                }
+                next.push(d);
+            }
+
+            if next.iter().min().is_some_and(|m| *m > self.max) {
+                return None;
+            }
+
+            prev = std::mem::replace(&mut row, next);
+            last_char = Some(c);
+        }
+
+        (row[n] <= self.max).then_some(row[n])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distance() {
+        let a = EditDistance::new("quick", 1);
+        assert_eq!(a.distance("quick"), Some(0));
+        assert_eq!(a.distance("quack"), Some(1));
+        assert_eq!(a.distance("quikc"), Some(1));
+        assert_eq!(a.distance("quic"), Some(1));
+        assert_eq!(a.distance("quicker"), None);
+        assert_eq!(a.distance("slow"), None);
+
+        let a = EditDistance::new("你好", 1);
+        assert_eq!(a.distance("你们好"), Some(1));
+        assert_eq!(a.distance(""), None);
+    }
+
+    #[test]
+    fn test_fuzzy_terms() {
+        use crate::test_util::{mem_segment, test_scope};
+        use proto::core::field::Type as FieldType;
+
+        let scope = test_scope(crate::easy_schema(
+            "test",
+            vec![("content".to_string(), FieldType::Text, None)],
+        ));
+        let segment = mem_segment(
+            &scope,
+            &[
+                ("r1", r#"{"content": "quick quack"}"#),
+                ("r2", r#"{"content": "quack duck"}"#),
+                ("r3", r#"{"content": "lazy dog"}"#),
+            ],
+        );
+        let field = scope.get_field("content").unwrap();
+        let reader = segment.get_text_reader(&field).unwrap();
+
+        assert_eq!(reader.fuzzy_terms("quikc", 0, 0, 50), vec!["quikc"]);
+        assert_eq!(reader.fuzzy_terms("quikc", 1, 0, 50), vec!["quick"]);
+        // the nearest first, then the most frequent
+        assert_eq!(
+            reader.fuzzy_terms("quikc", 2, 0, 50),
+            vec!["quick", "quack"]
+        );
+        assert_eq!(
+            reader.fuzzy_terms("quck", 1, 0, 50),
+            vec!["quack", "duck", "quick"]
+        );
+        assert_eq!(reader.fuzzy_terms("quck", 1, 0, 1), vec!["quack"]);
+        assert_eq!(reader.fuzzy_terms("quikc", 2, 3, 50), vec!["quick"]);
+    }
+}
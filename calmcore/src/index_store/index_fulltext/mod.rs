@@ -1,25 +1,35 @@
+mod fuzzy;
 pub(crate) mod reader;
 pub(crate) mod serializer;
 mod writer;
 
 use croaring::Bitmap;
-use proto::core::{
-    field::{self},
-    value::Kind,
-    Field,
+use itertools::Itertools;
+use proto::{
+    core::{
+        field::{self},
+        value::Kind,
+        Field,
+    },
+    DottedPath,
 };
 use reader::FulltextIndexReader;
 use serializer::{DocDeserializer, TokenDeserializer, DOC_INDEX, INDEX_INFO, TERM_INDEX};
 use std::{
+    borrow::Cow,
     path::PathBuf,
     sync::{
         atomic::{AtomicU32, AtomicU64, Ordering},
-        Arc,
+        Arc, RwLock,
     },
 };
 use writer::Handler;
 
-use crate::{analyzer::Analyzer, util::CoreResult, RecordWrapper};
+use crate::{
+    analyzer::{self, Analyzer},
+    util::CoreResult,
+    RecordWrapper,
+};
 
 use super::store::InvertIndex;
 
@@ -29,11 +39,15 @@ type DocInvertIndex = InvertIndex<(u32, String), Vec<u32>>;
 pub struct FulltextIndex {
     start: u64,
     inner: Arc<proto::core::Field>,
-    analyzer: Arc<Analyzer>,
+    analyzer: RwLock<Arc<Analyzer>>,
+    // index digest of the analyzer which the data indexed with
+    index_digest: AtomicU32,
     token_index: TermInvertIndex,
     doc_index: DocInvertIndex,
     doc_count: AtomicU32,
     total_term: AtomicU64,
+    // records have value of the field
+    exists: RwLock<Arc<Bitmap>>,
 }
 
 impl FulltextIndex {
@@ -42,15 +56,23 @@ impl FulltextIndex {
         Ok(Self {
             start,
             inner,
-            analyzer,
+            index_digest: AtomicU32::new(analyzer.index_digest()),
+            analyzer: RwLock::new(analyzer),
             token_index: TermInvertIndex::new_memory(),
             doc_index: DocInvertIndex::new_memory(),
             doc_count: AtomicU32::new(0),
             total_term: AtomicU64::new(0),
+            exists: Default::default(),
         })
     }
 
-    pub(crate) fn new_disk(start: u64, inner: Arc<Field>, path: PathBuf) -> CoreResult<Self> {
+    /// exists is None for segments written before it was recorded, it is made of all tokens
+    pub(crate) fn new_disk(
+        start: u64,
+        inner: Arc<Field>,
+        path: PathBuf,
+        exists: Option<Bitmap>,
+    ) -> CoreResult<Self> {
         let analyzer = Self::make_analyzer(&inner)?;
 
         let info: serde_json::Value =
@@ -58,20 +80,29 @@ impl FulltextIndex {
 
         let doc_count = info.get("doc_count").unwrap().as_u64().unwrap() as u32;
         let total_term = info.get("total_term").unwrap().as_u64().unwrap();
+        // segments written before digest was recorded are taken as up to date
+        let index_digest = info
+            .get("analyzer_digest")
+            .and_then(|v| v.as_u64())
+            .map_or(analyzer.index_digest(), |v| v as u32);
+
+        let token_index =
+            TermInvertIndex::new_disk(path.join(TERM_INDEX), Box::new(TokenDeserializer {}))?;
+        let exists = exists.unwrap_or_else(|| token_index.index_reader().union_all());
+
         Ok(Self {
             start,
             inner,
-            analyzer,
-            token_index: TermInvertIndex::new_disk(
-                path.join(TERM_INDEX),
-                Box::new(TokenDeserializer {}),
-            )?,
+            analyzer: RwLock::new(analyzer),
+            index_digest: AtomicU32::new(index_digest),
+            token_index,
             doc_index: DocInvertIndex::new_disk(
                 path.join(DOC_INDEX),
                 Box::new(DocDeserializer {}),
             )?,
             doc_count: AtomicU32::new(doc_count),
             total_term: AtomicU64::new(total_term),
+            exists: RwLock::new(Arc::new(exists)),
         })
     }
 
@@ -85,23 +116,39 @@ impl FulltextIndex {
         FulltextIndexReader {
             start: self.start,
             inner: self.inner.clone(),
-            analyzer: self.analyzer.clone(),
+            analyzer: self.analyzer.read().unwrap().clone(),
+            index_digest: self.index_digest.load(Ordering::Relaxed),
             token_index: self.token_index.index_reader(),
             doc_index: self.doc_index.index_reader(),
             doc_count,
             total_term,
+            exists: self.exists.read().unwrap().clone(),
         }
     }
 
-    pub fn field_name(&self) -> &str {
-        &self.inner.name
+    pub fn index_name(&self) -> Cow<'_, str> {
+        self.inner.index_name()
     }
 
     fn abs_id(&self, id: u64) -> u32 {
         (id - self.start) as u32
     }
 
-    fn make_analyzer(field: &Arc<Field>) -> CoreResult<Arc<Analyzer>> {
+    /// replace the analyzer, an empty index takes its index digest too
+    pub(crate) fn set_analyzer(&self, analyzer: Arc<Analyzer>) {
+        let mut current = self.analyzer.write().unwrap();
+        if self.doc_count.load(Ordering::Relaxed) == 0 {
+            self.index_digest
+                .store(analyzer.index_digest(), Ordering::Relaxed);
+        }
+        *current = analyzer;
+    }
+
+    pub(crate) fn analyzer(&self) -> Arc<Analyzer> {
+        self.analyzer.read().unwrap().clone()
+    }
+
+    pub(crate) fn make_analyzer(field: &Arc<Field>) -> CoreResult<Arc<Analyzer>> {
         Ok(Arc::new(
             match field.option.as_ref().and_then(|o| match o {
                 field::Option::Fulltext(option) => Some(option),
@@ -119,22 +166,38 @@ impl FulltextIndex {
         if records.is_empty() {
             return;
         }
+        let analyzer = self.analyzer();
         let mut handler = self.handler();
+        let mut exists = self.exists.read().unwrap().as_ref().clone();
         for r in records {
             if let Some(val) = &r.value {
-                if let Some(value) = val.obj().fields.get(&self.inner.name) {
-                    if let Some(Kind::StringValue(text)) = value.kind.as_ref() {
-                        if text.is_empty() {
+                if let Some(value) = val.path(self.inner.source_path()) {
+                    let text = match value.kind.as_ref() {
+                        Some(Kind::StringValue(text)) => Cow::Borrowed(text.as_str()),
+                        // every element of an array, like values of an array of objects
+                        Some(Kind::ListValue(list)) => Cow::Owned(
+                            list.values
+                                .iter()
+                                .filter_map(|v| match &v.kind {
+                                    Some(Kind::StringValue(s)) => Some(s.as_str()),
+                                    _ => None,
+                                })
+                                .join("\n"),
+                        ),
+                        _ => {
+                            log::trace!("field value:{:?} is not text, ignore it", value);
                             continue;
                         }
-                        let tokens = self.analyzer.analyzer_index(text);
-                        self.doc_count.fetch_add(1, Ordering::Relaxed);
-                        self.total_term
-                            .fetch_add(tokens.len() as u64, Ordering::Relaxed);
-                        handler.push_index(tokens, self.abs_id(r.id()));
-                    } else {
-                        log::trace!("field value:{:?} is not text, ignore it", value);
+                    };
+                    if text.is_empty() {
+                        continue;
                     }
+                    exists.add(self.abs_id(r.id()));
+                    let tokens = analyzer.analyzer_index(&text);
+                    self.doc_count.fetch_add(1, Ordering::Relaxed);
+                    self.total_term
+                        .fetch_add(analyzer::position_len(&tokens) as u64, Ordering::Relaxed);
+                    handler.push_index(tokens, self.abs_id(r.id()));
                 }
             }
         }
@@ -144,5 +207,6 @@ impl FulltextIndex {
         //replace maptree with new one
         self.doc_index.replace(doc_index);
         self.token_index.replace(token_index);
+        *self.exists.write().unwrap() = Arc::new(exists);
     }
 }
@@ -1,24 +1,45 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{borrow::Cow, cmp::Reverse, collections::HashMap, sync::Arc};
 
 use croaring::Bitmap;
+use itertools::Itertools;
+use proto::core::Explanation;
 
 use crate::{
     analyzer::{Analyzer, Token},
-    index_store::store::InvertIndexReader,
-    util::CoreResult,
+    index_store::{
+        pattern::{TermPattern, MAX_PATTERN_TERMS},
+        store::{InvertIndexReader, IterKey},
+    },
+    util::{CoreError, CoreResult},
 };
+
+use super::fuzzy::EditDistance;
+
+/// query token -> (doc count, terms in index it matches),
+/// a token matches itself only, unless it is expanded by fuzzy query
+pub(crate) type TokenTerms = HashMap<String, (usize, Vec<String>)>;
+
 pub struct FulltextIndexReader {
     pub start: u64,
     pub inner: Arc<proto::core::Field>,
     pub analyzer: Arc<Analyzer>,
+    // index digest of the analyzer which the data indexed with
+    pub index_digest: u32,
     pub(crate) token_index: InvertIndexReader<String, Bitmap>,
     pub(crate) doc_index: InvertIndexReader<(u32, String), Vec<u32>>,
     // Field information
     pub doc_count: u32,  // total Document count
     pub total_term: u64, // Average document length
+    // records have value of the field
+    pub exists: Arc<Bitmap>,
 }
 impl FulltextIndexReader {
-    pub(crate) fn analyzer(&self, value: &str) -> CoreResult<Vec<Token>> {
+    /// data was indexed with other dicts than the current analyzer
+    pub fn is_stale(&self) -> bool {
+        self.index_digest != self.analyzer.index_digest()
+    }
+
+    pub(crate) fn analyzer(&self, value: &str) -> CoreResult<Vec<Vec<Token>>> {
         Ok(self.analyzer.analyzer_query(value))
     }
 
@@ -29,38 +50,159 @@ impl FulltextIndexReader {
             .collect())
     }
 
+    /// walk terms start with prefix in order, stop when f return false
+    pub(crate) fn walk_terms<F>(&self, prefix: &str, mut f: F)
+    where
+        F: FnMut(&str, &Bitmap) -> bool,
+    {
+        let start = prefix.to_string();
+        self.token_index.range(Some(&start), |k, v| {
+            let term = match k {
+                IterKey::Memory(k) => Cow::Borrowed(k.as_str()),
+                IterKey::Disk(k) => String::from_utf8_lossy(k),
+            };
+            if !term.starts_with(prefix) {
+                return false;
+            }
+            f(&term, v)
+        });
+    }
+
+    /// union of tokens matched by pattern
+    pub(crate) fn pattern(&self, pattern: &TermPattern) -> CoreResult<Bitmap> {
+        let mut results = Vec::new();
+        self.walk_terms(pattern.head(), |term, bitmap| {
+            if pattern.is_match(term.as_bytes()) {
+                results.push(bitmap.clone());
+            }
+            results.len() <= MAX_PATTERN_TERMS
+        });
+
+        if results.len() > MAX_PATTERN_TERMS {
+            return Err(CoreError::InvalidParam(format!(
+                "field:{} pattern:{:?} matches more than {} terms",
+                self.inner.name, pattern, MAX_PATTERN_TERMS
+            )));
+        }
+
+        Ok(Bitmap::fast_or(&results.iter().collect_vec()))
+    }
+
+    /// terms within the edit distance of token, the nearest and most frequent first
+    pub(crate) fn fuzzy_terms(
+        &self,
+        token: &str,
+        distance: u32,
+        prefix_length: usize,
+        max_expansions: usize,
+    ) -> Vec<String> {
+        if distance == 0 {
+            return vec![token.to_string()];
+        }
+
+        let prefix: String = token.chars().take(prefix_length).collect();
+        let edit = EditDistance::new(token, distance);
+
+        let mut terms = Vec::new();
+        self.walk_terms(&prefix, |term, bitmap| {
+            if let Some(d) = edit.distance(term) {
+                terms.push((d, Reverse(bitmap.cardinality()), term.to_string()));
+            }
+            true
+        });
+
+        terms
+            .into_iter()
+            .sorted()
+            .take(max_expansions)
+            .map(|(_, _, term)| term)
+            .collect()
+    }
+
     pub(crate) fn score(
         &self,
         doc_id: u32,
-        tokens: &[Token],
-        token_doc_len: &HashMap<String, usize>,
+        paths: &[Vec<Token>],
+        token_terms: &TokenTerms,
         operator: bool,
         slop: i32,
     ) -> Option<f32> {
-        let offset_map = token_doc_len
-            .iter()
-            .map(|(token, _)| {
-                let key = (doc_id, token.clone());
-                let offsets = self.doc_index.get(&key).unwrap_or_default();
-                (key.1, offsets)
-            })
-            .collect::<HashMap<_, _>>();
+        let offset_map = self.term_offsets(doc_id, token_terms);
+        let dl = self.doc_len(doc_id);
 
-        // Get document length
-        let dl = self
-            .doc_index
-            .get(&(doc_id, "".to_string()))
-            .and_then(|a| a.first().cloned())
-            .unwrap_or(1);
-
-        //phrase query filter, if not match return None
-        if operator && !pharse_filter(tokens, &offset_map, slop) {
+        //phrase query filter, any synonym path match is ok, if not match return None
+        if operator && !paths.iter().any(|p| pharse_filter(p, &offset_map, slop)) {
             return None;
         }
 
         let avgdl = self.total_term as f32 / self.doc_count as f32;
 
-        Some(self.score_bm25(dl as f32, avgdl, token_doc_len, &offset_map))
+        Some(self.score_bm25(dl as f32, avgdl, token_terms, &offset_map))
+    }
+
+    /// bm25 of every token of the doc, it is the same as score
+    pub(crate) fn explain(&self, doc_id: u32, token_terms: &TokenTerms) -> Explanation {
+        let offset_map = self.term_offsets(doc_id, token_terms);
+        let dl = self.doc_len(doc_id) as f32;
+        let avgdl = self.total_term as f32 / self.doc_count as f32;
+
+        let details = offset_map
+            .iter()
+            .filter(|(_, positions)| !positions.is_empty())
+            .sorted_by(|a, b| a.0.cmp(b.0))
+            .map(|(token, positions)| {
+                let tf = positions.len() as f32;
+                let df = token_terms.get(token).map_or(1, |(df, _)| *df) as f32;
+                let (idf, score) = self.bm25(tf, df, dl, avgdl);
+                Explanation {
+                    value: score,
+                    description: format!(
+                        "bm25 of {:?} in {}: idf={} (doc_count={} df={}), tf={}, dl={}, avgdl={}",
+                        token, self.inner.name, idf, self.doc_count, df, tf, dl, avgdl
+                    ),
+                    details: vec![],
+                }
+            })
+            .collect_vec();
+
+        Explanation {
+            value: details.iter().map(|d| d.value).sum(),
+            description: format!("sum of bm25 in {}", self.inner.name),
+            details,
+        }
+    }
+
+    // positions of all terms a token matches
+    fn term_offsets(&self, doc_id: u32, token_terms: &TokenTerms) -> HashMap<String, Vec<u32>> {
+        token_terms
+            .iter()
+            .map(|(token, (_, terms))| {
+                let offsets = match terms.as_slice() {
+                    [term] => self
+                        .doc_index
+                        .get(&(doc_id, term.clone()))
+                        .unwrap_or_default(),
+                    _ => terms
+                        .iter()
+                        .flat_map(|term| {
+                            self.doc_index
+                                .get(&(doc_id, term.clone()))
+                                .unwrap_or_default()
+                        })
+                        .sorted()
+                        .collect(),
+                };
+                (token.clone(), offsets)
+            })
+            .collect()
+    }
+
+    // Get document length
+    fn doc_len(&self, doc_id: u32) -> u32 {
+        self.doc_index
+            .get(&(doc_id, "".to_string()))
+            .and_then(|a| a.first().cloned())
+            .unwrap_or(1)
     }
 
     // [ \text{Score}(D,Q) = \sum_{i=1}^{n} IDF(q_i) \cdot \frac{f(q_i, D) \cdot (k1 + 1)}{f(q_i, D) + k1 \cdot (1 - b + b \cdot \frac{|D|}{\text{AVGDL}})} ]
@@ -70,33 +212,39 @@ impl FulltextIndexReader {
         &self,
         dl: f32,
         avgdl: f32,
-        token_doc_len: &HashMap<String, usize>, // Token count in store
+        token_terms: &TokenTerms,               // Token count in store
         offset_map: &HashMap<String, Vec<u32>>, // Token offset map
     ) -> f32 {
-        // BM25 参数，可以根据需要调整
-        const K1: f32 = 1.2;
-        const B: f32 = 0.75;
-
         let mut score = 0.0;
 
         for (term, positions) in offset_map {
             // 计算词频 TF
             let tf = positions.len() as f32;
 
-            // 获取文档频率 DF 并计算 IDF
-            let df = *token_doc_len.get(term).unwrap_or(&1) as f32;
-            let idf = ((self.doc_count as f32 - df + 0.5) / (df + 0.5)).ln();
-
-            // 文档长度归一化
-            let norm = 1.0 - B + B * (dl / avgdl);
+            // 获取文档频率 DF
+            let df = token_terms.get(term).map_or(1, |(df, _)| *df) as f32;
 
-            // BM25 评分公式
-            let term_score = idf * (tf * (K1 + 1.0)) / (tf + K1 * norm);
-            score += term_score;
+            score += self.bm25(tf, df, dl, avgdl).1;
         }
 
         score
     }
+
+    /// idf and bm25 score of a term
+    fn bm25(&self, tf: f32, df: f32, dl: f32, avgdl: f32) -> (f32, f32) {
+        // BM25 参数，可以根据需要调整
+        const K1: f32 = 1.2;
+        const B: f32 = 0.75;
+
+        // 计算 IDF, 加 1 使 IDF 不为负, 否则 boost 会让分数更低
+        let idf = (1.0 + (self.doc_count as f32 - df + 0.5) / (df + 0.5)).ln();
+
+        // 文档长度归一化
+        let norm = 1.0 - B + B * (dl / avgdl);
+
+        // BM25 评分公式
+        (idf, idf * (tf * (K1 + 1.0)) / (tf + K1 * norm))
+    }
 }
 
 /// Phrase query filter function
@@ -167,11 +315,15 @@ mod tests {
                 name: "hello".to_string(),
                 attrs: None,
                 index: 0,
+                start: 0,
+                end: 5,
             },
             Token {
                 name: "world".to_string(),
                 attrs: None,
                 index: 1,
+                start: 6,
+                end: 11,
             },
         ];
 
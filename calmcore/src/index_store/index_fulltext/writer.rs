@@ -4,7 +4,7 @@ use croaring::Bitmap;
 use itertools::Itertools;
 use mem_btree::{Action, BTree, BatchWrite};
 
-use crate::analyzer::Token;
+use crate::analyzer::{self, Token};
 
 type ReleaseResult = (BTree<String, Bitmap>, BTree<(u32, String), Vec<u32>>);
 
@@ -32,17 +32,20 @@ impl Handler {
         // Insert document length
         self.doc_index_buffer.insert(
             (id, "".to_string()),
-            Action::Put(vec![tokens.len() as u32], None),
+            Action::Put(vec![analyzer::position_len(&tokens) as u32], None),
         );
         for (term, tokens) in tokens.iter().into_group_map_by(|t| &t.name) {
             self.doc_index_buffer.insert(
                 (id, term.to_string()),
-                Action::Put(tokens.iter().map(|t| t.index as u32).collect(), None),
+                Action::Put(
+                    tokens.iter().map(|t| t.index as u32).dedup().collect(),
+                    None,
+                ),
             );
 
             if let Some(bi) = self.token_index_buffer.get_mut(term) {
                 bi.mut_value().add(id);
-                return;
+                continue;
             }
 
             let mut bi = self
@@ -0,0 +1,183 @@
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    path::PathBuf,
+    sync::{Arc, RwLock},
+};
+
+use croaring::Bitmap;
+use proto::{
+    core::{field, value::Kind, Field, Value},
+    DottedPath,
+};
+
+use crate::{
+    util::{CoreError, CoreResult},
+    RecordWrapper,
+};
+
+use super::index_term::{reader::TermIndexReader, TermIndex};
+
+/// file of parent ids of nested objects, u32 big endian by child id
+pub(crate) const PARENTS: &str = "_parents";
+
+/// every object of a nested field is a hidden child with its own id in the segment,
+/// fields of objects are term indexes of child ids, so conditions match in the same object
+pub struct NestedIndex {
+    start: u64,
+    inner: Arc<Field>,
+    children: HashMap<String, TermIndex>,
+    // child id -> parent abs id
+    parents: RwLock<Arc<Vec<u32>>>,
+}
+
+impl NestedIndex {
+    pub fn new_mem(start: u64, inner: Arc<Field>) -> CoreResult<Self> {
+        let children = nested_fields(&inner)?
+            .into_iter()
+            .map(|f| Ok((f.name.clone(), TermIndex::new_mem(0, f)?)))
+            .collect::<CoreResult<_>>()?;
+        Ok(Self {
+            start,
+            inner,
+            children,
+            parents: Default::default(),
+        })
+    }
+
+    pub fn new_disk(start: u64, inner: Arc<Field>, path: PathBuf) -> CoreResult<Self> {
+        let children = nested_fields(&inner)?
+            .into_iter()
+            .map(|f| {
+                let path = path.join(&f.name);
+                Ok((f.name.clone(), TermIndex::new_disk(0, f, path, None)?))
+            })
+            .collect::<CoreResult<_>>()?;
+        let parents = std::fs::read(path.join(PARENTS))?
+            .chunks_exact(4)
+            .map(|b| u32::from_be_bytes(b.try_into().unwrap()))
+            .collect();
+        Ok(Self {
+            start,
+            inner,
+            children,
+            parents: RwLock::new(Arc::new(parents)),
+        })
+    }
+
+    pub fn index_name(&self) -> Cow<'_, str> {
+        self.inner.index_name()
+    }
+
+    pub fn write(&self, records: &[RecordWrapper]) {
+        let mut parents = self.parents.read().unwrap().as_ref().clone();
+        let mut objects = Vec::new();
+        for r in records {
+            let value = match r
+                .value
+                .as_ref()
+                .and_then(|v| v.path(self.inner.source_path()))
+            {
+                Some(v) => v,
+                None => continue,
+            };
+            let values = match value.as_ref() {
+                Value {
+                    kind: Some(Kind::ListValue(list)),
+                } => list.values.clone(),
+                Value {
+                    kind: Some(Kind::ObjectValue(_)),
+                } => vec![value.into_owned()],
+                _ => {
+                    log::trace!("nested value:{:?} is not object, ignore it", value);
+                    continue;
+                }
+            };
+            for v in values {
+                objects.push((parents.len() as u32, v));
+                parents.push(r.abs_id(self.start));
+            }
+        }
+        if objects.is_empty() {
+            return;
+        }
+
+        for (name, index) in self.children.iter() {
+            let values = objects
+                .iter()
+                .filter_map(|(id, v)| v.path(name).map(|v| (*id, v)))
+                .collect::<Vec<(u32, Cow<Value>)>>();
+            index.write_values(values);
+        }
+        *self.parents.write().unwrap() = Arc::new(parents);
+    }
+
+    pub fn reader(&self) -> NestedIndexReader {
+        let parents = self.parents.read().unwrap().clone();
+        NestedIndexReader {
+            inner: self.inner.clone(),
+            children: self
+                .children
+                .iter()
+                .map(|(name, index)| (name.clone(), index.reader()))
+                .collect(),
+            exists: parents.iter().copied().collect(),
+            parents,
+        }
+    }
+}
+
+pub struct NestedIndexReader {
+    pub inner: Arc<Field>,
+    pub children: HashMap<String, TermIndexReader>,
+    pub parents: Arc<Vec<u32>>,
+    // records have any object
+    pub exists: Bitmap,
+}
+
+impl NestedIndexReader {
+    pub(crate) fn child(&self, name: &str) -> CoreResult<&TermIndexReader> {
+        self.children.get(name).ok_or_else(|| {
+            CoreError::InvalidParam(format!(
+                "field:{:?} not found in nested:{:?}",
+                name, self.inner.name
+            ))
+        })
+    }
+
+    /// all objects
+    pub(crate) fn all_children(&self) -> Bitmap {
+        Bitmap::from_range(0..self.parents.len() as u32)
+    }
+
+    /// records of objects
+    pub(crate) fn parents_of(&self, children: &Bitmap) -> Bitmap {
+        children
+            .iter()
+            .filter_map(|id| self.parents.get(id as usize).copied())
+            .collect()
+    }
+}
+
+/// fields of objects in nested field, only term types are supported
+pub(crate) fn nested_fields(field: &Field) -> CoreResult<Vec<Arc<Field>>> {
+    let fields = match &field.option {
+        Some(field::Option::Nested(n)) => n.fields.clone(),
+        _ => Vec::new(),
+    };
+    fields
+        .into_iter()
+        .map(|f| match f.r#type() {
+            field::Type::Bool
+            | field::Type::Int
+            | field::Type::Float
+            | field::Type::String
+            | field::Type::Date
+            | field::Type::Bytes => Ok(Arc::new(f)),
+            _ => Err(CoreError::InvalidParam(format!(
+                "nested field:{:?} not support type of field:{:?}",
+                field.name, f.name
+            ))),
+        })
+        .collect()
+}
@@ -2,13 +2,18 @@ pub(crate) mod reader;
 pub(crate) mod serializer;
 mod writer;
 use crate::{
-    util::{kind_to_vec_fix_type, CoreResult, KindType},
+    util::{kind_to_vec_fix_field, CoreResult, KindType},
     RecordWrapper,
 };
 use croaring::Bitmap;
+use proto::{core::Value, DottedPath};
 use reader::TermIndexReader;
 use serializer::TermDeserializer;
-use std::{path::PathBuf, sync::Arc};
+use std::{
+    borrow::Cow,
+    path::PathBuf,
+    sync::{Arc, RwLock},
+};
 use writer::Handler;
 
 use super::store::InvertIndex;
@@ -19,6 +24,8 @@ pub struct TermIndex {
     start: u64,
     inner: Arc<proto::core::Field>,
     term_index: TermInvertIndex,
+    // records have value of the field
+    exists: RwLock<Arc<Bitmap>>,
 }
 
 impl TermIndex {
@@ -27,14 +34,24 @@ impl TermIndex {
             start,
             inner,
             term_index: TermInvertIndex::new_memory(),
+            exists: Default::default(),
         })
     }
 
-    pub fn new_disk(start: u64, inner: Arc<proto::core::Field>, path: PathBuf) -> CoreResult<Self> {
+    /// exists is None for segments written before it was recorded, it is made of all terms
+    pub fn new_disk(
+        start: u64,
+        inner: Arc<proto::core::Field>,
+        path: PathBuf,
+        exists: Option<Bitmap>,
+    ) -> CoreResult<Self> {
+        let term_index = TermInvertIndex::new_disk(path, Box::new(TermDeserializer {}))?;
+        let exists = exists.unwrap_or_else(|| term_index.index_reader().union_all());
         Ok(Self {
             start,
             inner,
-            term_index: TermInvertIndex::new_disk(path, Box::new(TermDeserializer {}))?,
+            term_index,
+            exists: RwLock::new(Arc::new(exists)),
         })
     }
 
@@ -48,47 +65,60 @@ impl TermIndex {
             term_record_index: self.term_index.index_reader(),
             inner: self.inner.clone(),
             term_serializer: Box::new(serializer::TermSerializer {}),
+            exists: self.exists.read().unwrap().clone(),
         }
     }
 
-    pub fn field_type(&self) -> proto::core::field::Type {
-        self.inner.r#type()
-    }
-
-    pub fn field_name(&self) -> &str {
-        &self.inner.name
+    pub fn index_name(&self) -> Cow<'_, str> {
+        self.inner.index_name()
     }
 }
 
 impl TermIndex {
     pub fn write(&self, records: &[RecordWrapper]) {
-        if records.is_empty() {
+        let values = records
+            .iter()
+            .filter_map(|r| {
+                let value = r.value.as_ref()?.path(self.inner.source_path())?;
+                Some((r.abs_id(self.start), value))
+            })
+            .collect::<Vec<_>>();
+        self.write_values(values);
+    }
+
+    /// index values by ids, ids of nested objects are not record ids
+    pub(crate) fn write_values(&self, values: Vec<(u32, Cow<Value>)>) {
+        if values.is_empty() {
             return;
         }
 
         let mut handler = self.handler();
-        for r in records {
-            if let Some(val) = &r.value {
-                if let Some(value) = val.obj().fields.get(&self.inner.name) {
-                    if let Some(kind) = value.kind.as_ref() {
-                        match kind_to_vec_fix_type(kind, &self.field_type()) {
-                            Ok(KindType::Single(v)) => handler.push_index(v, r.abs_id(self.start)),
-                            Ok(KindType::Array(arr)) => {
-                                for v in arr {
-                                    handler.push_index(v, r.abs_id(self.start))
-                                }
-                            }
-                            Err(e) => log::trace!("err:{:?}, ignore it", e),
+        let mut exists = self.exists.read().unwrap().as_ref().clone();
+        for (id, value) in values {
+            if let Some(kind) = value.kind.as_ref() {
+                match kind_to_vec_fix_field(kind, &self.inner) {
+                    Ok(KindType::Single(v)) => {
+                        handler.push_index(v, id);
+                        exists.add(id);
+                    }
+                    Ok(KindType::Array(arr)) => {
+                        if !arr.is_empty() {
+                            exists.add(id);
+                        }
+                        for v in arr {
+                            handler.push_index(v, id)
                         }
-                    } else {
-                        log::trace!("field value:{:?} is not text, ignore it", value);
                     }
+                    Err(e) => log::trace!("err:{:?}, ignore it", e),
                 }
+            } else {
+                log::trace!("field value:{:?} is not text, ignore it", value);
             }
         }
 
         //replace maptree with new one
         self.term_index.replace(handler.release());
+        *self.exists.write().unwrap() = Arc::new(exists);
     }
 }
 
@@ -110,6 +140,9 @@ mod tests {
             name: "test".to_string(),
             option: Default::default(),
             r#type: proto::core::field::Type::String as i32,
+            source: String::new(),
+            array: false,
+            version: 0,
         });
 
         TermIndex::new_mem(0, field).unwrap()
@@ -5,8 +5,11 @@ use itertools::Itertools;
 use mem_btree::persist::KVSerializer;
 
 use crate::{
-    index_store::store::{InvertIndexReader, IterKey},
-    util::CoreResult,
+    index_store::{
+        pattern::{TermPattern, MAX_PATTERN_TERMS},
+        store::{InvertIndexReader, IterKey},
+    },
+    util::{CoreError, CoreResult},
 };
 
 pub struct TermIndexReader {
@@ -14,6 +17,8 @@ pub struct TermIndexReader {
     pub(crate) term_record_index: InvertIndexReader<Vec<u8>, Bitmap>,
     pub inner: Arc<proto::core::Field>,
     pub term_serializer: Box<dyn KVSerializer<Vec<u8>, Bitmap>>,
+    // records have value of the field
+    pub exists: Arc<Bitmap>,
 }
 
 impl TermIndexReader {
@@ -32,6 +37,20 @@ impl TermIndexReader {
         self.term_record_index.range(start, f);
     }
 
+    /// all terms in order with their records
+    pub(crate) fn terms<F>(&self, mut f: F)
+    where
+        F: FnMut(&[u8], &Bitmap),
+    {
+        self.term_record_index.range(None, |k, v| {
+            match &k {
+                IterKey::Memory(k) => f(k, v),
+                IterKey::Disk(k) => f(k, v),
+            }
+            true
+        });
+    }
+
     pub fn in_terms(&self, list: &[Vec<u8>]) -> Bitmap {
         let mut result = Bitmap::new();
         for v in list {
@@ -42,6 +61,35 @@ impl TermIndexReader {
         result
     }
 
+    /// union of terms matched by pattern, terms are walked from the head of pattern
+    pub(crate) fn pattern(&self, pattern: &TermPattern) -> CoreResult<Bitmap> {
+        let head = pattern.head().as_bytes().to_vec();
+        let mut results = Vec::new();
+
+        self.term_record_index.range(Some(&head), |k, v| {
+            let term = match &k {
+                IterKey::Memory(k) => k.as_slice(),
+                IterKey::Disk(k) => k,
+            };
+            if !term.starts_with(&head) {
+                return false;
+            }
+            if pattern.is_match(term) {
+                results.push(v.clone());
+            }
+            results.len() <= MAX_PATTERN_TERMS
+        });
+
+        if results.len() > MAX_PATTERN_TERMS {
+            return Err(CoreError::InvalidParam(format!(
+                "field:{} pattern:{:?} matches more than {} terms",
+                self.inner.name, pattern, MAX_PATTERN_TERMS
+            )));
+        }
+
+        Ok(Bitmap::fast_or(&results.iter().collect_vec()))
+    }
+
     pub fn between(
         &self,
         low: Option<&Vec<u8>>,
@@ -1,6 +1,8 @@
 pub(crate) mod index_fulltext;
+pub(crate) mod index_nested;
 pub(crate) mod index_term;
 mod index_vector;
+pub(crate) mod pattern;
 pub mod seacher;
 pub mod segment;
 mod segment_disk;
@@ -12,6 +14,7 @@ use itertools::Itertools;
 use proto::core::{Field, Record};
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 use regex::Regex;
+use seacher::cache::FilterCacheInfo;
 use segment::{Segment, SegmentReader};
 use segment_mem::MemSegment;
 use serde::{Deserialize, Serialize};
@@ -24,14 +27,16 @@ use std::{
 };
 
 use crate::{
+    analyzer::Analyzer,
+    persist::EXISTS,
     util::{CoreError, CoreResult},
-    RecordWrapper,
+    ActionType, RecordWrapper, Scope,
 };
 
 /// index fields schema
-/// in the future, one field may have multiple index
-/// example  title need two index type,  {title: fulltext, title: keyword} so we can make  title1 -> keywordindex  and title2 -> fulltext index
-/// title -> [Field{name:title1 , field_name=title, index_opt=keyword}, Field{name:title2 , field_name=title, index_opt=fulltext}]
+/// one value may have multiple index by the `source` of fields
+/// example  title need two index type, title -> fulltext index and title.raw -> keyword index
+/// [Field{name:title, type=text}, Field{name:title.raw, source=title, type=string}]
 pub struct IndexStore {
     path: PathBuf,
     freezed: Vec<Segment>,
@@ -143,7 +148,47 @@ impl IndexStore {
 
         let mut segment = MemSegment::new(start, fields)?;
         mem::swap(&mut self.current, &mut segment);
-        self.freezed.push(Segment::Hot(Box::new(segment)));
+        // an empty segment has nothing to persist, the new one takes its start
+        if segment.end() >= segment.start() {
+            self.freezed.push(Segment::Hot(Box::new(segment)));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn add_index_field(&self, field: Arc<Field>) -> CoreResult<()> {
+        self.current.add_index_field(self.current.start(), field)
+    }
+
+    /// analyzers of the current segment, they are the latest loaded
+    pub(crate) fn analyzers(&self) -> HashMap<String, Arc<Analyzer>> {
+        self.current.analyzers()
+    }
+
+    /// replace analyzers of all segments, the current segment is freezed first
+    /// if it has data indexed with other index time dicts
+    pub(crate) fn set_analyzers(
+        &mut self,
+        analyzers: HashMap<String, Arc<Analyzer>>,
+        fields: HashMap<String, Arc<Field>>,
+    ) -> CoreResult<()> {
+        let current = self.current.reader();
+        let freeze = analyzers.iter().any(|(name, analyzer)| {
+            current
+                .index_fulltext
+                .get(name)
+                .is_some_and(|r| r.doc_count > 0 && r.index_digest != analyzer.index_digest())
+        });
+
+        if freeze {
+            self.new_current_segment(fields)?;
+        }
+
+        for (name, analyzer) in analyzers {
+            self.current.set_analyzer(&name, analyzer.clone());
+            self.freezed
+                .iter()
+                .for_each(|s| s.set_analyzer(&name, analyzer.clone()));
+        }
         Ok(())
     }
 
@@ -158,7 +203,8 @@ impl IndexStore {
         Segment::new(path, &fields)
     }
 
-    pub(crate) fn hot_to_warm(&mut self, segment: Segment) -> CoreResult<()> {
+    /// put the segment in place of the freezed one with the same start
+    pub(crate) fn replace_segment(&mut self, segment: Segment) -> CoreResult<()> {
         self.freezed
             .iter_mut()
             .find(|f| f.start() == segment.start())
@@ -171,6 +217,89 @@ impl IndexStore {
             )
     }
 
+    /// index the field in hot freezed segments, return warm segments without index of it
+    pub(crate) fn index_hot_segments(
+        &self,
+        scope: &Scope,
+        field: Arc<Field>,
+    ) -> CoreResult<Vec<SegmentReader>> {
+        let mut warms = Vec::new();
+        for segment in self.freezed.iter() {
+            match segment {
+                Segment::Hot(h) if !h.has_index(&field.index_name()) => {
+                    h.build_index_field(scope, field.clone())?
+                }
+                Segment::Warm(w) if !w.has_index(&field.index_name()) => {
+                    warms.push(segment.reader())
+                }
+                _ => {}
+            }
+        }
+        Ok(warms)
+    }
+
+    /// write index of the field to the dir of warm segment by its records
+    pub(crate) fn write_index_field(
+        base_path: &Path,
+        scope: &Scope,
+        field: Arc<Field>,
+        segment: &SegmentReader,
+    ) -> CoreResult<()> {
+        let SegmentReader::Warm(disk) = segment else {
+            return Ok(());
+        };
+        let path = base_path
+            .join("segments")
+            .join(format!("{}-{}", disk.start(), disk.end()));
+
+        // index left by an alter not finished
+        let name = field.index_name().into_owned();
+        Self::remove_index_dir(base_path, segment, &name)?;
+
+        let mem = MemSegment::new(disk.start() - 1, HashMap::from([(name, field)]))?;
+        let records = disk
+            .records()
+            .into_iter()
+            .map(|r| RecordWrapper::new(scope, r, ActionType::Append))
+            .collect_vec();
+        mem.write_records(records, disk.end(), None);
+
+        crate::persist::write_index(&path, &mem.reader())
+    }
+
+    /// remove the index from hot segments, return warm segments having it
+    pub(crate) fn drop_index(&self, name: &str) -> Vec<SegmentReader> {
+        self.current.remove_index(name);
+        let mut warms = Vec::new();
+        for segment in self.freezed.iter() {
+            match segment {
+                Segment::Hot(h) => h.remove_index(name),
+                Segment::Warm(w) if w.has_index(name) => warms.push(segment.reader()),
+                _ => {}
+            }
+        }
+        warms
+    }
+
+    pub(crate) fn remove_index_dir(
+        base_path: &Path,
+        segment: &SegmentReader,
+        name: &str,
+    ) -> CoreResult<()> {
+        let path =
+            base_path
+                .join("segments")
+                .join(format!("{}-{}", segment.start(), segment.end()));
+        for p in [path.join(name), path.join(EXISTS).join(name)] {
+            if p.is_dir() {
+                std::fs::remove_dir_all(p)?;
+            } else if p.exists() {
+                std::fs::remove_file(p)?;
+            }
+        }
+        Ok(())
+    }
+
     pub(crate) fn info(&self, name: String) -> CoreResult<StoreInfo> {
         StoreInfo::new(name, &self.path, &self.segment_readers())
     }
@@ -184,6 +313,8 @@ pub struct StoreInfo {
     pub total_doc_count: u32,
     pub disk_size_bytes: u64,
     pub mem_size_bytes: u64,
+    #[serde(default)]
+    pub filter_cache: FilterCacheInfo,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -195,6 +326,9 @@ pub struct SegmentInfo {
     pub doc_count: u32,
     pub del_count: u32,
     pub marker: Option<String>,
+    // fulltext fields indexed with out of date dicts, rebuild them to apply the new dicts
+    #[serde(default)]
+    pub stale_fields: Vec<String>,
 }
 
 impl StoreInfo {
@@ -227,6 +361,7 @@ impl StoreInfo {
             total_doc_count,
             disk_size_bytes,
             mem_size_bytes,
+            filter_cache: Default::default(),
         })
     }
 }
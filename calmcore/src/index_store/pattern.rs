@@ -0,0 +1,160 @@
+use regex::Regex;
+
+use crate::util::{CoreError, CoreResult};
+
+/// max terms a pattern can match in one segment, query fails if more
+pub(crate) const MAX_PATTERN_TERMS: usize = 10000;
+
+/// pattern of prefix, wildcard and regexp queries, it is tested on every term of a field.
+/// terms are walked from the literal head of pattern, so a pattern starts with literal is much cheaper
+#[derive(Debug, Clone)]
+pub(crate) struct TermPattern {
+    // every matched term starts with it
+    head: String,
+    // None means every term starts with head is matched
+    regex: Option<Regex>,
+}
+
+impl TermPattern {
+    pub fn prefix(value: &str, case_insensitive: bool) -> CoreResult<Self> {
+        if !case_insensitive {
+            return Ok(Self {
+                head: value.to_string(),
+                regex: None,
+            });
+        }
+        Self::new(
+            &format!("{}(?s:.*)", regex::escape(value)),
+            value.to_string(),
+            true,
+        )
+    }
+
+    /// `%` matches any chars and `_` matches one char like sql LIKE, `\` escapes them
+    pub fn wildcard(value: &str, case_insensitive: bool) -> CoreResult<Self> {
+        let mut re = String::with_capacity(value.len() * 2);
+        let mut head = String::new();
+        let mut literal = true;
+
+        let mut chars = value.chars();
+        while let Some(c) = chars.next() {
+            let c = match c {
+                '%' => {
+                    re.push_str("(?s:.*)");
+                    literal = false;
+                    continue;
+                }
+                '_' => {
+                    re.push_str("(?s:.)");
+                    literal = false;
+                    continue;
+                }
+                '\\' => chars.next().unwrap_or('\\'),
+                c => c,
+            };
+            re.push_str(&regex::escape(c.encode_utf8(&mut [0; 4])));
+            if literal {
+                head.push(c);
+            }
+        }
+
+        Self::new(&re, head, case_insensitive)
+    }
+
+    /// the regex must match the whole term
+    pub fn regexp(value: &str, case_insensitive: bool) -> CoreResult<Self> {
+        Self::new(value, regex_head(value), case_insensitive)
+    }
+
+    fn new(re: &str, head: String, case_insensitive: bool) -> CoreResult<Self> {
+        let flag = if case_insensitive { "(?i)" } else { "" };
+        let regex = Regex::new(&format!("{}^(?:{})$", flag, re))
+            .map_err(|e| CoreError::InvalidParam(format!("invalid pattern:{:?} err:{}", re, e)))?;
+
+        // a char with case can not be used to seek if case insensitive
+        let head = match case_insensitive {
+            true => head.chars().take_while(|c| !has_case(*c)).collect(),
+            false => head,
+        };
+
+        Ok(Self {
+            head,
+            regex: Some(regex),
+        })
+    }
+
+    pub fn head(&self) -> &str {
+        &self.head
+    }
+
+    pub fn is_match(&self, term: &[u8]) -> bool {
+        if !term.starts_with(self.head.as_bytes()) {
+            return false;
+        }
+        match &self.regex {
+            Some(regex) => std::str::from_utf8(term).is_ok_and(|t| regex.is_match(t)),
+            None => true,
+        }
+    }
+}
+
+fn has_case(c: char) -> bool {
+    !c.to_lowercase().eq(c.to_uppercase())
+}
+
+/// literal chars before the first meta char of regex,
+/// the char followed by an optional quantifier is not sure, so it is excluded
+fn regex_head(re: &str) -> String {
+    if re.contains('|') {
+        return String::new();
+    }
+    let mut head = String::new();
+    let mut chars = re.chars().peekable();
+    while let Some(c) = chars.next() {
+        if "\\.+*?()|[]{}^$".contains(c) || matches!(chars.peek(), Some('*' | '?' | '{')) {
+            break;
+        }
+        head.push(c);
+    }
+    head
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_term_pattern() {
+        let p = TermPattern::prefix("ab", false).unwrap();
+        assert_eq!(p.head(), "ab");
+        assert!(p.is_match(b"abc"));
+        assert!(!p.is_match(b"Abc"));
+
+        let p = TermPattern::prefix("ab", true).unwrap();
+        assert_eq!(p.head(), "");
+        assert!(p.is_match(b"ABC"));
+        assert!(!p.is_match(b"bab"));
+
+        let p = TermPattern::wildcard("a_c%", false).unwrap();
+        assert_eq!(p.head(), "a");
+        assert!(p.is_match(b"abc"));
+        assert!(p.is_match("a你cde".as_bytes()));
+        assert!(!p.is_match(b"ac"));
+
+        let p = TermPattern::wildcard(r"10\%.txt", false).unwrap();
+        assert_eq!(p.head(), "10%.txt");
+        assert!(p.is_match(b"10%.txt"));
+        assert!(!p.is_match(b"10%atxt"));
+
+        let p = TermPattern::regexp("abc?d+", false).unwrap();
+        assert_eq!(p.head(), "ab");
+        assert!(p.is_match(b"abdd"));
+        assert!(!p.is_match(b"xabdd"));
+
+        let p = TermPattern::regexp("ab|cd", true).unwrap();
+        assert_eq!(p.head(), "");
+        assert!(p.is_match(b"CD"));
+
+        assert!(TermPattern::regexp("a(b", false).is_err());
+    }
+}
@@ -0,0 +1,221 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+use croaring::{Bitmap, Portable};
+use serde::{Deserialize, Serialize};
+
+use super::context::CacheKey;
+
+/// default size limit of filter cache
+pub const DEFAULT_FILTER_CACHE_BYTES: usize = 64 * 1024 * 1024;
+
+/// content version of a segment, end, doc count and del count, a cached bitmap is valid
+/// only for the same version. disk segment never changes, hot segment changes when records
+/// are written or deleted
+pub type Version = (u64, u64, u64);
+
+/// version of disk segments, it is not any version of hot segment
+pub const IMMUTABLE_VERSION: u64 = u64::MAX;
+
+struct Entry {
+    version: Version,
+    bitmap: Bitmap,
+    bytes: usize,
+    tick: u64,
+}
+
+#[derive(Default)]
+struct Inner {
+    entries: HashMap<CacheKey, Entry>,
+    // tick -> key, the first one is the least recently used
+    lru: BTreeMap<u64, CacheKey>,
+    tick: u64,
+    bytes: usize,
+}
+
+impl Inner {
+    fn remove(&mut self, key: &str) {
+        if let Some(e) = self.entries.remove(key) {
+            self.lru.remove(&e.tick);
+            self.bytes -= e.bytes;
+        }
+    }
+
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+}
+
+/// bitmaps of filters shared by queries of an engine, keys start with segment start,
+/// so the same filter of different segments are different entries.
+/// least recently used entries are evicted when size is over the limit
+pub struct FilterCache {
+    limit: AtomicUsize,
+    inner: Mutex<Inner>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl FilterCache {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit: AtomicUsize::new(limit),
+            inner: Mutex::new(Inner::default()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    pub fn get(&self, key: &str, version: Version) -> Option<Bitmap> {
+        let mut inner = self.inner.lock().unwrap();
+        let tick = inner.next_tick();
+
+        let found = match inner.entries.get_mut(key) {
+            Some(e) if e.version == version => {
+                let old = std::mem::replace(&mut e.tick, tick);
+                Some((old, e.bitmap.clone()))
+            }
+            Some(_) => {
+                // segment changed, it is out of date
+                inner.remove(key);
+                None
+            }
+            None => None,
+        };
+
+        match found {
+            Some((old, bitmap)) => {
+                inner.lru.remove(&old);
+                inner.lru.insert(tick, key.to_string());
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                Some(bitmap)
+            }
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    pub fn insert(&self, key: &str, version: Version, bitmap: &Bitmap) {
+        let bytes = key.len() + bitmap.get_serialized_size_in_bytes::<Portable>();
+        let limit = self.limit.load(Ordering::Relaxed);
+        if bytes > limit {
+            return;
+        }
+
+        let mut inner = self.inner.lock().unwrap();
+        inner.remove(key);
+
+        let tick = inner.next_tick();
+        inner.entries.insert(
+            key.to_string(),
+            Entry {
+                version,
+                bitmap: bitmap.clone(),
+                bytes,
+                tick,
+            },
+        );
+        inner.lru.insert(tick, key.to_string());
+        inner.bytes += bytes;
+
+        Self::evict(&mut inner, limit);
+    }
+
+    /// change size limit, entries over it are evicted at once
+    pub fn set_limit(&self, limit: usize) {
+        self.limit.store(limit, Ordering::Relaxed);
+        Self::evict(&mut self.inner.lock().unwrap(), limit);
+    }
+
+    pub fn clear(&self) {
+        *self.inner.lock().unwrap() = Inner::default();
+    }
+
+    pub fn info(&self) -> FilterCacheInfo {
+        let inner = self.inner.lock().unwrap();
+        let hits = self.hits.load(Ordering::Relaxed);
+        let misses = self.misses.load(Ordering::Relaxed);
+        FilterCacheInfo {
+            entries: inner.entries.len() as u64,
+            size_bytes: inner.bytes as u64,
+            limit_bytes: self.limit.load(Ordering::Relaxed) as u64,
+            hits,
+            misses,
+            hit_rate: if hits + misses == 0 {
+                0.0
+            } else {
+                hits as f64 / (hits + misses) as f64
+            },
+        }
+    }
+
+    fn evict(inner: &mut Inner, limit: usize) {
+        while inner.bytes > limit {
+            let key = match inner.lru.first_key_value() {
+                Some((_, key)) => key.clone(),
+                None => break,
+            };
+            inner.remove(&key);
+        }
+    }
+}
+
+impl Default for FilterCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_FILTER_CACHE_BYTES)
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct FilterCacheInfo {
+    pub entries: u64,
+    pub size_bytes: u64,
+    pub limit_bytes: u64,
+    pub hits: u64,
+    pub misses: u64,
+    pub hit_rate: f64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_cache() {
+        let bm = Bitmap::from_range(0..100);
+        let size = "a".len() + bm.get_serialized_size_in_bytes::<Portable>();
+
+        let cache = FilterCache::new(size * 2);
+        cache.insert("a", (1, 1, 0), &bm);
+        cache.insert("b", (1, 1, 0), &bm);
+        assert_eq!(cache.get("a", (1, 1, 0)).unwrap().cardinality(), 100);
+
+        // b is least recently used
+        cache.insert("c", (1, 1, 0), &bm);
+        assert!(cache.get("b", (1, 1, 0)).is_none());
+        assert!(cache.get("a", (1, 1, 0)).is_some());
+        assert!(cache.get("c", (1, 1, 0)).is_some());
+
+        // version changed
+        assert!(cache.get("a", (2, 2, 0)).is_none());
+        assert!(cache.get("a", (1, 1, 0)).is_none());
+
+        let info = cache.info();
+        assert_eq!(info.entries, 1);
+        assert_eq!(info.hits, 3);
+        assert_eq!(info.misses, 3);
+        assert_eq!(info.hit_rate, 0.5);
+
+        cache.set_limit(0);
+        assert_eq!(cache.info().entries, 0);
+        assert_eq!(cache.info().size_bytes, 0);
+    }
+}
@@ -1,12 +1,14 @@
 use std::{
     collections::HashMap,
-    sync::{atomic::AtomicU32, Mutex},
+    sync::{atomic::AtomicU32, Arc, Mutex},
 };
 
 use croaring::Bitmap;
 
 use crate::index_store::segment::SegmentReader;
 
+use super::cache::{FilterCache, Version};
+
 pub type CacheKey = String;
 
 pub struct SearchContext {
@@ -15,8 +17,18 @@ pub struct SearchContext {
 
 impl SearchContext {
     pub fn new(segments: &[SegmentReader]) -> SearchContext {
+        Self::with_cache(segments, None)
+    }
+
+    /// keyed bitmaps are got from and put to the filter cache if it is some
+    pub fn with_cache(
+        segments: &[SegmentReader],
+        cache: Option<Arc<FilterCache>>,
+    ) -> SearchContext {
         let segment_contexts = segments.iter().fold(HashMap::new(), |mut ctx, segment| {
-            ctx.insert(segment.start(), Mutex::new(SegmentContext::new()));
+            let mut sc = SegmentContext::new();
+            sc.cache = cache.clone().map(|c| (c, segment.version()));
+            ctx.insert(segment.start(), Mutex::new(sc));
             ctx
         });
         SearchContext { segment_contexts }
@@ -32,6 +44,11 @@ pub struct SegmentContext {
     cache_id: HashMap<CacheKey, u32>,
     cache_value: HashMap<u32, Bitmap>,
     sequence: AtomicU32,
+    // keys found and not found, for profile
+    cache_hits: AtomicU32,
+    cache_misses: AtomicU32,
+    // filter cache of engine and version of the segment
+    cache: Option<(Arc<FilterCache>, Version)>,
 }
 
 impl SegmentContext {
@@ -42,6 +59,13 @@ impl SegmentContext {
     }
 
     pub fn insert(&mut self, key: String, bm: Bitmap) -> u32 {
+        if let Some((cache, version)) = &self.cache {
+            cache.insert(&key, *version, &bm);
+        }
+        self.insert_local(key, bm)
+    }
+
+    fn insert_local(&mut self, key: String, bm: Bitmap) -> u32 {
         let id = self
             .sequence
             .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
@@ -50,8 +74,37 @@ impl SegmentContext {
         id
     }
 
-    pub fn get(&self, key: &String) -> Option<u32> {
-        self.cache_id.get(key).cloned()
+    pub fn get(&mut self, key: &String) -> Option<u32> {
+        let mut id = self.cache_id.get(key).cloned();
+        if id.is_none() {
+            let cached = self
+                .cache
+                .as_ref()
+                .and_then(|(cache, version)| cache.get(key, *version));
+            id = cached.map(|bm| self.insert_local(key.clone(), bm));
+        }
+        let counter = match id {
+            Some(_) => &self.cache_hits,
+            None => &self.cache_misses,
+        };
+        counter.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        id
+    }
+
+    /// hits and misses of get
+    pub fn cache_stats(&self) -> (u32, u32) {
+        (
+            self.cache_hits.load(std::sync::atomic::Ordering::Relaxed),
+            self.cache_misses.load(std::sync::atomic::Ordering::Relaxed),
+        )
+    }
+
+    /// key of a cached bitmap, values inserted without key have none
+    pub fn key_of(&self, id: u32) -> Option<&str> {
+        self.cache_id
+            .iter()
+            .find(|(_, v)| **v == id)
+            .map(|(k, _)| k.as_str())
     }
 
     pub fn value_insert(&mut self, bm: Bitmap) -> u32 {
@@ -0,0 +1,482 @@
+use proto::DottedPath;
+
+use crate::util::{self, CoreError, CoreResult};
+
+/// function score of ORDER BY, it makes a new score of hit from the query score
+/// and values of record, fields missed in record are 0
+#[derive(Debug, Clone)]
+pub enum ScoreFunction {
+    // score of the query
+    Score,
+    Number(f64),
+    Field(String),
+    Neg(Box<ScoreFunction>),
+    Binary(Box<ScoreFunction>, ArithOperator, Box<ScoreFunction>),
+    Math(MathFunction, Vec<ScoreFunction>),
+    FieldValueFactor {
+        field: String,
+        factor: f64,
+        modifier: Modifier,
+        missing: f64,
+    },
+    Decay(Decay),
+    // random in [0, 1), same seed same value for a record
+    Random {
+        seed: u64,
+        field: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum ArithOperator {
+    Plus,
+    Minus,
+    Multiply,
+    Divide,
+    Modulo,
+}
+
+/// `log` and `log1p` are natural log as java Math
+#[derive(Debug, Clone, Copy)]
+pub enum MathFunction {
+    Ln,
+    Log10,
+    Log1p,
+    Sqrt,
+    Abs,
+    Exp,
+    Floor,
+    Ceil,
+    Pow,
+    Min,
+    Max,
+}
+
+impl MathFunction {
+    /// function and number of args
+    pub fn from_name(name: &str) -> Option<(Self, usize)> {
+        let v = match name {
+            "ln" | "log" => (Self::Ln, 1),
+            "log10" => (Self::Log10, 1),
+            "log1p" | "ln1p" => (Self::Log1p, 1),
+            "sqrt" => (Self::Sqrt, 1),
+            "abs" => (Self::Abs, 1),
+            "exp" => (Self::Exp, 1),
+            "floor" => (Self::Floor, 1),
+            "ceil" => (Self::Ceil, 1),
+            "pow" => (Self::Pow, 2),
+            "min" => (Self::Min, 2),
+            "max" => (Self::Max, 2),
+            _ => return None,
+        };
+        Some(v)
+    }
+}
+
+/// modifier of field_value_factor, `log` is log10 as elasticsearch
+#[derive(Debug, Clone, Copy)]
+pub enum Modifier {
+    None,
+    Log,
+    Log1p,
+    Log2p,
+    Ln,
+    Ln1p,
+    Ln2p,
+    Square,
+    Sqrt,
+    Reciprocal,
+}
+
+impl Modifier {
+    pub fn from_name(name: &str) -> CoreResult<Self> {
+        let v = match name {
+            "none" => Self::None,
+            "log" => Self::Log,
+            "log1p" => Self::Log1p,
+            "log2p" => Self::Log2p,
+            "ln" => Self::Ln,
+            "ln1p" => Self::Ln1p,
+            "ln2p" => Self::Ln2p,
+            "square" => Self::Square,
+            "sqrt" => Self::Sqrt,
+            "reciprocal" => Self::Reciprocal,
+            _ => {
+                return Err(CoreError::InvalidParam(format!(
+                    "field_value_factor modifier:{:?} is not support",
+                    name
+                )))
+            }
+        };
+        Ok(v)
+    }
+
+    fn apply(&self, v: f64) -> f64 {
+        match self {
+            Self::None => v,
+            Self::Log => v.log10(),
+            Self::Log1p => (v + 1.0).log10(),
+            Self::Log2p => (v + 2.0).log10(),
+            Self::Ln => v.ln(),
+            Self::Ln1p => v.ln_1p(),
+            Self::Ln2p => (v + 2.0).ln(),
+            Self::Square => v * v,
+            Self::Sqrt => v.sqrt(),
+            Self::Reciprocal => 1.0 / v,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum DecayKind {
+    Gauss,
+    Linear,
+    Exp,
+}
+
+/// origin of decay, the type of field value is decided by it
+#[derive(Debug, Clone, Copy)]
+pub enum Origin {
+    Number(f64),
+    // milliseconds of unix epoch
+    Date(i64),
+    Geo { lat: f64, lon: f64 },
+}
+
+/// score is 1 in offset of origin and `decay` at scale + offset away,
+/// distance of date is in milliseconds and geo is in meters.
+/// records without the field are scored 1
+#[derive(Debug, Clone)]
+pub struct Decay {
+    pub kind: DecayKind,
+    pub field: String,
+    pub origin: Origin,
+    pub scale: f64,
+    pub offset: f64,
+    pub decay: f64,
+}
+
+impl Decay {
+    /// origin is a number, `lat,lon`, `now` or a date, scale and offset are in units of it,
+    /// like `10km` for geo and `7d` for date
+    pub fn new(
+        kind: DecayKind,
+        field: String,
+        origin: &str,
+        scale: &str,
+        offset: Option<&str>,
+        decay: f64,
+    ) -> CoreResult<Self> {
+        let origin = if let Ok(v) = origin.parse::<f64>() {
+            Origin::Number(v)
+        } else if let Some((lat, lon)) = parse_geo_str(origin) {
+            Origin::Geo { lat, lon }
+        } else if origin.eq_ignore_ascii_case("now") {
+            Origin::Date(util::now_millis())
+        } else {
+            Origin::Date(util::parse_date_millis(origin).ok_or_else(|| {
+                CoreError::InvalidParam(format!("decay origin:{:?} is invalid", origin))
+            })?)
+        };
+
+        let parse = |v: &str| -> CoreResult<f64> {
+            let n = match origin {
+                Origin::Number(_) => v.parse::<f64>().ok(),
+                Origin::Date(_) => parse_unit(
+                    v,
+                    &[
+                        ("ms", 1.0),
+                        ("s", 1e3),
+                        ("m", 6e4),
+                        ("h", 3.6e6),
+                        ("d", 8.64e7),
+                        ("w", 6.048e8),
+                    ],
+                ),
+                Origin::Geo { .. } => parse_unit(
+                    v,
+                    &[
+                        ("km", 1e3),
+                        ("m", 1.0),
+                        ("mi", 1609.344),
+                        ("yd", 0.9144),
+                        ("ft", 0.3048),
+                    ],
+                ),
+            };
+            n.filter(|n| *n >= 0.0).ok_or_else(|| {
+                CoreError::InvalidParam(format!("decay scale or offset:{:?} is invalid", v))
+            })
+        };
+
+        let scale = parse(scale)?;
+        if scale <= 0.0 {
+            return Err(CoreError::InvalidParam(
+                "decay scale must be positive".to_string(),
+            ));
+        }
+        if !(decay > 0.0 && decay < 1.0) {
+            return Err(CoreError::InvalidParam(format!(
+                "decay must be in (0, 1), got:{}",
+                decay
+            )));
+        }
+
+        Ok(Self {
+            kind,
+            field,
+            origin,
+            scale,
+            offset: offset.map(parse).transpose()?.unwrap_or(0.0),
+            decay,
+        })
+    }
+
+    fn eval(&self, value: Option<&serde_json::Value>) -> f64 {
+        let distance = match (self.origin, value) {
+            (_, None) => return 1.0,
+            (Origin::Number(o), Some(v)) => json_f64(v).map(|v| (v - o).abs()),
+            (Origin::Date(o), Some(v)) => json_date(v).map(|v| (v - o).abs() as f64),
+            (Origin::Geo { lat, lon }, Some(v)) => {
+                json_geo(v).map(|(lat2, lon2)| haversine(lat, lon, lat2, lon2))
+            }
+        };
+        let d = match distance {
+            Some(d) => (d - self.offset).max(0.0),
+            None => return 1.0,
+        };
+
+        match self.kind {
+            DecayKind::Gauss => (d * d * self.decay.ln() / (self.scale * self.scale)).exp(),
+            DecayKind::Exp => self.decay.powf(d / self.scale),
+            DecayKind::Linear => {
+                let s = self.scale / (1.0 - self.decay);
+                ((s - d) / s).max(0.0)
+            }
+        }
+    }
+}
+
+impl ScoreFunction {
+    /// new score of a hit, NaN is taken as 0
+    pub fn eval(&self, id: u64, score: f32, data: &serde_json::Value) -> f32 {
+        let v = self.eval_f64(id, score, data);
+        if v.is_nan() {
+            0.0
+        } else {
+            v as f32
+        }
+    }
+
+    fn eval_f64(&self, id: u64, score: f32, data: &serde_json::Value) -> f64 {
+        match self {
+            ScoreFunction::Score => score as f64,
+            ScoreFunction::Number(v) => *v,
+            ScoreFunction::Field(name) => data.path(name).and_then(|v| json_f64(&v)).unwrap_or(0.0),
+            ScoreFunction::Neg(f) => -f.eval_f64(id, score, data),
+            ScoreFunction::Binary(l, op, r) => {
+                let (l, r) = (l.eval_f64(id, score, data), r.eval_f64(id, score, data));
+                match op {
+                    ArithOperator::Plus => l + r,
+                    ArithOperator::Minus => l - r,
+                    ArithOperator::Multiply => l * r,
+                    ArithOperator::Divide => l / r,
+                    ArithOperator::Modulo => l % r,
+                }
+            }
+            ScoreFunction::Math(f, args) => {
+                let v = |i: usize| args[i].eval_f64(id, score, data);
+                match f {
+                    MathFunction::Ln => v(0).ln(),
+                    MathFunction::Log10 => v(0).log10(),
+                    MathFunction::Log1p => v(0).ln_1p(),
+                    MathFunction::Sqrt => v(0).sqrt(),
+                    MathFunction::Abs => v(0).abs(),
+                    MathFunction::Exp => v(0).exp(),
+                    MathFunction::Floor => v(0).floor(),
+                    MathFunction::Ceil => v(0).ceil(),
+                    MathFunction::Pow => v(0).powf(v(1)),
+                    MathFunction::Min => v(0).min(v(1)),
+                    MathFunction::Max => v(0).max(v(1)),
+                }
+            }
+            ScoreFunction::FieldValueFactor {
+                field,
+                factor,
+                modifier,
+                missing,
+            } => {
+                let v = data
+                    .path(field)
+                    .and_then(|v| json_f64(&v))
+                    .unwrap_or(*missing);
+                modifier.apply(v * factor)
+            }
+            ScoreFunction::Decay(decay) => decay.eval(data.path(&decay.field).as_deref()),
+            ScoreFunction::Random { seed, field } => {
+                let key = match field {
+                    Some(f) => fnv(
+                        util::json_value_to_string(&data.path(f).unwrap_or_default()).as_bytes(),
+                    ),
+                    None => id,
+                };
+                (splitmix(seed ^ key) >> 11) as f64 / (1u64 << 53) as f64
+            }
+        }
+    }
+}
+
+fn json_f64(v: &serde_json::Value) -> Option<f64> {
+    match v {
+        serde_json::Value::Number(n) => n.as_f64(),
+        serde_json::Value::Bool(b) => Some(*b as u8 as f64),
+        serde_json::Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+fn json_date(v: &serde_json::Value) -> Option<i64> {
+    match v {
+        serde_json::Value::Number(n) => n.as_i64(),
+        serde_json::Value::String(s) => util::parse_date_millis(s),
+        _ => None,
+    }
+}
+
+/// `{"lat": 1, "lon": 2}`, `"lat,lon"` or `[lon, lat]` as geojson
+fn json_geo(v: &serde_json::Value) -> Option<(f64, f64)> {
+    match v {
+        serde_json::Value::Object(o) => Some((json_f64(o.get("lat")?)?, json_f64(o.get("lon")?)?)),
+        serde_json::Value::String(s) => parse_geo_str(s),
+        serde_json::Value::Array(a) if a.len() == 2 => Some((json_f64(&a[1])?, json_f64(&a[0])?)),
+        _ => None,
+    }
+}
+
+fn parse_geo_str(s: &str) -> Option<(f64, f64)> {
+    let (lat, lon) = s.split_once(',')?;
+    Some((lat.trim().parse().ok()?, lon.trim().parse().ok()?))
+}
+
+/// number with one of units, no unit means the unit of 1
+fn parse_unit(v: &str, units: &[(&str, f64)]) -> Option<f64> {
+    let v = v.trim();
+    if let Ok(n) = v.parse::<f64>() {
+        return Some(n);
+    }
+    // longest unit first, so `ms` is not taken as `s`
+    units
+        .iter()
+        .filter(|(u, _)| v.ends_with(u))
+        .max_by_key(|(u, _)| u.len())
+        .and_then(|(u, m)| {
+            v[..v.len() - u.len()]
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .map(|n| n * m)
+        })
+}
+
+/// meters between two points
+fn haversine(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    const EARTH_RADIUS: f64 = 6_371_008.8;
+    let (p1, p2) = (lat1.to_radians(), lat2.to_radians());
+    let dp = p2 - p1;
+    let dl = (lon2 - lon1).to_radians();
+    let a = (dp / 2.0).sin().powi(2) + p1.cos() * p2.cos() * (dl / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS * a.sqrt().asin()
+}
+
+fn fnv(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |h, b| {
+        (h ^ *b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+fn splitmix(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e3779b97f4a7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_score_function() {
+        let data =
+            json!({"likes": 9, "price": 120, "at": "2024-01-11", "loc": {"lat": 0.0, "lon": 1.0}});
+
+        // _score * log(1 + likes)
+        let f = ScoreFunction::Binary(
+            Box::new(ScoreFunction::Score),
+            ArithOperator::Multiply,
+            Box::new(ScoreFunction::Math(
+                MathFunction::Log10,
+                vec![ScoreFunction::Binary(
+                    Box::new(ScoreFunction::Number(1.0)),
+                    ArithOperator::Plus,
+                    Box::new(ScoreFunction::Field("likes".to_string())),
+                )],
+            )),
+        );
+        assert_eq!(f.eval(1, 2.0, &data), 2.0);
+
+        let f = ScoreFunction::FieldValueFactor {
+            field: "none".to_string(),
+            factor: 2.0,
+            modifier: Modifier::Square,
+            missing: 3.0,
+        };
+        assert_eq!(f.eval(1, 0.0, &data), 36.0);
+
+        let decay = |kind, field: &str, origin, scale, offset| {
+            ScoreFunction::Decay(
+                Decay::new(kind, field.to_string(), origin, scale, offset, 0.5).unwrap(),
+            )
+            .eval(1, 0.0, &data)
+        };
+        assert!((decay(DecayKind::Gauss, "price", "100", "20", None) - 0.5).abs() < 1e-6);
+        assert!((decay(DecayKind::Exp, "price", "100", "10", Some("10")) - 0.5).abs() < 1e-6);
+        assert!((decay(DecayKind::Linear, "price", "100", "40", None) - 0.75).abs() < 1e-6);
+        assert_eq!(
+            decay(DecayKind::Gauss, "price", "110", "20", Some("10")),
+            1.0
+        );
+        assert_eq!(decay(DecayKind::Gauss, "none", "100", "20", None), 1.0);
+        assert!((decay(DecayKind::Exp, "at", "2024-01-01", "10d", None) - 0.5).abs() < 1e-6);
+        // one degree of longitude on equator is about 111km
+        let v = decay(DecayKind::Linear, "loc", "0,0", "222km", None);
+        assert!((v - 0.75).abs() < 1e-3, "{}", v);
+
+        assert!(Decay::new(DecayKind::Gauss, "a".to_string(), "1", "0", None, 0.5).is_err());
+        assert!(Decay::new(DecayKind::Gauss, "a".to_string(), "1", "1", None, 1.0).is_err());
+        assert!(Decay::new(DecayKind::Gauss, "a".to_string(), "x", "1", None, 0.5).is_err());
+
+        let random = |seed| ScoreFunction::Random { seed, field: None }.eval(7, 0.0, &data);
+        assert_eq!(random(1), random(1));
+        assert_ne!(random(1), random(2));
+        assert!((0.0..1.0).contains(&random(3)));
+    }
+
+    #[test]
+    fn test_parse_date() {
+        assert_eq!(util::parse_date_millis("1970-01-02"), Some(86_400_000));
+        assert_eq!(
+            util::parse_date_millis("2024-02-29T12:30:15.5Z"),
+            Some(1_709_209_815_500)
+        );
+        assert_eq!(
+            util::parse_date_millis("2024-02-29 20:30:15.5+08:00"),
+            Some(1_709_209_815_500)
+        );
+        assert_eq!(util::parse_date_millis("1969-12-31"), Some(-86_400_000));
+        assert_eq!(util::parse_date_millis("2024-13-01"), None);
+        assert_eq!(util::parse_date_millis("abc"), None);
+    }
+}
@@ -0,0 +1,313 @@
+use std::{collections::HashSet, sync::Arc};
+
+use itertools::Itertools;
+
+use crate::{analyzer::Analyzer, index_store::segment::SegmentReader};
+
+use super::plan::{Highlight, Query};
+
+/// max fragments returned for one field of a hit
+const MAX_FRAGMENTS: usize = 5;
+
+pub(crate) struct Highlighter {
+    option: Highlight,
+    analyzer: Arc<Analyzer>,
+    // analyzed tokens of text and phrase queries on the field, with fuzziness they are
+    // the terms expanded in the segments
+    tokens: HashSet<String>,
+}
+
+impl Highlighter {
+    pub fn new(
+        option: Highlight,
+        analyzer: Arc<Analyzer>,
+        query: Option<&Query>,
+        segments: &[SegmentReader],
+    ) -> Self {
+        let mut tokens = HashSet::new();
+        if let Some(query) = query {
+            collect_tokens(query, &option.field.name, &analyzer, segments, &mut tokens);
+        }
+        Self {
+            option,
+            analyzer,
+            tokens,
+        }
+    }
+
+    pub fn field_name(&self) -> &str {
+        &self.option.field.name
+    }
+
+    /// path of the highlighted text in record
+    pub fn source_path(&self) -> &str {
+        self.option.field.source_path()
+    }
+
+    /// mark matched tokens of text, return fragments, empty if nothing matched
+    pub fn highlight(&self, text: &str) -> Vec<String> {
+        if self.tokens.is_empty() {
+            return vec![];
+        }
+
+        // matched spans, overlapped spans are merged, stacked synonyms share the same span,
+        // offsets out of the text or not on char boundaries are skipped
+        let mut spans: Vec<(usize, usize)> = Vec::new();
+        for (start, end) in self
+            .analyzer
+            .analyzer_index(text)
+            .into_iter()
+            .filter(|t| self.tokens.contains(&t.name))
+            .map(|t| (t.start, t.end))
+            .filter(|(start, end)| start < end && text.get(*start..*end).is_some())
+            .sorted()
+        {
+            match spans.last_mut() {
+                Some(last) if start <= last.1 => last.1 = last.1.max(end),
+                _ => spans.push((start, end)),
+            }
+        }
+
+        if spans.is_empty() {
+            return vec![];
+        }
+
+        let size = self.option.fragment_size;
+        if size == 0 {
+            return vec![self.mark(text, 0, text.len(), &spans)];
+        }
+
+        let mut fragments = Vec::new();
+        let (mut i, mut prev_end) = (0, 0);
+        while i < spans.len() && fragments.len() < MAX_FRAGMENTS {
+            // put the first span in the middle of the fragment
+            let (start, end) = spans[i];
+            let pad = size.saturating_sub(end - start) / 2;
+            let mut begin =
+                floor_char_boundary(text, start.saturating_sub(pad).max(prev_end).min(start));
+            // do not cut words at the edges of fragment
+            if begin > 0 {
+                if let Some((p, c)) = text
+                    .get(begin..start)
+                    .and_then(|s| s.char_indices().find(|(_, c)| c.is_whitespace()))
+                {
+                    begin += p + c.len_utf8();
+                }
+            }
+            let mut stop = ceil_char_boundary(text, (begin + size).max(end));
+            if stop < text.len() {
+                if let Some((p, _)) = text
+                    .get(end..stop)
+                    .and_then(|s| s.char_indices().rev().find(|(_, c)| c.is_whitespace()))
+                {
+                    stop = end + p;
+                }
+            }
+
+            let n = spans[i..].iter().take_while(|(_, e)| *e <= stop).count();
+            // end the fragment before a span it would cut
+            if let Some((s, _)) = spans.get(i + n) {
+                stop = stop.min(*s);
+            }
+            fragments.push(self.mark(text, begin, stop, &spans[i..i + n]));
+
+            i += n;
+            prev_end = stop;
+        }
+
+        fragments
+    }
+
+    fn mark(&self, text: &str, begin: usize, end: usize, spans: &[(usize, usize)]) -> String {
+        let mut result = String::with_capacity(end - begin + spans.len() * 10);
+        let mut pos = begin;
+        for (start, stop) in spans {
+            result.push_str(&text[pos..*start]);
+            result.push_str(&self.option.pre_tag);
+            result.push_str(&text[*start..*stop]);
+            result.push_str(&self.option.post_tag);
+            pos = *stop;
+        }
+        result.push_str(&text[pos..end]);
+        result
+    }
+}
+
+fn collect_tokens(
+    query: &Query,
+    field: &str,
+    analyzer: &Analyzer,
+    segments: &[SegmentReader],
+    tokens: &mut HashSet<String>,
+) {
+    let analyze = |value: &str| {
+        analyzer
+            .analyzer_query(value)
+            .into_iter()
+            .flatten()
+            .map(|t| t.name)
+            .filter(|t| !t.trim().is_empty())
+            .collect::<Vec<_>>()
+    };
+    match query {
+        Query::Text {
+            value,
+            field: f,
+            fuzziness,
+            ..
+        } if f.name == field => {
+            for t in analyze(value) {
+                // a token is expanded to the terms as the query does in every segment
+                if let Some(fz) = fuzziness {
+                    for reader in segments.iter().filter_map(|s| s.get_text_reader(f).ok()) {
+                        tokens.extend(reader.fuzzy_terms(
+                            &t,
+                            fz.distance(&t),
+                            fz.prefix_length,
+                            fz.max_expansions,
+                        ));
+                    }
+                }
+                tokens.insert(t);
+            }
+        }
+        Query::Phrase {
+            value, field: f, ..
+        } if f.name == field => tokens.extend(analyze(value)),
+        Query::Logical { left, right, .. } => {
+            collect_tokens(left, field, analyzer, segments, tokens);
+            collect_tokens(right, field, analyzer, segments, tokens);
+        }
+        Query::Bool { must, should, .. } => {
+            for q in must.iter().chain(should.iter()) {
+                collect_tokens(q, field, analyzer, segments, tokens);
+            }
+        }
+        Query::DisMax { queries, .. }
+        | Query::MoreLikeThis {
+            resolved: Some((queries, _)),
+            ..
+        } => {
+            for q in queries {
+                collect_tokens(q, field, analyzer, segments, tokens);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn floor_char_boundary(text: &str, mut index: usize) -> usize {
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+fn ceil_char_boundary(text: &str, index: usize) -> usize {
+    let mut index = index.min(text.len());
+    while !text.is_char_boundary(index) {
+        index += 1;
+    }
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use proto::core::{field::Type, Field};
+
+    use super::*;
+
+    fn highlighter(query: &str, fragment_size: usize) -> Highlighter {
+        let field = Arc::new(Field {
+            name: "content".to_string(),
+            r#type: Type::Text as i32,
+            option: None,
+            source: String::new(),
+            array: false,
+            version: 0,
+        });
+        let query = Query::Text {
+            value: query.to_string(),
+            boost: 1.0,
+            operator: "or".to_string(),
+            field: field.clone(),
+            fuzziness: None,
+        };
+        Highlighter::new(
+            Highlight {
+                field,
+                pre_tag: "<em>".to_string(),
+                post_tag: "</em>".to_string(),
+                fragment_size,
+            },
+            Arc::new(Analyzer::default()),
+            Some(&query),
+            &[],
+        )
+    }
+
+    #[test]
+    fn test_highlight() {
+        let h = highlighter("rust", 0);
+        assert_eq!(
+            h.highlight("Rust is fast, 你好 rust!"),
+            vec!["<em>Rust</em> is fast, 你好 <em>rust</em>!"]
+        );
+        assert!(h.highlight("nothing here").is_empty());
+
+        let h = highlighter("fox", 12);
+        let text = "the quick brown fox jumps over the lazy dog, a fox again";
+        assert_eq!(
+            h.highlight(text),
+            vec!["<em>fox</em> jumps", "a <em>fox</em> again"]
+        );
+
+        // the second span starts inside the first fragment
+        let h = highlighter("fox", 5);
+        assert_eq!(
+            h.highlight("fox,fox"),
+            vec!["<em>fox</em>,", "<em>fox</em>"]
+        );
+    }
+
+    #[test]
+    fn test_highlight_fuzzy() {
+        use crate::{
+            index_store::seacher::plan::Fuzziness,
+            test_util::{mem_segment, test_scope},
+        };
+
+        let scope = test_scope(crate::easy_schema(
+            "test",
+            vec![("content".to_string(), Type::Text, None)],
+        ));
+        let segment = mem_segment(&scope, &[("r1", r#"{"content": "quack and quick"}"#)]);
+        let field = scope.get_field("content").unwrap();
+        let query = Query::Text {
+            value: "quikc".to_string(),
+            boost: 1.0,
+            operator: "or".to_string(),
+            field: field.clone(),
+            fuzziness: Some(Fuzziness {
+                distance: Some(1),
+                ..Default::default()
+            }),
+        };
+        let h = Highlighter::new(
+            Highlight {
+                field,
+                pre_tag: "<em>".to_string(),
+                post_tag: "</em>".to_string(),
+                fragment_size: 0,
+            },
+            Arc::new(Analyzer::default()),
+            Some(&query),
+            &[segment],
+        );
+        // terms are expanded in the segment, `quack` is two edits away
+        assert_eq!(
+            h.highlight("quack and quick"),
+            vec!["quack and <em>quick</em>"]
+        );
+    }
+}
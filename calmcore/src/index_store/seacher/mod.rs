@@ -1,34 +1,56 @@
+pub mod cache;
 pub(crate) mod context;
+pub(crate) mod function;
+mod highlight;
+mod more_like_this;
 pub(crate) mod plan;
 
 use std::{
     borrow::Cow,
     cmp::Ordering,
-    collections::{BTreeSet, HashMap},
+    collections::{BTreeMap, BTreeSet, HashMap},
     sync::{Arc, LazyLock},
+    time::Instant,
 };
 
+use cache::FilterCache;
 use context::SearchContext;
 use croaring::Bitmap;
+use function::ScoreFunction;
+use highlight::Highlighter;
 use itertools::Itertools;
-use plan::{PhysicsPlan, Query};
-use proto::core::{
-    field::{self},
-    Field, Hit, QueryResult, Record,
+use plan::{Collapse, DateHistogram, Highlight, PhysicsPlan, Query};
+use proto::{
+    core::{
+        field::{self},
+        query_result::Relation,
+        Bucket, Explanation, Field, Hit, Profile, QueryResult, Record, SegmentProfile,
+    },
+    DottedPath,
 };
 use rayon::iter::{
     IndexedParallelIterator, IntoParallelIterator, IntoParallelRefIterator, ParallelIterator,
 };
 
-use crate::util::{self, CoreError, CoreResult};
+use crate::{
+    util::{self, CoreError, CoreResult},
+    Scope,
+};
 
-use super::{segment::SegmentReader, stream::HitStream};
+use super::{
+    index_fulltext::FulltextIndex,
+    segment::SegmentReader,
+    stream::{BitmapStream, HitStream},
+};
 
 static SCORE_FIELD: LazyLock<Arc<Field>> = LazyLock::new(|| {
     Arc::new(Field {
         name: String::from("_score"),
         r#type: field::Type::Float as i32,
         option: None,
+        source: String::new(),
+        array: false,
+        version: 0,
     })
 });
 
@@ -38,7 +60,7 @@ pub struct SegmentSearcher<'a> {
 }
 
 impl SegmentSearcher<'_> {
-    pub fn next(&mut self) -> Option<Hit> {
+    pub fn next(&mut self, explain: bool) -> Option<Hit> {
         self.stream.next();
 
         self.stream.value().map(|id| Hit {
@@ -46,6 +68,9 @@ impl SegmentSearcher<'_> {
             score: self.stream.score(),
             record: None,
             sort: vec![],
+            highlights: Default::default(),
+            explanation: explain.then(|| self.stream.explain()),
+            inner_hits: vec![],
         })
     }
 
@@ -59,29 +84,84 @@ type Filters = Vec<Bitmap>;
 
 pub struct Searcher {
     segments: Vec<SegmentReader>,
+    cache: Option<Arc<FilterCache>>,
+    scope: Option<Arc<Scope>>,
 }
 
 impl Searcher {
     pub fn new(mut segments: Vec<SegmentReader>) -> Self {
         segments.sort_by_key(|r| std::cmp::Reverse(r.start()));
-        Self { segments }
+        Self {
+            segments,
+            cache: None,
+            scope: None,
+        }
+    }
+
+    /// filters are shared with other queries by the cache
+    pub fn with_cache(segments: Vec<SegmentReader>, cache: Arc<FilterCache>) -> Self {
+        Self {
+            cache: Some(cache),
+            ..Self::new(segments)
+        }
+    }
+
+    /// fields of order by are found in the scope, so an altered field sorts by its version
+    pub fn with_scope(mut self, scope: Arc<Scope>) -> Self {
+        self.scope = Some(scope);
+        self
     }
 
-    pub fn search_query(&self, query: Query) -> CoreResult<QueryResult> {
+    pub fn search_query(&self, mut query: Query) -> CoreResult<QueryResult> {
         log::debug!("search_query:{:?}", query);
+        self.rewrite(&mut query)?;
+        if let Query::Count { .. } = query {
+            return Ok(QueryResult {
+                hits: vec![],
+                total_hits: self.count(query)?,
+                profile: None,
+                total_hits_relation: Relation::Eq as i32,
+                buckets: vec![],
+            });
+        }
+        let buckets = match &query {
+            Query::Search {
+                date_histogram: Some(h),
+                ..
+            } => self.date_histogram(&query, h)?,
+            _ => vec![],
+        };
         if let Query::Search {
             projection,
             query,
             order_by,
             limit,
+            highlight,
+            function,
+            explain,
+            cache,
+            track_total_hits,
+            collapse,
+            date_histogram: _,
         } = query
         {
+            let sc = match cache {
+                true => SearchContext::with_cache(&self.segments, self.cache.clone()),
+                false => SearchContext::new(&self.segments),
+            };
             self.search(
+                &sc,
                 projection,
                 query.as_ref().map(|q| q.as_ref()),
                 order_by,
                 limit,
+                highlight,
+                function.as_ref(),
+                explain,
+                track_total_hits,
+                collapse.as_ref(),
             )
+            .map(|result| QueryResult { buckets, ..result })
         } else {
             Err(CoreError::InvalidParam(format!(
                 "query is not a valid query:{:?}",
@@ -90,48 +170,251 @@ impl Searcher {
         }
     }
 
+    /// if explain, hits have explanation of score and result has the profile of query.
+    /// total_hits is the exact number of hits, with track_total_hits it is counted up to
+    /// the number, and it is the number with relation gte if more hits are matched.
+    /// with collapse, hits are the best of groups and total_hits is still the number of records
+    #[allow(clippy::too_many_arguments)]
     pub fn search(
         &self,
+        sc: &SearchContext,
         projection: Vec<String>,
         query: Option<&Query>,
         order_by: Vec<(String, bool)>,
         limit: (usize, usize),
+        highlight: Vec<Highlight>,
+        function: Option<&ScoreFunction>,
+        explain: bool,
+        track_total_hits: Option<u64>,
+        collapse: Option<&Collapse>,
     ) -> CoreResult<QueryResult> {
-        let sc = SearchContext::new(&self.segments);
+        let mut profile = explain.then(Profile::default);
 
-        let result = {
-            let (streams, filters) = self.query_execute(query, &sc)?;
+        let highlighters = highlight
+            .into_iter()
+            .map(|h| {
+                // segments without the field have no reader, the analyzer is made by the field
+                let analyzer = match self
+                    .segments
+                    .iter()
+                    .find_map(|s| s.get_text_reader(&h.field).ok())
+                {
+                    Some(reader) => reader.analyzer.clone(),
+                    None => FulltextIndex::make_analyzer(&h.field)?,
+                };
+                Ok(Highlighter::new(h, analyzer, query, &self.segments))
+            })
+            .collect::<CoreResult<Vec<_>>>()?;
 
-            // statistics total hits
-            let mut total_hits = filters.iter().map(|f| f.cardinality()).sum::<u64>();
+        let result = {
+            let (mut streams, filters) = self.query_execute(query, sc, &mut profile)?;
 
             let order_by = self.make_order_by(order_by)?;
 
-            let (hits, realcount) = if streams.is_empty() && order_by.is_empty() {
-                (self.topn_with_filter(limit, filters)?, None)
-            } else {
-                self.topn(limit, &order_by, streams)?
+            let now = Instant::now();
+            let (hits, total_hits) =
+                if streams.is_empty() && order_by.is_empty() && collapse.is_none() {
+                    // no query, filters are all records
+                    let total_hits = filters.iter().map(|f| f.cardinality()).sum::<u64>();
+                    (self.topn_with_filter(limit, filters, explain)?, total_hits)
+                } else {
+                    // no query, but records must be sorted
+                    if streams.is_empty() {
+                        streams = filters
+                            .iter()
+                            .zip(self.segments.iter())
+                            .map(|(f, s)| {
+                                Box::new(BitmapStream::new(s.start(), f.iter(), 0.0))
+                                    as Box<dyn HitStream>
+                            })
+                            .collect();
+                    }
+                    match collapse {
+                        Some(c) => {
+                            self.topn_collapse(limit, &order_by, streams, function, explain, c)?
+                        }
+                        None => self.topn(
+                            limit,
+                            &order_by,
+                            streams,
+                            function,
+                            explain,
+                            track_total_hits,
+                        )?,
+                    }
+                };
+            record_time(&mut profile, "topn", now);
+
+            let (total_hits, relation) = match track_total_hits {
+                Some(n) if total_hits > n => (n, Relation::Gte),
+                _ => (total_hits, Relation::Eq),
             };
 
-            if let Some(realcount) = realcount {
-                total_hits = realcount;
+            let now = Instant::now();
+            let hits = self.projection(&projection, &highlighters, hits)?;
+            record_time(&mut profile, "fetch", now);
+
+            QueryResult {
+                hits,
+                total_hits,
+                profile,
+                total_hits_relation: relation as i32,
+                buckets: vec![],
+            }
+        };
+
+        Ok(result)
+    }
+
+    /// number of records matched by the query, only filters are evaluated,
+    /// hits are not scored or fetched. it is the same as total_hits of search
+    pub fn count(&self, mut query: Query) -> CoreResult<u64> {
+        self.rewrite(&mut query)?;
+        let (query, sc) = self.filter_context(&query)?;
+        let counts = self
+            .segments
+            .par_iter()
+            .map(|s| self.segment_count(s, query, &sc, u64::MAX))
+            .collect::<CoreResult<Vec<u64>>>()?;
+        Ok(counts.into_iter().sum())
+    }
+
+    /// if any record matched the query, segments after the first matched one are not evaluated
+    pub fn exists(&self, mut query: Query) -> CoreResult<bool> {
+        self.rewrite(&mut query)?;
+        let (query, sc) = self.filter_context(&query)?;
+        for s in self.segments.iter() {
+            if self.segment_count(s, query, &sc, 1)? > 0 {
+                return Ok(true);
             }
+        }
+        Ok(false)
+    }
 
-            let hits = self.projection(&projection, hits)?;
+    /// matched records counted by buckets of the date field, buckets without records are
+    /// not returned. it is evaluated with the filter context like count
+    fn date_histogram(&self, query: &Query, histogram: &DateHistogram) -> CoreResult<Vec<Bucket>> {
+        let (query, sc) = self.filter_context(query)?;
+        let (n, unit) = histogram.interval;
+        let counts = self
+            .segments
+            .par_iter()
+            .map(|s| {
+                let matched = self.segment_matched(s, query, &sc)?;
+                let mut counts = BTreeMap::new();
+                // terms are in order of millis, so buckets are counted in order
+                s.terms(&histogram.field, |term, records| {
+                    let millis = match memcomparable::from_slice::<i64>(term) {
+                        Ok(m) => m,
+                        Err(e) => {
+                            log::warn!("date term:{:?} err:{:?}", term, e);
+                            return;
+                        }
+                    };
+                    let count = records.and_cardinality(&matched);
+                    if count > 0 {
+                        if let Some(key) = util::round_date(millis, n, unit, histogram.time_zone) {
+                            *counts.entry(key).or_insert(0) += count;
+                        }
+                    }
+                })?;
+                Ok(counts)
+            })
+            .collect::<CoreResult<Vec<BTreeMap<i64, u64>>>>()?;
 
-            QueryResult { hits, total_hits }
+        let mut buckets = BTreeMap::new();
+        for (key, count) in counts.into_iter().flatten() {
+            *buckets.entry(key).or_insert(0) += count;
+        }
+        Ok(buckets
+            .into_iter()
+            .map(|(key, doc_count)| Bucket {
+                key,
+                key_as_string: util::format_date_millis(key, histogram.time_zone),
+                doc_count,
+            })
+            .collect())
+    }
+
+    /// matched records of a segment, records are got by stream if filter is not exact
+    fn segment_matched(
+        &self,
+        segment: &SegmentReader,
+        query: Option<&Query>,
+        sc: &SearchContext,
+    ) -> CoreResult<Bitmap> {
+        let query = match query {
+            Some(q) => q,
+            None => return Ok(segment.all_record()),
         };
+        let mut guard = sc.get(segment.start()).unwrap().lock().unwrap();
+        let plan = PhysicsPlan::new(segment, query, &mut guard)?;
+        Ok(plan.matched(segment.start(), &mut guard))
+    }
 
-        Ok(result)
+    fn filter_context<'a>(
+        &self,
+        query: &'a Query,
+    ) -> CoreResult<(Option<&'a Query>, SearchContext)> {
+        let (query, cache) = match query {
+            Query::Search { query, cache, .. } | Query::Count { query, cache } => {
+                (query.as_deref(), *cache)
+            }
+            _ => {
+                return Err(CoreError::InvalidParam(format!(
+                    "query is not a valid query:{:?}",
+                    query
+                )))
+            }
+        };
+        let sc = match cache {
+            true => SearchContext::with_cache(&self.segments, self.cache.clone()),
+            false => SearchContext::new(&self.segments),
+        };
+        Ok((query, sc))
+    }
+
+    /// matched records of a segment, if filter of the plan is not exact, records are
+    /// counted by stream and it stops when the count reaches limit
+    fn segment_count(
+        &self,
+        segment: &SegmentReader,
+        query: Option<&Query>,
+        sc: &SearchContext,
+        limit: u64,
+    ) -> CoreResult<u64> {
+        let query = match query {
+            Some(q) => q,
+            None => return Ok(segment.all_record().cardinality()),
+        };
+        let mut guard = sc.get(segment.start()).unwrap().lock().unwrap();
+        let plan = PhysicsPlan::new(segment, query, &mut guard)?;
+        let filter = plan.as_filter(&guard);
+        if plan.filter_is_exact() {
+            return Ok(filter.cardinality());
+        }
+
+        let mut stream = plan.into_stream(segment.start(), &mut guard, &filter);
+        let mut count = 0;
+        while count < limit {
+            stream.next();
+            if stream.value().is_none() {
+                break;
+            }
+            count += 1;
+        }
+        Ok(count)
     }
 
     fn query_execute(
         &self,
         query: Option<&Query>,
         sc: &SearchContext,
+        profile: &mut Option<Profile>,
     ) -> CoreResult<(Streams, Filters)> {
         let value = match query {
             Some(query) => {
+                let now = Instant::now();
                 let plans = self
                     .segments
                     .par_iter()
@@ -140,6 +423,9 @@ impl Searcher {
                         PhysicsPlan::new(s, query, &mut guard)
                     })
                     .collect::<CoreResult<Vec<PhysicsPlan>>>()?;
+                record_time(profile, "plan", now);
+
+                let now = Instant::now();
                 let filters = plans
                     .par_iter()
                     .zip(&self.segments)
@@ -148,7 +434,23 @@ impl Searcher {
                         p.as_filter(&guard)
                     })
                     .collect::<Vec<_>>();
+                record_time(profile, "filter", now);
 
+                // streams change bitmaps in context, so plans are described before
+                if let Some(profile) = profile {
+                    for (p, s) in plans.iter().zip(&self.segments) {
+                        let guard = sc.get(s.start()).unwrap().lock().unwrap();
+                        let (cache_hits, cache_misses) = guard.cache_stats();
+                        profile.segments.push(SegmentProfile {
+                            start: s.start(),
+                            plan: Some(p.describe(&guard)),
+                            cache_hits,
+                            cache_misses,
+                        });
+                    }
+                }
+
+                let now = Instant::now();
                 let streams = plans
                     .into_par_iter()
                     .zip(self.segments.par_iter())
@@ -159,6 +461,7 @@ impl Searcher {
                         p.into_stream(s.start(), &mut guard, f)
                     })
                     .collect::<Vec<_>>();
+                record_time(profile, "stream", now);
                 (streams, filters)
             }
             None => {
@@ -169,24 +472,62 @@ impl Searcher {
         Ok(value)
     }
 
-    fn projection(&self, projection: &Vec<String>, hits: Vec<SortedHit>) -> CoreResult<Vec<Hit>> {
+    fn projection(
+        &self,
+        projection: &Vec<String>,
+        highlighters: &[Highlighter],
+        hits: Vec<SortedHit>,
+    ) -> CoreResult<Vec<Hit>> {
         let mut result = Vec::with_capacity(hits.len());
         for hit in hits {
             let SortedHit {
                 id,
                 score,
                 mut record,
-                value,
+                mut value,
                 sort,
-                ..
+                explanation,
+                inner_hits,
             } = hit;
 
+            let inner_hits = self.projection(projection, highlighters, inner_hits)?;
+
+            // hits from filter only have no value parsed
+            if (!highlighters.is_empty() || !projection.is_empty()) && value.is_null() {
+                value = util::record_json(&record.data)?;
+            }
+
+            let highlights = highlighters
+                .iter()
+                .filter_map(|h| {
+                    let text = value.path(h.source_path())?;
+                    let text = text.as_str()?;
+                    let fragments = h.highlight(text);
+                    if fragments.is_empty() {
+                        return None;
+                    }
+                    Some((
+                        h.field_name().to_string(),
+                        proto::core::Highlight { fragments },
+                    ))
+                })
+                .collect();
+
             if !projection.is_empty() {
-                let mut new_obj = HashMap::new();
+                let mut new_obj = serde_json::Map::new();
+                // `author.name` is kept as {"author": {"name": ..}}
+                for field in projection
+                    .iter()
+                    .filter(|f| f.contains('.') && value.get(f.as_str()).is_none())
+                {
+                    if let Some(v) = value.path(field) {
+                        insert_path(&mut new_obj, field, v.into_owned());
+                    }
+                }
                 if let serde_json::Value::Object(mut data) = value {
                     for field in projection {
                         if let Some(v) = data.remove(field) {
-                            new_obj.insert(field, v);
+                            new_obj.insert(field.clone(), v);
                         }
                     }
                     if let Ok(v) = serde_json::to_vec(&new_obj) {
@@ -200,6 +541,9 @@ impl Searcher {
                 score,
                 record: Some(record),
                 sort,
+                highlights,
+                explanation,
+                inner_hits,
             });
         }
 
@@ -213,7 +557,17 @@ impl Searcher {
                 let field = if field.eq_ignore_ascii_case("_score") {
                     SCORE_FIELD.clone()
                 } else {
-                    match self.segments[0].get_field(&field) {
+                    let scope_field = self.scope.as_ref().and_then(|s| s.get_field(&field));
+                    let name = match &scope_field {
+                        Some(f) => f.index_name().into_owned(),
+                        None => field.clone(),
+                    };
+                    match self
+                        .segments
+                        .iter()
+                        .find_map(|s| s.get_field(&name))
+                        .or(scope_field)
+                    {
                         Some(f) => f,
                         None => {
                             return Err(CoreError::InvalidParam(format!(
@@ -232,6 +586,7 @@ impl Searcher {
         &self,
         limit: (usize, usize),
         filters: Vec<Bitmap>,
+        explain: bool,
     ) -> CoreResult<Vec<SortedHit>> {
         let size = limit.0 + limit.1;
 
@@ -255,6 +610,12 @@ impl Searcher {
                         record: record.into_owned(),
                         value: serde_json::Value::Null,
                         sort: Vec::new(),
+                        explanation: explain.then(|| Explanation {
+                            value: 0.0,
+                            description: "no query, records are not scored".to_string(),
+                            details: vec![],
+                        }),
+                        inner_hits: vec![],
                     });
                 }
 
@@ -268,13 +629,16 @@ impl Searcher {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct SortedHit {
     id: u64,
     score: f32,
     record: Record,
     value: serde_json::Value,
     sort: Vec<Vec<u8>>,
+    explanation: Option<Explanation>,
+    // top hits of the collapsed group, the first is the hit itself
+    inner_hits: Vec<SortedHit>,
 }
 
 impl SortedHit {
@@ -285,6 +649,8 @@ impl SortedHit {
             record,
             value,
             sort,
+            explanation: hit.explanation,
+            inner_hits: vec![],
         }
     }
 
@@ -299,22 +665,33 @@ impl SortedHit {
     }
 
     fn make_sort(
-        id: u64,
-        score: f32,
+        hit: &mut Hit,
         data: &[u8],
         order_by: &Vec<(Arc<Field>, bool)>,
+        function: Option<&ScoreFunction>,
     ) -> CoreResult<(serde_json::Value, Vec<Vec<u8>>)> {
-        let data = serde_json::from_slice::<serde_json::Value>(data)?;
+        let data = util::record_json(data)?;
+
+        if let Some(f) = function {
+            hit.score = f.eval(hit.id, hit.score, &data);
+            hit.explanation = hit.explanation.take().map(|e| Explanation {
+                value: hit.score,
+                description: format!("function score {:?}", f),
+                details: vec![e],
+            });
+        }
+        let (id, score) = (hit.id, hit.score);
 
+        // date values are sorted by millis, so values in different zones are compared right
         let encode_field =
-            |tp: &field::Type, value: Option<&serde_json::Value>| -> CoreResult<Vec<u8>> {
+            |field: &Field, value: Option<&serde_json::Value>| -> CoreResult<Vec<u8>> {
                 let value = match value {
                     Some(v) => v,
                     None => return Ok(vec![]),
                 };
                 let value = util::json_value_to_string(value);
 
-                util::str_to_vec_fix_type(&value, tp)
+                util::str_to_vec_fix_field(&value, field)
             };
 
         let mut sort = Vec::with_capacity(order_by.len() + 1);
@@ -331,7 +708,10 @@ impl SortedHit {
                     }
                     sort.push(vec);
                 }
-                _ => sort.push(encode_field(&field.r#type(), data.get(&field.name))?),
+                _ => sort.push(encode_field(
+                    field,
+                    data.path(field.source_path()).as_deref(),
+                )?),
             }
         }
 
@@ -367,7 +747,10 @@ impl Searcher {
         limit: (usize, usize),
         order_by: &Vec<(Arc<Field>, bool)>,
         streams: Vec<Box<dyn HitStream>>,
-    ) -> CoreResult<(Vec<SortedHit>, Option<u64>)> {
+        function: Option<&ScoreFunction>,
+        explain: bool,
+        track_total_hits: Option<u64>,
+    ) -> CoreResult<(Vec<SortedHit>, u64)> {
         let streams = self
             .segments
             .par_iter()
@@ -381,9 +764,22 @@ impl Searcher {
 
         let mut real_count: u64 = 0;
 
-        for mut stream in streams {
+        // order by is empty and heap is full, hits after are only counted
+        let mut full = false;
+
+        'outer: for mut stream in streams {
             let mut min: Option<SortedHit> = None;
-            while let Some(hit) = stream.next() {
+            while let Some(mut hit) = stream.next(explain && !full) {
+                // hits after the heap is full are only counted, their records are not read
+                if full {
+                    real_count += 1;
+                    // more than tracked, total is a lower bound
+                    if track_total_hits.is_some_and(|n| real_count > n) {
+                        break 'outer;
+                    }
+                    continue;
+                }
+
                 let record = match stream.doc(hit.id) {
                     Some(record) => record,
                     None => continue,
@@ -392,7 +788,7 @@ impl Searcher {
                 real_count += 1;
 
                 let (value, sort) =
-                    SortedHit::make_sort(hit.id, hit.score, &record.data, order_by)?;
+                    SortedHit::make_sort(&mut hit, &record.data, order_by, function)?;
 
                 let sort_hit = if min.is_none()
                     || min.as_ref().unwrap().cmp_record(&sort) == Ordering::Greater
@@ -406,20 +802,120 @@ impl Searcher {
 
                 if heap.len() > size {
                     min = heap.pop_last();
-                    //if order by only one field, it is id ,so we can return early
-                    if order_by.is_empty() {
-                        return Ok((
-                            heap.into_iter().skip(limit.0).take(limit.1).collect_vec(),
-                            None,
-                        ));
-                    }
+                    //if order by only one field, it is id, hits after are not kept
+                    full = order_by.is_empty();
                 }
             }
         }
 
         Ok((
             heap.into_iter().skip(limit.0).take(limit.1).collect_vec(),
-            Some(real_count),
+            real_count,
+        ))
+    }
+
+    /// best hit of every value of the collapse field, groups are sorted by their best hits.
+    /// all hits must be visited to know the best of a group, so it never stops early
+    fn topn_collapse(
+        &self,
+        limit: (usize, usize),
+        order_by: &Vec<(Arc<Field>, bool)>,
+        streams: Vec<Box<dyn HitStream>>,
+        function: Option<&ScoreFunction>,
+        explain: bool,
+        collapse: &Collapse,
+    ) -> CoreResult<(Vec<SortedHit>, u64)> {
+        let streams = self
+            .segments
+            .par_iter()
+            .zip(streams)
+            .map(|(segment, stream)| SegmentSearcher { stream, segment })
+            .collect::<Vec<SegmentSearcher>>();
+
+        let field = &collapse.field;
+        let keep = collapse.inner_hits.max(1);
+
+        // value of field -> top hits of the group, none is records without the field
+        let mut groups: HashMap<Option<Vec<u8>>, BTreeSet<SortedHit>> = HashMap::new();
+
+        let mut real_count: u64 = 0;
+
+        for mut stream in streams {
+            while let Some(mut hit) = stream.next(explain) {
+                let record = match stream.doc(hit.id) {
+                    Some(record) => record,
+                    None => continue,
+                };
+
+                real_count += 1;
+
+                let (value, sort) =
+                    SortedHit::make_sort(&mut hit, &record.data, order_by, function)?;
+
+                let key = value
+                    .path(field.source_path())
+                    .filter(|v| !v.is_null())
+                    .map(|v| util::str_to_vec_fix_field(&util::json_value_to_string(&v), field))
+                    .transpose()?;
+
+                let group = groups.entry(key).or_default();
+                if group.len() >= keep
+                    && group.last().unwrap().cmp_record(&sort) != Ordering::Greater
+                {
+                    continue;
+                }
+
+                group.insert(SortedHit::new(hit, (*record).clone(), value, sort));
+                if group.len() > keep {
+                    group.pop_last();
+                }
+            }
+        }
+
+        let mut bests = groups
+            .into_values()
+            .map(|group| {
+                let mut group = group.into_iter();
+                let mut best = group.next().unwrap();
+                if collapse.inner_hits > 0 {
+                    best.inner_hits = std::iter::once(best.clone()).chain(group).collect();
+                }
+                best
+            })
+            .collect_vec();
+        bests.sort();
+
+        Ok((
+            bests.into_iter().skip(limit.0).take(limit.1).collect_vec(),
+            real_count,
         ))
     }
 }
+
+/// insert value to nested objects by a dotted path
+fn insert_path(
+    obj: &mut serde_json::Map<String, serde_json::Value>,
+    path: &str,
+    value: serde_json::Value,
+) {
+    match path.split_once('.') {
+        Some((head, rest)) => {
+            let child = obj
+                .entry(head)
+                .or_insert_with(|| serde_json::Value::Object(Default::default()));
+            if let serde_json::Value::Object(child) = child {
+                insert_path(child, rest, value);
+            }
+        }
+        None => {
+            obj.insert(path.to_string(), value);
+        }
+    }
+}
+
+/// add micros since start to the phase of profile
+fn record_time(profile: &mut Option<Profile>, phase: &str, start: Instant) {
+    if let Some(p) = profile {
+        *p.timings.entry(phase.to_string()).or_default() += start.elapsed().as_micros() as u64;
+    }
+}
@@ -0,0 +1,166 @@
+use std::{collections::HashMap, sync::Arc};
+
+use itertools::Itertools;
+use proto::{core::Field, DottedPath};
+
+use crate::util::{self, CoreResult};
+
+use super::{plan::Query, Searcher};
+
+impl Searcher {
+    /// resolve more like this in the query tree, it needs term statistics of all segments
+    pub(crate) fn rewrite(&self, query: &mut Query) -> CoreResult<()> {
+        match query {
+            Query::MoreLikeThis {
+                like,
+                fields,
+                max_query_terms,
+                min_term_freq,
+                min_doc_freq,
+                boost,
+                resolved,
+            } if resolved.is_none() => {
+                *resolved = Some(self.like_terms(
+                    like,
+                    fields,
+                    *max_query_terms,
+                    *min_term_freq,
+                    *min_doc_freq,
+                    *boost,
+                )?);
+            }
+            Query::Not { query, .. } => self.rewrite(query)?,
+            Query::Logical { left, right, .. } => {
+                self.rewrite(left)?;
+                self.rewrite(right)?;
+            }
+            Query::Bool {
+                must,
+                should,
+                must_not,
+                filter,
+                ..
+            } => {
+                for q in must
+                    .iter_mut()
+                    .chain(should.iter_mut())
+                    .chain(must_not.iter_mut())
+                    .chain(filter.iter_mut())
+                {
+                    self.rewrite(q)?;
+                }
+            }
+            Query::DisMax { queries, .. } => {
+                for q in queries.iter_mut() {
+                    self.rewrite(q)?;
+                }
+            }
+            Query::Search {
+                query: Some(query), ..
+            }
+            | Query::Count {
+                query: Some(query), ..
+            } => self.rewrite(query)?,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// top terms by tf-idf of the liked record or text, a text query for each term
+    /// boosted by its weight, the best term is boost
+    fn like_terms(
+        &self,
+        like: &str,
+        fields: &[Arc<Field>],
+        max_query_terms: usize,
+        min_term_freq: usize,
+        min_doc_freq: usize,
+        boost: f32,
+    ) -> CoreResult<(Vec<Query>, Option<u64>)> {
+        // segments are sorted by start desc, the first one has the latest version
+        let liked = self
+            .segments
+            .iter()
+            .find_map(|s| s.get(&like.to_string()).map(|r| (s, r.into_owned())));
+        let value = match &liked {
+            Some((_, record)) => Some(util::record_json(&record.data)?),
+            None => None,
+        };
+
+        // (tf-idf, field, term)
+        let mut candidates = Vec::new();
+        for field in fields {
+            let readers = self
+                .segments
+                .iter()
+                .filter_map(|s| s.get_text_reader(field).ok())
+                .collect_vec();
+            if readers.is_empty() {
+                continue;
+            }
+            let text = match &value {
+                Some(v) => match v.path(field.source_path()) {
+                    Some(text) => util::json_value_to_string(&text).into_owned(),
+                    None => continue,
+                },
+                None => like.to_string(),
+            };
+
+            let mut freqs: HashMap<String, usize> = HashMap::new();
+            for token in readers[0].analyzer.analyzer_index(&text) {
+                if !token.name.trim().is_empty() {
+                    *freqs.entry(token.name).or_default() += 1;
+                }
+            }
+            // the record is indexed, positions of its terms are in doc index
+            if let Some((segment, record)) = &liked {
+                if let Ok(reader) = segment.get_text_reader(field) {
+                    let doc_id = (record.id - segment.start()) as u32;
+                    for (term, tf) in freqs.iter_mut() {
+                        if let Some(positions) = reader.doc_index.get(&(doc_id, term.clone())) {
+                            *tf = positions.len();
+                        }
+                    }
+                }
+            }
+
+            let doc_count = readers.iter().map(|r| r.doc_count as f32).sum::<f32>();
+            for (term, tf) in freqs {
+                if tf < min_term_freq {
+                    continue;
+                }
+                let df = readers
+                    .iter()
+                    .map(|r| r.token_index.get(&term).map_or(0, |b| b.cardinality()))
+                    .sum::<u64>();
+                // a term only in the liked record matches nothing else
+                if df <= liked.is_some() as u64 || df < min_doc_freq as u64 {
+                    continue;
+                }
+                let df = df as f32;
+                let idf = ((doc_count - df + 0.5) / (df + 0.5) + 1.0).ln();
+                candidates.push((tf as f32 * idf, field.clone(), term));
+            }
+        }
+
+        let candidates = candidates
+            .into_iter()
+            .sorted_by(|a, b| b.0.total_cmp(&a.0).then_with(|| a.2.cmp(&b.2)))
+            .take(max_query_terms)
+            .collect_vec();
+        let max = candidates.first().map_or(1.0, |c| c.0.max(f32::EPSILON));
+
+        let queries = candidates
+            .into_iter()
+            .map(|(weight, field, term)| Query::Text {
+                value: term,
+                boost: boost * weight / max,
+                operator: "or".to_string(),
+                field,
+                fuzziness: None,
+            })
+            .collect();
+
+        Ok((queries, liked.map(|(_, r)| r.id)))
+    }
+}
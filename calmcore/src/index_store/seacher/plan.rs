@@ -3,19 +3,21 @@ use itertools::Itertools;
 
 use std::{collections::HashMap, sync::Arc};
 
-use proto::core::Field;
+use proto::core::{Explanation, Field};
 
 use crate::{
     analyzer::Token,
     index_store::{
-        index_fulltext::reader::FulltextIndexReader,
+        index_fulltext::reader::{FulltextIndexReader, TokenTerms},
+        index_nested::NestedIndexReader,
+        pattern::TermPattern,
         segment::SegmentReader,
-        stream::{BitmapStream, CombHitStream, HitStream, TextStream},
+        stream::{BitmapStream, CombHitStream, FilterStream, HitStream, TextStream},
     },
-    util::CoreResult,
+    util::{CoreError, CoreResult},
 };
 
-use super::context::SegmentContext;
+use super::{context::SegmentContext, function::ScoreFunction};
 
 #[derive(Debug, Clone)]
 pub enum LogicOperator {
@@ -61,20 +63,164 @@ pub enum Query {
         boost: f32,
         operator: String, // default is "or"
         field: Arc<Field>,
+        fuzziness: Option<Fuzziness>,
+    },
+    Prefix {
+        value: String,
+        case_insensitive: bool,
+        boost: f32,
+        field: Arc<Field>,
+    },
+    // `%` matches any chars, `_` matches one char
+    Wildcard {
+        value: String,
+        case_insensitive: bool,
+        boost: f32,
+        field: Arc<Field>,
+    },
+    Regexp {
+        value: String,
+        case_insensitive: bool,
+        boost: f32,
+        field: Arc<Field>,
+    },
+    // records have value of the field
+    Exists {
+        boost: f32,
+        field: Arc<Field>,
+    },
+    // records not matched by the query
+    Not {
+        query: Box<Query>,
+        boost: f32,
     },
     Logical {
         left: Box<Query>,
         right: Box<Query>,
         operator: LogicOperator,
     },
+    // must and should clauses are scored, filter and must_not clauses are not
+    Bool {
+        must: Vec<Query>,
+        should: Vec<Query>,
+        must_not: Vec<Query>,
+        filter: Vec<Query>,
+        // records must match at least this number of should clauses
+        minimum_should_match: usize,
+    },
+    // records matched by any query, scored by the best one,
+    // scores of others are added by tie_breaker, 1.0 means sum of all
+    DisMax {
+        queries: Vec<Query>,
+        tie_breaker: f32,
+    },
+    // records have an object of the nested field matched by the query, fields of the
+    // query are fields of objects, so all conditions match in the same object
+    Nested {
+        path: Arc<Field>,
+        query: Box<Query>,
+        boost: f32,
+    },
+    // records like a record or a text, top tf-idf terms of the fields are searched,
+    // they are chosen by statistics of all segments, so searcher resolves it before planning
+    MoreLikeThis {
+        // name of a record, or a text if no record has the name
+        like: String,
+        fields: Vec<Arc<Field>>,
+        max_query_terms: usize,
+        min_term_freq: usize,
+        min_doc_freq: usize,
+        boost: f32,
+        // text queries of terms and id of the liked record which is excluded
+        resolved: Option<(Vec<Query>, Option<u64>)>,
+    },
     Search {
         // from: String,
         projection: Vec<String>,
         query: Option<Box<Query>>,
         order_by: Vec<(String, bool)>,
         limit: (usize, usize),
+        highlight: Vec<Highlight>,
+        // new score of hits, made in topn
+        function: Option<ScoreFunction>,
+        // explain score of hits and profile the query
+        explain: bool,
+        // filters are got from and put to the filter cache of engine
+        cache: bool,
+        // hits are counted up to this number, total is a lower bound if more, none is exact
+        track_total_hits: Option<u64>,
+        // only the best hit of every value of the field is returned
+        collapse: Option<Collapse>,
+        // matched records are counted by buckets of a date field
+        date_histogram: Option<DateHistogram>,
+    },
+    // number of matched records, hits are not scored or fetched
+    Count {
+        query: Option<Box<Query>>,
+        cache: bool,
     },
 }
+
+/// highlight matched tokens of a text field in hits
+#[derive(Debug, Clone)]
+pub struct Highlight {
+    pub field: Arc<Field>,
+    pub pre_tag: String,
+    pub post_tag: String,
+    // bytes of a fragment, 0 means the whole text
+    pub fragment_size: usize,
+}
+
+/// hits are grouped by value of a field, records without the field are one group
+#[derive(Debug, Clone)]
+pub struct Collapse {
+    pub field: Arc<Field>,
+    // top hits of a group kept in the best hit, 0 means none
+    pub inner_hits: usize,
+}
+
+/// buckets of a date field by interval, keys are the start of buckets in the time zone
+#[derive(Debug, Clone)]
+pub struct DateHistogram {
+    pub field: Arc<Field>,
+    // number and unit like (1, 'd')
+    pub interval: (i64, char),
+    // offset millis of the time zone
+    pub time_zone: i64,
+}
+
+/// expand tokens to terms in index within the edit distance
+#[derive(Debug, Clone)]
+pub struct Fuzziness {
+    // None means auto, by the length of token
+    pub distance: Option<u32>,
+    // chars must be same at the head of term
+    pub prefix_length: usize,
+    // max terms one token expand to
+    pub max_expansions: usize,
+}
+
+impl Default for Fuzziness {
+    fn default() -> Self {
+        Self {
+            distance: None,
+            prefix_length: 0,
+            max_expansions: 50,
+        }
+    }
+}
+
+impl Fuzziness {
+    pub fn distance(&self, token: &str) -> u32 {
+        self.distance
+            .unwrap_or_else(|| match token.chars().count() {
+                0..=2 => 0,
+                3..=5 => 1,
+                _ => 2,
+            })
+    }
+}
+
 impl Query {
     pub(crate) fn set_boost(&mut self, boost: f32) {
         match self {
@@ -83,24 +229,39 @@ impl Query {
             Query::InList { boost: b, .. } => *b = boost,
             Query::Phrase { boost: b, .. } => *b = boost,
             Query::Text { boost: b, .. } => *b = boost,
-            Query::Logical { .. } | Query::Search { .. } => unreachable!(),
+            Query::Prefix { boost: b, .. } => *b = boost,
+            Query::Wildcard { boost: b, .. } => *b = boost,
+            Query::Regexp { boost: b, .. } => *b = boost,
+            Query::Exists { boost: b, .. } => *b = boost,
+            Query::Not { boost: b, .. } => *b = boost,
+            Query::MoreLikeThis { boost: b, .. } => *b = boost,
+            Query::Nested { boost: b, .. } => *b = boost,
+            Query::Logical { .. }
+            | Query::Bool { .. }
+            | Query::DisMax { .. }
+            | Query::Search { .. }
+            | Query::Count { .. } => unreachable!(),
         }
     }
 }
 
 pub enum PhysicsPlan {
     Map(f32, u32),
-    // boost, terms, total_bitmap, token_bitmap, operator(true is and/ false is or), phrase_len(zero means no phrase)
+    // boost, token paths, total_bitmap, token_bitmap, operator(true is and/ false is or), phrase_len(zero means no phrase)
     Text(
         Arc<FulltextIndexReader>,
         f32,
-        Vec<Token>,
+        Vec<Vec<Token>>,
         u32,
-        HashMap<String, usize>,
+        TokenTerms,
         bool,
         i32,
     ),
     Combin(Vec<PhysicsPlan>, LogicOperator),
+    // must, should, minimum_should_match, matched bitmap
+    Bool(Vec<PhysicsPlan>, Vec<PhysicsPlan>, usize, u32),
+    // queries, tie_breaker
+    DisMax(Vec<PhysicsPlan>, f32),
 }
 
 impl PhysicsPlan {
@@ -117,7 +278,7 @@ impl PhysicsPlan {
                 field,
             } => match operator {
                 ComparisonOperator::Eq => {
-                    let key = format!("{}/{}={:?}", segment.start(), &field.name, value);
+                    let key = format!("{}/{}={:?}", segment.start(), field.index_name(), value);
                     if let Some(id) = sc.get(&key) {
                         return Ok(PhysicsPlan::Map(*boost, id));
                     }
@@ -127,7 +288,7 @@ impl PhysicsPlan {
                     Ok(PhysicsPlan::Map(*boost, sc.insert(key, rb)))
                 }
                 ComparisonOperator::NotEq => {
-                    let key = format!("{}{}!{:?}", segment.start(), &field.name, value);
+                    let key = format!("{}/{}!{:?}", segment.start(), field.index_name(), value);
                     if let Some(id) = sc.get(&key) {
                         return Ok(PhysicsPlan::Map(*boost, id));
                     }
@@ -146,7 +307,7 @@ impl PhysicsPlan {
                 let key = format!(
                     "{}/{}bt{:?}{}{:?}{}",
                     segment.start(),
-                    &field.name,
+                    field.index_name(),
                     low,
                     low_eq,
                     high,
@@ -159,7 +320,7 @@ impl PhysicsPlan {
                 Ok(PhysicsPlan::Map(*boost, sc.insert(key, rb)))
             }
             Query::InList { list, boost, field } => {
-                let key = format!("{}/{}in{:?}", segment.start(), &field.name, list,);
+                let key = format!("{}/{}in{:?}", segment.start(), field.index_name(), list,);
                 if let Some(id) = sc.get(&key) {
                     return Ok(PhysicsPlan::Map(*boost, id));
                 }
@@ -171,120 +332,65 @@ impl PhysicsPlan {
                 slop,
                 boost,
                 field,
-            } => {
-                let reader = segment.get_text_reader(field)?;
-
-                let tokens = reader.analyzer(value)?;
-
-                let mut total = None;
-
-                let mut out_tokens = Vec::new();
-                let mut out_keys = Vec::new();
-
-                let mut token_doc_len = HashMap::new();
-
-                for t in tokens.iter().map(|t| &t.name).unique() {
-                    let key = format!("{}/{}={:?}", segment.start(), &field.name, t);
-                    match sc.get(&key) {
-                        Some(id) => {
-                            let term_bitmap = sc.value_ref_get(id);
-                            token_doc_len.insert(t.clone(), term_bitmap.cardinality() as usize);
-                            total = total
-                                .map(|total| total & term_bitmap)
-                                .or_else(|| Some(term_bitmap.clone()));
-                        }
-                        None => {
-                            out_keys.push(key);
-                            out_tokens.push(t);
-                        }
-                    }
-                }
-
-                for ((b, key), t) in reader
-                    .tokens(&out_tokens)?
-                    .into_iter()
-                    .zip(out_keys)
-                    .zip(out_tokens)
-                {
-                    token_doc_len.insert(t.clone(), b.cardinality() as usize);
-                    total = total.map(|total| total & &b).or_else(|| Some(b.clone()));
-                    sc.insert(key, b);
-                }
-
-                let total_map = sc.value_insert(total.unwrap_or_default());
-
-                Ok(Self::Text(
-                    reader,
-                    *boost,
-                    tokens,
-                    total_map,
-                    token_doc_len,
-                    true,
-                    *slop,
-                ))
-            }
+            } => Self::text(segment, field, value, *boost, true, *slop, None, sc),
             Query::Text {
                 value,
                 operator,
                 boost,
                 field,
+                fuzziness,
             } => {
                 let operator = "and".eq_ignore_ascii_case(operator);
-
-                let reader = segment.get_text_reader(field)?;
-
-                let tokens = reader.analyzer(value)?;
-
-                let mut total = None;
-
-                let mut out_tokens = Vec::new();
-                let mut out_keys = Vec::new();
-
-                let mut token_doc_len = HashMap::new();
-
-                for t in tokens.iter().map(|t| &t.name).unique() {
-                    let key = format!("{}/{}={:?}", segment.start(), &field.name, t);
-                    match sc.get(&key) {
-                        Some(id) => {
-                            let b = sc.value_ref_get(id);
-                            total = total
-                                .map(|total| if operator { total & b } else { total | b })
-                                .or_else(|| Some(b.clone()));
-                            token_doc_len.insert(t.clone(), b.cardinality() as usize);
-                        }
-                        None => {
-                            out_keys.push(key);
-                            out_tokens.push(t);
-                        }
-                    }
-                }
-
-                for ((b, key), t) in reader
-                    .tokens(&out_tokens)?
-                    .into_iter()
-                    .zip(out_keys)
-                    .zip(out_tokens)
-                {
-                    token_doc_len.insert(t.clone(), b.cardinality() as usize);
-
-                    total = total
-                        .map(|total| if operator { total & &b } else { total | &b })
-                        .or_else(|| Some(b.clone()));
-
-                    sc.insert(key, b);
-                }
-
-                let total_map = sc.value_insert(total.unwrap_or_default());
-
-                Ok(Self::Text(
-                    reader,
+                Self::text(
+                    segment,
+                    field,
+                    value,
                     *boost,
-                    tokens,
-                    total_map,
-                    token_doc_len,
-                    true,
+                    operator,
                     -1,
-                ))
+                    fuzziness.as_ref(),
+                    sc,
+                )
+            }
+            Query::Prefix {
+                value,
+                case_insensitive,
+                boost,
+                field,
+            } => {
+                let pattern = TermPattern::prefix(value, *case_insensitive)?;
+                Self::pattern(segment, field, *boost, pattern, sc)
+            }
+            Query::Wildcard {
+                value,
+                case_insensitive,
+                boost,
+                field,
+            } => {
+                let pattern = TermPattern::wildcard(value, *case_insensitive)?;
+                Self::pattern(segment, field, *boost, pattern, sc)
+            }
+            Query::Regexp {
+                value,
+                case_insensitive,
+                boost,
+                field,
+            } => {
+                let pattern = TermPattern::regexp(value, *case_insensitive)?;
+                Self::pattern(segment, field, *boost, pattern, sc)
+            }
+            Query::Exists { boost, field } => {
+                let key = format!("{}/{}exists", segment.start(), field.index_name());
+                if let Some(id) = sc.get(&key) {
+                    return Ok(PhysicsPlan::Map(*boost, id));
+                }
+                let rb = segment.exists(field)?;
+                Ok(PhysicsPlan::Map(*boost, sc.insert(key, rb)))
+            }
+            Query::Not { query, boost } => {
+                let filter = Self::new(segment, query, sc)?.matched(segment.start(), sc);
+                let rb = segment.all_record() - filter;
+                Ok(PhysicsPlan::Map(*boost, sc.value_insert(rb)))
             }
             Query::Logical {
                 left,
@@ -298,7 +404,91 @@ impl PhysicsPlan {
                     LogicOperator::Or => Ok(l.or(r)),
                 }
             }
-            Query::Search { .. } => unreachable!(),
+            Query::Bool {
+                must: must_q,
+                should: should_q,
+                must_not,
+                filter,
+                minimum_should_match,
+            } => {
+                let must = must_q
+                    .iter()
+                    .map(|q| Self::new(segment, q, sc))
+                    .collect::<CoreResult<Vec<_>>>()?;
+                let should = should_q
+                    .iter()
+                    .map(|q| Self::new(segment, q, sc))
+                    .collect::<CoreResult<Vec<_>>>()?;
+
+                // without must and filter clauses, records must match a should clause
+                let minimum_should_match = match must.is_empty() && filter.is_empty() {
+                    true => (*minimum_should_match).max(1),
+                    false => *minimum_should_match,
+                };
+
+                // plans are made again to be walked if their filters are not exact,
+                // the plans of must and should are kept for scoring
+                let mut rb = segment.all_record();
+                for q in must_q.iter().chain(filter.iter()) {
+                    rb &= Self::new(segment, q, sc)?.matched(segment.start(), sc);
+                }
+                for q in must_not.iter() {
+                    rb -= Self::new(segment, q, sc)?.matched(segment.start(), sc);
+                }
+                if minimum_should_match > 0 {
+                    let bitmaps = should_q
+                        .iter()
+                        .map(|q| Ok(Self::new(segment, q, sc)?.matched(segment.start(), sc)))
+                        .collect::<CoreResult<Vec<_>>>()?;
+                    rb &= at_least(&bitmaps, minimum_should_match);
+                }
+
+                Ok(PhysicsPlan::Bool(
+                    must,
+                    should,
+                    minimum_should_match,
+                    sc.value_insert(rb),
+                ))
+            }
+            Query::DisMax {
+                queries,
+                tie_breaker,
+            } => Ok(PhysicsPlan::DisMax(
+                queries
+                    .iter()
+                    .map(|q| Self::new(segment, q, sc))
+                    .collect::<CoreResult<Vec<_>>>()?,
+                *tie_breaker,
+            )),
+            Query::MoreLikeThis { resolved, .. } => {
+                let (queries, exclude) = resolved.as_ref().ok_or_else(|| {
+                    CoreError::Internal("more_like_this is not resolved".to_string())
+                })?;
+                let should = queries
+                    .iter()
+                    .map(|q| Self::new(segment, q, sc))
+                    .collect::<CoreResult<Vec<_>>>()?;
+                let mut rb = at_least(&should.iter().map(|p| p.as_filter(sc)).collect_vec(), 1);
+                if let Some(id) = exclude.filter(|id| *id >= segment.start()) {
+                    rb.remove((id - segment.start()) as u32);
+                }
+                Ok(PhysicsPlan::Bool(vec![], should, 1, sc.value_insert(rb)))
+            }
+            Query::Nested { path, query, boost } => {
+                let key = format!("{}/{}nested{:?}", segment.start(), path.index_name(), query);
+                if let Some(id) = sc.get(&key) {
+                    return Ok(PhysicsPlan::Map(*boost, id));
+                }
+                let rb = if segment.has_index(path) {
+                    let reader = segment.get_nested_reader(path)?;
+                    let children = nested_filter(&reader, query)?;
+                    reader.parents_of(&children) & segment.all_record()
+                } else {
+                    Bitmap::new()
+                };
+                Ok(PhysicsPlan::Map(*boost, sc.insert(key, rb)))
+            }
+            Query::Search { .. } | Query::Count { .. } => unreachable!(),
         }
     }
 
@@ -321,24 +511,85 @@ impl PhysicsPlan {
                 }
                 Box::new(cs)
             }
-            PhysicsPlan::Text(
-                reader,
-                boost,
-                tokens,
-                total_bitmap,
-                token_doc_len,
-                operator,
-                slop,
-            ) => Box::new(TextStream::new(
-                reader,
-                boost,
-                tokens,
-                sc.and_value(total_bitmap, filter).iter(),
-                token_doc_len,
-                operator,
-                slop,
-            )),
+            PhysicsPlan::Text(reader, boost, paths, total_bitmap, token_terms, operator, slop) => {
+                Box::new(TextStream::new(
+                    reader,
+                    boost,
+                    paths,
+                    sc.and_value(total_bitmap, filter).iter(),
+                    token_terms,
+                    operator,
+                    slop,
+                ))
+            }
+            PhysicsPlan::Bool(must, should, minimum_should_match, key) => {
+                let matched = sc.and_value(key, filter).clone();
+
+                // every matched record is in the stream, filter clauses are not scored
+                let mut cs = CombHitStream::new(must.len() + should.len() + 1, LogicOperator::Or);
+                cs.add(Box::new(BitmapStream::new(
+                    start,
+                    sc.value_ref_get(key).iter(),
+                    0.0,
+                )));
+                for p in must.into_iter() {
+                    cs.add(p.into_stream(start, sc, filter));
+                }
+                if minimum_should_match > 1 {
+                    let mut should_cs = CombHitStream::at_least(should.len(), minimum_should_match);
+                    for p in should.into_iter() {
+                        should_cs.add(p.into_stream(start, sc, filter));
+                    }
+                    cs.add(Box::new(should_cs));
+                } else {
+                    for p in should.into_iter() {
+                        cs.add(p.into_stream(start, sc, filter));
+                    }
+                }
+
+                Box::new(FilterStream::new(start, Box::new(cs), matched))
+            }
+            PhysicsPlan::DisMax(vec, tie_breaker) => {
+                let mut cs = CombHitStream::dis_max(vec.len(), tie_breaker);
+                for v in vec.into_iter() {
+                    cs.add(v.into_stream(start, sc, filter));
+                }
+                Box::new(cs)
+            }
+        }
+    }
+
+    /// if records of as_filter are the same as the stream, text plan of phrase
+    /// drops records whose token positions are not matched in stream
+    pub fn filter_is_exact(&self) -> bool {
+        match self {
+            PhysicsPlan::Map(..) | PhysicsPlan::Bool(..) => true,
+            PhysicsPlan::Text(_, _, paths, _, _, _, slop) => {
+                *slop < 0 || paths.iter().all(|p| p.len() <= 1)
+            }
+            PhysicsPlan::Combin(vec, _) | PhysicsPlan::DisMax(vec, _) => {
+                vec.iter().all(|p| p.filter_is_exact())
+            }
+        }
+    }
+
+    /// records matched by the plan, they are got by stream if filter is not exact
+    pub fn matched(self, start: u64, sc: &mut SegmentContext) -> Bitmap {
+        let filter = self.as_filter(sc);
+        if self.filter_is_exact() {
+            return filter;
+        }
+
+        let mut stream = self.into_stream(start, sc, &filter);
+        let mut matched = Bitmap::new();
+        loop {
+            stream.next();
+            match stream.value() {
+                Some(id) => matched.add((id - start) as u32),
+                None => break,
+            }
         }
+        matched
     }
 
     pub fn as_filter(&self, sc: &SegmentContext) -> Bitmap {
@@ -356,6 +607,71 @@ impl PhysicsPlan {
                 })
                 .unwrap_or_default(),
             PhysicsPlan::Text(_, _, _, total_bitmap, _, _, _) => sc.value_get(*total_bitmap),
+            PhysicsPlan::Bool(.., key) => sc.value_get(*key),
+            PhysicsPlan::DisMax(vec, _) => Bitmap::fast_or(
+                &vec.iter()
+                    .map(|p| p.as_filter(sc))
+                    .collect_vec()
+                    .iter()
+                    .collect_vec(),
+            ),
+        }
+    }
+
+    /// plan tree for explain, value is the cardinality of records matched
+    pub fn describe(&self, sc: &SegmentContext) -> Explanation {
+        let (description, details) = match self {
+            PhysicsPlan::Map(boost, key) => (
+                format!(
+                    "bitmap {} boost={}",
+                    sc.key_of(*key).unwrap_or("(computed)"),
+                    boost
+                ),
+                vec![],
+            ),
+            PhysicsPlan::Text(reader, boost, paths, _, token_terms, _, slop) => (
+                format!(
+                    "text on {} boost={} paths={:?} slop={}",
+                    reader.inner.name,
+                    boost,
+                    paths
+                        .iter()
+                        .map(|p| p.iter().map(|t| t.name.as_str()).collect_vec())
+                        .collect_vec(),
+                    slop
+                ),
+                token_terms
+                    .iter()
+                    .sorted_by(|a, b| a.0.cmp(b.0))
+                    .map(|(token, (df, terms))| Explanation {
+                        value: *df as f32,
+                        description: format!("token {:?} terms={:?}", token, terms),
+                        details: vec![],
+                    })
+                    .collect(),
+            ),
+            PhysicsPlan::Combin(vec, op) => (
+                format!("{:?}", op).to_lowercase(),
+                vec.iter().map(|p| p.describe(sc)).collect(),
+            ),
+            PhysicsPlan::Bool(must, should, minimum_should_match, _) => (
+                format!(
+                    "bool must={} should={} minimum_should_match={}",
+                    must.len(),
+                    should.len(),
+                    minimum_should_match
+                ),
+                must.iter().chain(should).map(|p| p.describe(sc)).collect(),
+            ),
+            PhysicsPlan::DisMax(vec, tie_breaker) => (
+                format!("dis_max tie_breaker={}", tie_breaker),
+                vec.iter().map(|p| p.describe(sc)).collect(),
+            ),
+        };
+        Explanation {
+            value: self.as_filter(sc).cardinality() as f32,
+            description,
+            details,
         }
     }
 
@@ -363,12 +679,128 @@ impl PhysicsPlan {
         match self {
             PhysicsPlan::Map(..) => true,
             PhysicsPlan::Text(..) => false,
-            PhysicsPlan::Combin(..) => true,
+            PhysicsPlan::Combin(vec, _) => vec.iter().all(|p| p.can_merge()),
+            PhysicsPlan::Bool(..) | PhysicsPlan::DisMax(..) => false,
         }
     }
 }
 
 impl PhysicsPlan {
+    /// make a text plan, token bitmaps are cached in segment context.
+    /// if conjunction every token of a path must match, any of the synonym paths is ok,
+    /// else any token match is ok. with fuzziness a token matches any term it expands to
+    #[allow(clippy::too_many_arguments)]
+    fn text(
+        segment: &SegmentReader,
+        field: &Field,
+        value: &str,
+        boost: f32,
+        conjunction: bool,
+        slop: i32,
+        fuzziness: Option<&Fuzziness>,
+        sc: &mut SegmentContext,
+    ) -> CoreResult<Self> {
+        if !segment.has_index(field) {
+            return Ok(PhysicsPlan::Map(boost, sc.value_insert(Bitmap::new())));
+        }
+        let reader = segment.get_text_reader(field)?;
+
+        let paths = reader.analyzer(value)?;
+
+        let mut ids = HashMap::new();
+        let mut token_terms = TokenTerms::new();
+
+        let mut out_tokens = Vec::new();
+        let mut out_keys = Vec::new();
+
+        for t in paths.iter().flatten().map(|t| &t.name).unique() {
+            // blank token only holds the position, no need to expand
+            if let Some(f) = fuzziness.filter(|_| !t.trim().is_empty()) {
+                let terms = reader.fuzzy_terms(t, f.distance(t), f.prefix_length, f.max_expansions);
+                let key = format!("{}/{}~{:?}", segment.start(), field.index_name(), terms);
+                let id = match sc.get(&key) {
+                    Some(id) => id,
+                    None => {
+                        let bitmaps = reader.tokens(&terms.iter().collect_vec())?;
+                        sc.insert(key, Bitmap::fast_or(&bitmaps.iter().collect_vec()))
+                    }
+                };
+                ids.insert(t.clone(), id);
+                token_terms.insert(t.clone(), (0, terms));
+                continue;
+            }
+
+            token_terms.insert(t.clone(), (0, vec![t.clone()]));
+            let key = format!("{}/{}={:?}", segment.start(), field.index_name(), t);
+            match sc.get(&key) {
+                Some(id) => {
+                    ids.insert(t.clone(), id);
+                }
+                None => {
+                    out_keys.push(key);
+                    out_tokens.push(t);
+                }
+            }
+        }
+
+        for ((b, key), t) in reader
+            .tokens(&out_tokens)?
+            .into_iter()
+            .zip(out_keys)
+            .zip(out_tokens)
+        {
+            ids.insert(t.clone(), sc.insert(key, b));
+        }
+
+        for (t, (df, _)) in token_terms.iter_mut() {
+            *df = sc.value_ref_get(ids[t]).cardinality() as usize;
+        }
+
+        let total = if conjunction {
+            paths
+                .iter()
+                .filter_map(|path| {
+                    path.iter()
+                        .map(|t| sc.value_ref_get(ids[&t.name]))
+                        .fold(None, |acc: Option<Bitmap>, b| {
+                            Some(acc.map_or_else(|| b.clone(), |acc| acc & b))
+                        })
+                })
+                .reduce(|a, b| a | b)
+        } else {
+            Some(Bitmap::fast_or(
+                &ids.values().map(|id| sc.value_ref_get(*id)).collect_vec(),
+            ))
+        };
+
+        let total_map = sc.value_insert(total.unwrap_or_default());
+
+        Ok(Self::Text(
+            reader,
+            boost,
+            paths,
+            total_map,
+            token_terms,
+            true,
+            slop,
+        ))
+    }
+
+    fn pattern(
+        segment: &SegmentReader,
+        field: &Field,
+        boost: f32,
+        pattern: TermPattern,
+        sc: &mut SegmentContext,
+    ) -> CoreResult<Self> {
+        let key = format!("{}/{}~{:?}", segment.start(), field.index_name(), pattern);
+        if let Some(id) = sc.get(&key) {
+            return Ok(PhysicsPlan::Map(boost, id));
+        }
+        let rb = segment.pattern(field, &pattern)?;
+        Ok(PhysicsPlan::Map(boost, sc.insert(key, rb)))
+    }
+
     fn and(self, other: PhysicsPlan, sc: &mut SegmentContext) -> Self {
         if !self.can_merge() || !other.can_merge() {
             return PhysicsPlan::Combin(vec![self, other], LogicOperator::And);
@@ -420,3 +852,615 @@ impl PhysicsPlan {
         items
     }
 }
+
+/// objects of a nested field matched by the query, only filters of term fields are supported
+fn nested_filter(reader: &NestedIndexReader, query: &Query) -> CoreResult<Bitmap> {
+    let rb = match query {
+        Query::Term {
+            value,
+            operator,
+            field,
+            ..
+        } => {
+            let rb = reader.child(&field.name)?.term(value).unwrap_or_default();
+            match operator {
+                ComparisonOperator::Eq => rb,
+                ComparisonOperator::NotEq => reader.all_children() - rb,
+            }
+        }
+        Query::Between {
+            low,
+            low_eq,
+            high,
+            high_eq,
+            field,
+            ..
+        } => reader
+            .child(&field.name)?
+            .between(low.as_ref(), *low_eq, high.as_ref(), *high_eq)?,
+        Query::InList { list, field, .. } => reader.child(&field.name)?.in_terms(list),
+        Query::Exists { field, .. } => reader.child(&field.name)?.exists.as_ref().clone(),
+        Query::Prefix {
+            value,
+            case_insensitive,
+            field,
+            ..
+        } => reader
+            .child(&field.name)?
+            .pattern(&TermPattern::prefix(value, *case_insensitive)?)?,
+        Query::Wildcard {
+            value,
+            case_insensitive,
+            field,
+            ..
+        } => reader
+            .child(&field.name)?
+            .pattern(&TermPattern::wildcard(value, *case_insensitive)?)?,
+        Query::Regexp {
+            value,
+            case_insensitive,
+            field,
+            ..
+        } => reader
+            .child(&field.name)?
+            .pattern(&TermPattern::regexp(value, *case_insensitive)?)?,
+        Query::Not { query, .. } => reader.all_children() - nested_filter(reader, query)?,
+        Query::Logical {
+            left,
+            right,
+            operator,
+        } => {
+            let l = nested_filter(reader, left)?;
+            let r = nested_filter(reader, right)?;
+            match operator {
+                LogicOperator::And => l & r,
+                LogicOperator::Or => l | r,
+            }
+        }
+        Query::Bool {
+            must,
+            should,
+            must_not,
+            filter,
+            minimum_should_match,
+        } => {
+            let mut rb = reader.all_children();
+            for q in must.iter().chain(filter) {
+                rb &= nested_filter(reader, q)?;
+            }
+            for q in must_not {
+                rb -= nested_filter(reader, q)?;
+            }
+            if *minimum_should_match > 0 {
+                let bitmaps = should
+                    .iter()
+                    .map(|q| nested_filter(reader, q))
+                    .collect::<CoreResult<Vec<_>>>()?;
+                rb &= at_least(&bitmaps, *minimum_should_match);
+            }
+            rb
+        }
+        Query::DisMax { queries, .. } => {
+            let bitmaps = queries
+                .iter()
+                .map(|q| nested_filter(reader, q))
+                .collect::<CoreResult<Vec<_>>>()?;
+            at_least(&bitmaps, 1)
+        }
+        _ => {
+            return Err(CoreError::Notsupport(format!(
+                "query in nested:{:?} only filters term fields",
+                reader.inner.name
+            )))
+        }
+    };
+    Ok(rb)
+}
+
+/// records in at least k of the bitmaps
+fn at_least(bitmaps: &[Bitmap], k: usize) -> Bitmap {
+    if k > bitmaps.len() {
+        return Bitmap::new();
+    }
+    // levels[i] is records in at least i + 1 bitmaps
+    let mut levels = vec![Bitmap::new(); k];
+    for b in bitmaps {
+        for i in (1..k).rev() {
+            let v = &levels[i - 1] & b;
+            levels[i] |= v;
+        }
+        levels[0] |= b;
+    }
+    levels.pop().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use proto::core::field::{self, Type as FieldType};
+
+    use super::*;
+    use crate::{
+        protocols::sql::sql_to_query,
+        test_util::{mem_segment, test_scope},
+        Scope,
+    };
+
+    fn where_query(scope: &Scope, sql: &str) -> CoreResult<Box<Query>> {
+        match sql_to_query(scope, sql)? {
+            Query::Search { query: Some(q), .. } => Ok(q),
+            q => panic!("no where clause in query: {:?}", q),
+        }
+    }
+
+    /// sorted names of records matched by the where clause of sql, as the searcher does
+    fn names(scope: &Scope, segment: &SegmentReader, sql: &str) -> CoreResult<Vec<String>> {
+        let query = where_query(scope, sql)?;
+        let mut sc = SegmentContext::new();
+        let matched = PhysicsPlan::new(segment, &query, &mut sc)?.matched(segment.start(), &mut sc);
+        Ok(matched
+            .iter()
+            .map(|id| {
+                segment
+                    .doc(segment.start() + id as u64)
+                    .unwrap()
+                    .name
+                    .clone()
+            })
+            .sorted()
+            .collect())
+    }
+
+    /// names and scores of records walked by the stream of sql
+    fn scores(scope: &Scope, segment: &SegmentReader, sql: &str) -> Vec<(String, f32)> {
+        let query = where_query(scope, sql).unwrap();
+        let mut sc = SegmentContext::new();
+        let plan = PhysicsPlan::new(segment, &query, &mut sc).unwrap();
+        let filter = plan.as_filter(&sc);
+        let mut stream = plan.into_stream(segment.start(), &mut sc, &filter);
+        let mut hits = Vec::new();
+        loop {
+            stream.next();
+            match stream.value() {
+                Some(id) => hits.push((segment.doc(id).unwrap().name.clone(), stream.score())),
+                None => break,
+            }
+        }
+        hits.sort_by(|a, b| a.0.cmp(&b.0));
+        hits
+    }
+
+    fn check(scope: &Scope, segment: &SegmentReader, cases: &[(&str, Vec<&str>)]) {
+        for (filter, expected) in cases {
+            let sql = format!("SELECT * FROM test WHERE {}", filter);
+            assert_eq!(names(scope, segment, &sql).unwrap(), *expected, "{}", sql);
+        }
+    }
+
+    #[test]
+    fn test_fuzzy() {
+        let scope = test_scope(crate::easy_schema(
+            "test",
+            vec![("content".to_string(), FieldType::Text, None)],
+        ));
+        let segment = mem_segment(
+            &scope,
+            &[
+                ("r1", r#"{"content": "quick fox"}"#),
+                ("r2", r#"{"content": "quack duck"}"#),
+                ("r3", r#"{"content": "lazy dog"}"#),
+            ],
+        );
+
+        check(
+            &scope,
+            &segment,
+            &[
+                ("content = text('quikc')", vec![]),
+                ("content = fuzzy('quikc', distance=1)", vec!["r1"]),
+                ("content = fuzzy('quikc', distance=2)", vec!["r1", "r2"]),
+                (
+                    "content = fuzzy('quikc', distance=2, prefix_length=3)",
+                    vec!["r1"],
+                ),
+                (
+                    "content = text('qiuck dgo', fuzziness='auto', operator='and')",
+                    vec![],
+                ),
+                (
+                    "content = text('lazzy dgo', fuzziness='auto', operator='and')",
+                    vec!["r3"],
+                ),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_pattern() {
+        let scope = test_scope(crate::easy_schema(
+            "test",
+            vec![
+                ("name".to_string(), FieldType::String, None),
+                ("content".to_string(), FieldType::Text, None),
+            ],
+        ));
+        let segment = mem_segment(
+            &scope,
+            &[
+                ("Apple", r#"{"name": "Apple", "content": "rust book"}"#),
+                ("apricot", r#"{"name": "apricot", "content": "rusty nail"}"#),
+                ("banana", r#"{"name": "banana", "content": "trust"}"#),
+            ],
+        );
+
+        check(
+            &scope,
+            &segment,
+            &[
+                ("name LIKE 'ap%'", vec!["apricot"]),
+                ("name ILIKE 'ap%'", vec!["Apple", "apricot"]),
+                ("name LIKE '%an_na'", vec!["banana"]),
+                ("name REGEXP '[ab].*a'", vec!["banana"]),
+                ("content = prefix('rust')", vec!["Apple", "apricot"]),
+                ("content = regexp('t?rust')", vec!["Apple", "banana"]),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_not_and_exists() {
+        let scope = test_scope(crate::easy_schema(
+            "test",
+            vec![
+                ("age".to_string(), FieldType::Int, None),
+                ("tags".to_string(), FieldType::String, None),
+                ("content".to_string(), FieldType::Text, None),
+            ],
+        ));
+        let segment = mem_segment(
+            &scope,
+            &[
+                (
+                    "r1",
+                    r#"{"age": 10, "tags": ["a", "b"], "content": "hello"}"#,
+                ),
+                ("r2", r#"{"age": 20, "tags": []}"#),
+                ("r3", r#"{"content": "world"}"#),
+            ],
+        );
+
+        check(
+            &scope,
+            &segment,
+            &[
+                ("age IS NULL", vec!["r3"]),
+                ("tags IS NOT NULL", vec!["r1"]),
+                ("content IS NULL", vec!["r2"]),
+                ("NOT age = 10", vec!["r2", "r3"]),
+                ("age NOT IN (10, 30)", vec!["r2", "r3"]),
+                ("age NOT BETWEEN 10 AND 20", vec!["r3"]),
+                (
+                    "content = 'hello' OR NOT content = 'world'",
+                    vec!["r1", "r2"],
+                ),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_bool() {
+        let scope = test_scope(crate::easy_schema(
+            "test",
+            vec![
+                ("age".to_string(), FieldType::Int, None),
+                ("tag".to_string(), FieldType::String, None),
+                ("content".to_string(), FieldType::Text, None),
+            ],
+        ));
+        let segment = mem_segment(
+            &scope,
+            &[
+                ("r1", r#"{"age": 10, "tag": "a", "content": "rust search"}"#),
+                ("r2", r#"{"age": 20, "tag": "b", "content": "rust"}"#),
+                ("r3", r#"{"age": 30, "tag": "a", "content": "go search"}"#),
+            ],
+        );
+        let scores = |sql: &str| scores(&scope, &segment, sql);
+
+        // filter clauses change nothing of scores
+        let text = scores("SELECT * FROM test WHERE content = 'rust'");
+        let filtered = scores("SELECT * FROM test WHERE content = 'rust' AND FILTER(age >= 10)");
+        assert_eq!(text, filtered);
+
+        let filtered = scores("SELECT * FROM test WHERE content = 'rust' AND FILTER(tag = 'a')");
+        assert_eq!(filtered, vec![text[0].clone()]);
+
+        let only_filter = scores("SELECT * FROM test WHERE FILTER(tag = 'a')");
+        assert_eq!(only_filter.len(), 2);
+        assert!(only_filter.iter().all(|(_, s)| *s == 0.0));
+
+        check(
+            &scope,
+            &segment,
+            &[
+                (
+                    "SHOULD(tag = 'a', content = 'search', age = 30, minimum_should_match=2)",
+                    vec!["r1", "r3"],
+                ),
+                (
+                    "SHOULD(tag = 'a', content = 'search', age = 30, minimum_should_match=3)",
+                    vec!["r3"],
+                ),
+                ("FILTER(tag = 'a') AND NOT content = 'go'", vec!["r1"]),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_bool_not_exact() {
+        let scope = test_scope(crate::easy_schema(
+            "test",
+            vec![
+                ("tag".to_string(), FieldType::String, None),
+                ("content".to_string(), FieldType::Text, None),
+            ],
+        ));
+        let segment = mem_segment(
+            &scope,
+            &[
+                ("r1", r#"{"tag": "a", "content": "quick brown fox"}"#),
+                ("r2", r#"{"tag": "a", "content": "brown quick fox"}"#),
+                ("r3", r#"{"tag": "b", "content": "lazy dog"}"#),
+            ],
+        );
+
+        // the filter of phrase has all tokens, records are walked to match the phrase
+        check(
+            &scope,
+            &segment,
+            &[
+                (
+                    "content = phrase('quick brown') AND FILTER(tag = 'a')",
+                    vec!["r1"],
+                ),
+                ("FILTER(content = phrase('quick brown'))", vec!["r1"]),
+                (
+                    "SHOULD(content = phrase('quick brown'), tag = 'b')",
+                    vec!["r1", "r3"],
+                ),
+                ("NOT content = phrase('quick brown')", vec!["r2", "r3"]),
+                (
+                    "FILTER(tag = 'a') AND NOT content = phrase('quick brown')",
+                    vec!["r2"],
+                ),
+                // a should clause is matched at least without must and filter
+                ("SHOULD(content = 'zzz', minimum_should_match=0)", vec![]),
+                (
+                    "SHOULD(content = 'zzz', minimum_should_match=0) AND FILTER(tag = 'b')",
+                    vec!["r3"],
+                ),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_multi_match() {
+        let scope = test_scope(crate::easy_schema(
+            "test",
+            vec![
+                ("title".to_string(), FieldType::Text, None),
+                ("body".to_string(), FieldType::Text, None),
+                ("tag".to_string(), FieldType::String, None),
+                (crate::ALL_FIELD.to_string(), FieldType::Text, None),
+            ],
+        ));
+        let segment = mem_segment(
+            &scope,
+            &[
+                (
+                    "r1",
+                    r#"{"title": "rust book", "body": "learn go", "tag": "lang"}"#,
+                ),
+                (
+                    "r2",
+                    r#"{"title": "go book", "body": "learn rust", "tag": "lang"}"#,
+                ),
+                (
+                    "r3",
+                    r#"{"title": "cooking", "body": "rust on pans", "tag": "kitchen"}"#,
+                ),
+            ],
+        );
+        let best = |sql: &str| {
+            scores(&scope, &segment, sql)
+                .into_iter()
+                .max_by(|a, b| a.1.total_cmp(&b.1))
+                .unwrap()
+                .0
+        };
+
+        // string field is in the catch-all field too
+        check(
+            &scope,
+            &segment,
+            &[
+                ("_all = 'kitchen'", vec!["r3"]),
+                ("_all = 'rust'", vec!["r1", "r2", "r3"]),
+                (
+                    "multi_match('rust', fields=['title^3', 'body'])",
+                    vec!["r1", "r2", "r3"],
+                ),
+                // rust and go are in different fields of r1 and r2
+                (
+                    "multi_match('rust go', fields='title,body', type='cross_fields', operator='and')",
+                    vec!["r1", "r2"],
+                ),
+                (
+                    "multi_match('rust book', fields='title,body', type='most_fields')",
+                    vec!["r1", "r2", "r3"],
+                ),
+            ],
+        );
+
+        assert_eq!(
+            best("SELECT * FROM test WHERE multi_match('rust', fields=['title^3', 'body'])"),
+            "r1"
+        );
+        assert_ne!(
+            best("SELECT * FROM test WHERE multi_match('rust', fields=['title', 'body^3'])"),
+            "r1"
+        );
+    }
+
+    #[test]
+    fn test_dotted_path() {
+        let scope = test_scope(crate::easy_schema(
+            "test",
+            vec![
+                ("author.name".to_string(), FieldType::String, None),
+                ("author.age".to_string(), FieldType::Int, None),
+                ("comments.text".to_string(), FieldType::Text, None),
+                ("comments.user".to_string(), FieldType::String, None),
+            ],
+        ));
+        let segment = mem_segment(
+            &scope,
+            &[
+                (
+                    "r1",
+                    r#"{"author": {"name": "tom", "age": 30}, "comments": [{"user": "ann", "text": "great book"}, {"user": "bob", "text": "boring"}]}"#,
+                ),
+                (
+                    "r2",
+                    r#"{"author": {"name": "jerry", "age": 20}, "comments": [{"user": "bob", "text": "funny story"}]}"#,
+                ),
+                ("r3", r#"{"author": {"name": "tom", "age": 40}}"#),
+            ],
+        );
+
+        check(
+            &scope,
+            &segment,
+            &[
+                ("author.name = 'tom'", vec!["r1", "r3"]),
+                ("author.age > 25", vec!["r1", "r3"]),
+                // every element of an array of objects is indexed
+                ("comments.user = 'bob'", vec!["r1", "r2"]),
+                ("comments.text = 'boring'", vec!["r1"]),
+                ("comments.user IN ('ann')", vec!["r1"]),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_nested() {
+        let sub = |name: &str, r#type: FieldType| Field {
+            name: name.to_string(),
+            r#type: r#type as i32,
+            ..Default::default()
+        };
+        let scope = test_scope(crate::easy_schema(
+            "test",
+            vec![(
+                "variants".to_string(),
+                FieldType::Nested,
+                Some(field::Option::Nested(field::NestedOption {
+                    fields: vec![sub("color", FieldType::String), sub("size", FieldType::Int)],
+                })),
+            )],
+        ));
+        let segment = mem_segment(
+            &scope,
+            &[
+                (
+                    "r1",
+                    r#"{"variants": [{"color": "red", "size": 1}, {"color": "blue", "size": 2}]}"#,
+                ),
+                ("r2", r#"{"variants": [{"color": "red", "size": 2}]}"#),
+                (
+                    "r3",
+                    r#"{"variants": [{"color": "blue", "size": 1}, {"color": "red", "size": 3}]}"#,
+                ),
+                ("r4", r#"{"variants": {"color": "red", "size": 2}}"#),
+            ],
+        );
+
+        // conditions match in the same object
+        check(
+            &scope,
+            &segment,
+            &[
+                (
+                    "nested(variants, color = 'red' AND size = 2)",
+                    vec!["r2", "r4"],
+                ),
+                ("nested(variants, color = 'blue' AND size = 2)", vec!["r1"]),
+                ("nested(variants, color = 'red' AND size >= 3)", vec!["r3"]),
+                (
+                    "nested(variants, color = 'red' AND NOT size = 2)",
+                    vec!["r1", "r3"],
+                ),
+                (
+                    "nested(variants, color = 'blue') AND nested(variants, size = 3)",
+                    vec!["r3"],
+                ),
+            ],
+        );
+
+        for filter in ["nested(variants)", "nested(variants, weight = 1)"] {
+            let sql = format!("SELECT * FROM test WHERE {}", filter);
+            assert!(names(&scope, &segment, &sql).is_err(), "{}", sql);
+        }
+    }
+
+    #[test]
+    fn test_normalizer() {
+        use proto::core::field::keyword_option::Normalizer;
+
+        let scope = test_scope(crate::easy_schema(
+            "test",
+            vec![
+                (
+                    "name".to_string(),
+                    FieldType::String,
+                    Some(field::Option::Keyword(field::KeywordOption {
+                        normalizers: vec![
+                            Normalizer::Trim as i32,
+                            Normalizer::AsciiFolding as i32,
+                            Normalizer::Lowercase as i32,
+                        ],
+                    })),
+                ),
+                ("code".to_string(), FieldType::String, None),
+            ],
+        ));
+        let segment = mem_segment(
+            &scope,
+            &[
+                ("r1", r#"{"name": "Alice", "code": "Alice"}"#),
+                ("r2", r#"{"name": " alice ", "code": "alice"}"#),
+                ("r3", r#"{"name": "Álice Smith"}"#),
+                ("r4", r#"{"name": ["ALICE SMITH", "Al"]}"#),
+                ("r5", r#"{"name": "Bob"}"#),
+            ],
+        );
+
+        check(
+            &scope,
+            &segment,
+            &[
+                ("name = 'ALICE'", vec!["r1", "r2"]),
+                ("name = ' alice smith'", vec!["r3", "r4"]),
+                ("name IN ('Alice', 'BÖB')", vec!["r1", "r2", "r5"]),
+                (
+                    "name BETWEEN 'Alice' AND 'ALICE Z'",
+                    vec!["r1", "r2", "r3", "r4"],
+                ),
+                ("name LIKE 'ÁLI%'", vec!["r1", "r2", "r3", "r4"]),
+                ("name LIKE 'AL_CE S%'", vec!["r3", "r4"]),
+                ("name != 'al'", vec!["r1", "r2", "r3", "r5"]),
+                // field without normalizers is exact
+                ("code = 'alice'", vec!["r2"]),
+            ],
+        );
+    }
+}
@@ -1,12 +1,15 @@
 use std::{borrow::Cow, collections::HashMap, path::PathBuf, sync::Arc};
 
 use croaring::Bitmap;
-use proto::core::{Field, Record};
+use proto::core::{field, Field, Record};
 
-use crate::util::CoreResult;
+use crate::{analyzer::Analyzer, util::CoreResult};
 
 use super::{
     index_fulltext::reader::FulltextIndexReader,
+    index_nested::NestedIndexReader,
+    pattern::TermPattern,
+    seacher::cache::{Version, IMMUTABLE_VERSION},
     segment_disk::DiskSegment,
     segment_mem::{MemSegment, MemSegmentReader},
 };
@@ -58,6 +61,13 @@ impl Segment {
             Segment::Warm(w) => w.find_by_name(name),
         }
     }
+
+    pub(crate) fn set_analyzer(&self, field: &str, analyzer: Arc<Analyzer>) {
+        match self {
+            Segment::Hot(h) => h.set_analyzer(field, analyzer),
+            Segment::Warm(w) => w.set_analyzer(field, analyzer),
+        }
+    }
 }
 
 pub enum SegmentReader {
@@ -80,7 +90,18 @@ impl SegmentReader {
         }
     }
 
+    /// a field added after the segment is made has no index in it
+    pub(crate) fn has_index(&self, field: &Field) -> bool {
+        match self {
+            SegmentReader::Hot(h) => h.has_index(&field.index_name()),
+            SegmentReader::Warm(w) => w.has_index(&field.index_name()),
+        }
+    }
+
     pub(crate) fn term(&self, field: &Field, value: &Vec<u8>) -> CoreResult<Bitmap> {
+        if !self.has_index(field) {
+            return Ok(Bitmap::new());
+        }
         match self {
             SegmentReader::Hot(mem) => mem.term(field, value),
             SegmentReader::Warm(disk) => disk.term(field, value),
@@ -102,6 +123,9 @@ impl SegmentReader {
         high: Option<&Vec<u8>>,
         high_eq: bool,
     ) -> CoreResult<Bitmap> {
+        if !self.has_index(field) {
+            return Ok(Bitmap::new());
+        }
         match self {
             SegmentReader::Hot(h) => h.between(field, low, low_eq, high, high_eq),
             SegmentReader::Warm(w) => w.between(field, low, low_eq, high, high_eq),
@@ -109,12 +133,57 @@ impl SegmentReader {
     }
 
     pub(crate) fn in_terms(&self, field: &Field, list: &[Vec<u8>]) -> CoreResult<Bitmap> {
+        if !self.has_index(field) {
+            return Ok(Bitmap::new());
+        }
         match self {
             SegmentReader::Hot(h) => h.in_terms(field, list),
             SegmentReader::Warm(w) => w.in_terms(field, list),
         }
     }
 
+    /// records have value of the field
+    pub(crate) fn exists(&self, field: &Field) -> CoreResult<Bitmap> {
+        if !self.has_index(field) {
+            return Ok(Bitmap::new());
+        }
+        if field.r#type() == field::Type::Text {
+            return Ok(self.get_text_reader(field)?.exists.as_ref().clone());
+        }
+        match self {
+            SegmentReader::Hot(h) => h.exists(field),
+            SegmentReader::Warm(w) => w.exists(field),
+        }
+    }
+
+    /// terms of string field or tokens of text field matched by pattern
+    pub(crate) fn pattern(&self, field: &Field, pattern: &TermPattern) -> CoreResult<Bitmap> {
+        if !self.has_index(field) {
+            return Ok(Bitmap::new());
+        }
+        if field.r#type() == field::Type::Text {
+            return self.get_text_reader(field)?.pattern(pattern);
+        }
+        match self {
+            SegmentReader::Hot(h) => h.pattern(field, pattern),
+            SegmentReader::Warm(w) => w.pattern(field, pattern),
+        }
+    }
+
+    /// all terms of a term field in order with their records
+    pub(crate) fn terms<F>(&self, field: &Field, f: F) -> CoreResult<()>
+    where
+        F: FnMut(&[u8], &Bitmap),
+    {
+        if !self.has_index(field) {
+            return Ok(());
+        }
+        match self {
+            SegmentReader::Hot(h) => h.terms(field, f),
+            SegmentReader::Warm(w) => w.terms(field, f),
+        }
+    }
+
     pub(crate) fn get_text_reader(&self, field: &Field) -> CoreResult<Arc<FulltextIndexReader>> {
         match self {
             SegmentReader::Hot(h) => h.get_text_reader(field),
@@ -122,6 +191,13 @@ impl SegmentReader {
         }
     }
 
+    pub(crate) fn get_nested_reader(&self, field: &Field) -> CoreResult<Arc<NestedIndexReader>> {
+        match self {
+            SegmentReader::Hot(h) => h.get_nested_reader(field),
+            SegmentReader::Warm(w) => w.get_nested_reader(field),
+        }
+    }
+
     pub(crate) fn doc(&self, id: u64) -> Option<Cow<Record>> {
         match self {
             SegmentReader::Hot(h) => h.doc(id),
@@ -143,6 +219,14 @@ impl SegmentReader {
         }
     }
 
+    /// content version for filter cache, hot segment changes when records are written or deleted
+    pub(crate) fn version(&self) -> Version {
+        match self {
+            SegmentReader::Hot(h) => (h.end, h.source_store.len() as u64, h.dels.cardinality()),
+            SegmentReader::Warm(w) => (w.end(), IMMUTABLE_VERSION, IMMUTABLE_VERSION),
+        }
+    }
+
     pub fn is_hot(&self) -> bool {
         match self {
             SegmentReader::Hot(_) => true,
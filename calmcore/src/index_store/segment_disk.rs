@@ -7,36 +7,64 @@ use std::{
 };
 
 use croaring::{Bitmap, Portable};
+use itertools::Itertools;
 use mem_btree::persist;
 use proto::core::{Field, Record};
 
 use crate::{
+    analyzer::Analyzer,
     index_store::index_fulltext::FulltextIndex,
     util::{CoreError, CoreResult},
 };
 
 use super::{
     index_fulltext::reader::FulltextIndexReader,
+    index_nested::{NestedIndex, NestedIndexReader},
     index_term::{reader::TermIndexReader, TermIndex},
+    pattern::TermPattern,
 };
 
 pub struct DiskSegment {
     start: u64,
     end: u64,
     dels: RwLock<Bitmap>,
+    // offset of the first record, segments written before ids started at the segment
+    // start have no record at the start
+    first: u32,
     name_store: persist::TreeReader<String, u32>,
     source_store: persist::TreeReader<u32, Record>,
+    // none if no record of the segment has payload
+    payload_store: Option<persist::TreeReader<u32, Vec<u8>>>,
     index_terms: HashMap<String, TermIndexReader>,
-    index_fulltext: HashMap<String, Arc<FulltextIndexReader>>,
+    index_fulltext: HashMap<String, TextIndex>,
+    index_nested: HashMap<String, Arc<NestedIndexReader>>,
     marker: Option<String>,
     usage_bytes: u64,
 }
 
+/// a disk text index keeps its reader, which is made again only when the analyzer is replaced
+struct TextIndex {
+    index: FulltextIndex,
+    reader: RwLock<Arc<FulltextIndexReader>>,
+}
+
 struct RecordDeserializer;
 
 impl persist::KVDeserializer<u32, Record> for RecordDeserializer {
     fn deserialize_value(&self, v: &[u8]) -> std::result::Result<Record, Box<dyn Error>> {
-        bincode::deserialize(v).map_err(|e| e.into())
+        crate::persist::decode_source(v).map_err(|e| e.into())
+    }
+
+    fn serialize_key<'a>(&self, k: &'a u32) -> Cow<'a, [u8]> {
+        Cow::Owned(k.to_be_bytes().to_vec())
+    }
+}
+
+struct PayloadDeserializer;
+
+impl persist::KVDeserializer<u32, Vec<u8>> for PayloadDeserializer {
+    fn deserialize_value(&self, v: &[u8]) -> std::result::Result<Vec<u8>, Box<dyn Error>> {
+        Ok(v.to_vec())
     }
 
     fn serialize_key<'a>(&self, k: &'a u32) -> Cow<'a, [u8]> {
@@ -91,6 +119,15 @@ impl DiskSegment {
         let name_store =
             persist::TreeReader::new(&path.join("_name"), Box::new(U32BeDeserializer {}))?;
 
+        let payload_path = path.join(crate::persist::PAYLOAD);
+        let payload_store = match payload_path.exists() {
+            true => Some(persist::TreeReader::new(
+                &payload_path,
+                Box::new(PayloadDeserializer {}),
+            )?),
+            false => None,
+        };
+
         let dels_path = path.join("_dels");
 
         let dels = if dels_path.exists() {
@@ -100,22 +137,37 @@ impl DiskSegment {
             RwLock::new(Bitmap::new())
         };
 
+        let first = source_store.get(&0).is_none() as u32;
+
         //read version
         let marker = crate::persist::read_version(&path)?.marker;
 
+        let exists_path = path.join(crate::persist::EXISTS);
+
         let mut index_terms = HashMap::new();
 
         let mut index_fulltext = HashMap::new();
 
-        for (name, field) in fields.iter() {
-            let field_path = path.join(name);
+        let mut index_nested = HashMap::new();
+
+        for field in fields.values() {
+            let name = field.index_name().into_owned();
+            let field_path = path.join(&name);
+            // field is added after the segment is written
+            if !field_path.exists() {
+                continue;
+            }
+            let exists = match exists_path.join(&name) {
+                p if p.exists() => Some(Bitmap::deserialize::<Portable>(&std::fs::read(p)?)),
+                _ => None,
+            };
 
             use proto::core::field::Type::*;
             match field.r#type() {
-                Bool | Int | Float | String => {
-                    match TermIndex::new_disk(start, field.clone(), field_path) {
+                Bool | Int | Float | String | Date | Bytes => {
+                    match TermIndex::new_disk(start, field.clone(), field_path, exists) {
                         Ok(ti) => {
-                            index_terms.insert(name.clone(), ti.reader());
+                            index_terms.insert(name, ti.reader());
                         }
                         Err(e) => {
                             log::error!("load term:{:?} index error:{:?}", name, e);
@@ -124,9 +176,10 @@ impl DiskSegment {
                     };
                 }
                 proto::core::field::Type::Text => {
-                    match FulltextIndex::new_disk(start, field.clone(), field_path) {
+                    match FulltextIndex::new_disk(start, field.clone(), field_path, exists) {
                         Ok(fi) => {
-                            index_fulltext.insert(name.clone(), Arc::new(fi.reader()));
+                            let reader = RwLock::new(Arc::new(fi.reader()));
+                            index_fulltext.insert(name, TextIndex { index: fi, reader });
                         }
                         Err(e) => {
                             log::error!("load fulltext:{:?} index error:{:?}", name, e);
@@ -134,6 +187,17 @@ impl DiskSegment {
                         }
                     };
                 }
+                Nested => {
+                    match NestedIndex::new_disk(start, field.clone(), field_path) {
+                        Ok(ni) => {
+                            index_nested.insert(name, Arc::new(ni.reader()));
+                        }
+                        Err(e) => {
+                            log::error!("load nested:{:?} index error:{:?}", name, e);
+                            return Err(e);
+                        }
+                    };
+                }
                 proto::core::field::Type::Geo => todo!(),
                 proto::core::field::Type::Vector => todo!(),
             }
@@ -143,10 +207,13 @@ impl DiskSegment {
             start,
             end,
             dels,
+            first,
             name_store,
             source_store,
+            payload_store,
             index_terms,
             index_fulltext,
+            index_nested,
             marker,
             usage_bytes,
         })
@@ -154,13 +221,30 @@ impl DiskSegment {
 
     pub(crate) fn get_text_reader(&self, field: &Field) -> CoreResult<Arc<FulltextIndexReader>> {
         self.index_fulltext
-            .get(&field.name)
-            .cloned()
+            .get(field.index_name().as_ref())
+            .map(|ti| ti.reader.read().unwrap().clone())
             .ok_or_else(|| {
                 CoreError::InvalidParam(format!("field:{:?} not found in text index", field.name))
             })
     }
 
+    pub(crate) fn get_nested_reader(&self, field: &Field) -> CoreResult<Arc<NestedIndexReader>> {
+        self.index_nested
+            .get(field.index_name().as_ref())
+            .cloned()
+            .ok_or_else(|| {
+                CoreError::InvalidParam(format!("field:{:?} not found in nested index", field.name))
+            })
+    }
+
+    pub(crate) fn set_analyzer(&self, field: &str, analyzer: Arc<Analyzer>) {
+        if let Some(ti) = self.index_fulltext.get(field) {
+            let mut reader = ti.reader.write().unwrap();
+            ti.index.set_analyzer(analyzer);
+            *reader = Arc::new(ti.index.reader());
+        }
+    }
+
     pub fn mark_delete(&self, _del: u64) {
         unimplemented!();
         // self.dels.write().unwrap().add((del - self.start) as u32);
@@ -175,19 +259,39 @@ impl DiskSegment {
     }
 
     pub fn all_record(&self) -> Bitmap {
-        let all_record = Bitmap::from_iter((0..self.end - self.start + 1).map(|v| v as u32));
+        let all_record = Bitmap::from_iter(self.first..=(self.end - self.start) as u32);
         all_record - &*self.dels.read().unwrap()
     }
 
     pub fn term(&self, field: &Field, value: &Vec<u8>) -> CoreResult<Bitmap> {
         Ok(self
-            .term_reader(&field.name)?
+            .term_reader(&field.index_name())?
             .term(value)
             .unwrap_or_default())
     }
 
     pub(crate) fn in_terms(&self, field: &Field, list: &[Vec<u8>]) -> CoreResult<Bitmap> {
-        Ok(self.term_reader(&field.name)?.in_terms(list))
+        Ok(self.term_reader(&field.index_name())?.in_terms(list))
+    }
+
+    pub(crate) fn exists(&self, field: &Field) -> CoreResult<Bitmap> {
+        Ok(self
+            .term_reader(&field.index_name())?
+            .exists
+            .as_ref()
+            .clone())
+    }
+
+    pub(crate) fn pattern(&self, field: &Field, pattern: &TermPattern) -> CoreResult<Bitmap> {
+        self.term_reader(&field.index_name())?.pattern(pattern)
+    }
+
+    pub(crate) fn terms<F>(&self, field: &Field, f: F) -> CoreResult<()>
+    where
+        F: FnMut(&[u8], &Bitmap),
+    {
+        self.term_reader(&field.index_name())?.terms(f);
+        Ok(())
     }
 
     pub(crate) fn between(
@@ -198,10 +302,16 @@ impl DiskSegment {
         high: Option<&Vec<u8>>,
         high_eq: bool,
     ) -> CoreResult<Bitmap> {
-        self.term_reader(&field.name)?
+        self.term_reader(&field.index_name())?
             .between(low, low_eq, high, high_eq)
     }
 
+    pub(crate) fn has_index(&self, name: &str) -> bool {
+        self.index_terms.contains_key(name)
+            || self.index_fulltext.contains_key(name)
+            || self.index_nested.contains_key(name)
+    }
+
     fn term_reader(&self, name: &str) -> CoreResult<&TermIndexReader> {
         self.index_terms
             .get(name)
@@ -209,7 +319,23 @@ impl DiskSegment {
     }
 
     pub(crate) fn doc(&self, id: u64) -> Option<Cow<Record>> {
-        self.source_store.get(&self.abs_id(id)).map(Cow::Owned)
+        let id = self.abs_id(id);
+        let mut record = self.source_store.get(&id)?;
+        if let Some(payload) = self.payload_store.as_ref().and_then(|p| p.get(&id)) {
+            record.payload = payload;
+        }
+        Some(Cow::Owned(record))
+    }
+
+    /// records of the segment for index, payloads are not read. deleted records are not
+    /// written to disk
+    pub(crate) fn records(&self) -> Vec<Record> {
+        let mut iter = self.source_store.iter();
+        let mut records = Vec::with_capacity(self.source_store.len() as usize);
+        while let Some((_, record)) = iter.next() {
+            records.push(record);
+        }
+        records
     }
 
     pub(crate) fn find_by_name(&self, name: &String) -> Option<u64> {
@@ -237,6 +363,13 @@ impl DiskSegment {
             doc_count: self.source_store.len(),
             del_count: self.dels.read().unwrap().cardinality() as u32,
             marker: self.marker.clone(),
+            stale_fields: self
+                .index_fulltext
+                .iter()
+                .filter(|(_, ti)| ti.reader.read().unwrap().is_stale())
+                .map(|(name, _)| name.clone())
+                .sorted()
+                .collect(),
         })
     }
 }
@@ -6,24 +6,48 @@ use std::{
 };
 
 use croaring::Bitmap;
+use itertools::Itertools;
 use mem_btree::{BTree, BatchWrite};
 use proto::core::{Field, Record};
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 
 use crate::{
+    analyzer::Analyzer,
     index_store::index_term::TermIndex,
     util::{CoreError, CoreResult},
-    RecordWrapper,
+    ActionType, RecordWrapper, Scope,
 };
 
 use super::{
     index_fulltext::{reader::FulltextIndexReader, FulltextIndex},
+    index_nested::{NestedIndex, NestedIndexReader},
     index_term::reader::TermIndexReader,
+    pattern::TermPattern,
 };
 
+#[allow(clippy::enum_variant_names)]
 pub enum IndexEnum {
     TermIndex(Arc<TermIndex>),
     FulltextIndex(Arc<FulltextIndex>),
+    NestedIndex(Arc<NestedIndex>),
+}
+
+impl IndexEnum {
+    fn index_name(&self) -> Cow<'_, str> {
+        match self {
+            IndexEnum::TermIndex(i) => i.index_name(),
+            IndexEnum::FulltextIndex(i) => i.index_name(),
+            IndexEnum::NestedIndex(i) => i.index_name(),
+        }
+    }
+
+    fn write(&self, records: &[RecordWrapper]) {
+        match self {
+            IndexEnum::TermIndex(i) => i.write(records),
+            IndexEnum::FulltextIndex(i) => i.write(records),
+            IndexEnum::NestedIndex(i) => i.write(records),
+        }
+    }
 }
 
 pub struct MemSegment {
@@ -41,10 +65,11 @@ pub struct MemSegment {
 
 impl MemSegment {
     pub fn new(start: u64, fields: HashMap<String, Arc<Field>>) -> CoreResult<Self> {
+        // ids are start..=end, it is empty if end < start
         let start = start + 1;
         let segment = MemSegment {
             start,
-            end: AtomicU64::new(start),
+            end: AtomicU64::new(start - 1),
             dels: RwLock::new(Default::default()),
             source_store: RwLock::new(BTree::new(32)),
             name_store: RwLock::new(BTree::new(32)),
@@ -66,8 +91,8 @@ impl MemSegment {
     /// if field already exists, return error
     #[allow(clippy::arc_with_non_send_sync)]
     pub fn add_index_field(&self, start: u64, field: Arc<Field>) -> CoreResult<()> {
-        let name = field.name.clone();
-        if self.index_term.read().unwrap().contains_key(&name) {
+        let name = field.index_name().into_owned();
+        if self.has_index(&name) {
             return Err(CoreError::Existed(format!(
                 "field {} already existed in index store",
                 name
@@ -76,7 +101,7 @@ impl MemSegment {
 
         use proto::core::field::Type::*;
         match field.r#type() {
-            Bool | Int | Float | String => {
+            Bool | Int | Float | String | Date | Bytes => {
                 let index = Arc::new(TermIndex::new_mem(start, field)?);
                 self.indexs_arr
                     .write()
@@ -92,6 +117,13 @@ impl MemSegment {
                     .push(IndexEnum::FulltextIndex(index.clone()));
                 self.index_fulltext.write().unwrap().insert(name, index);
             }
+            Nested => {
+                let index = Arc::new(NestedIndex::new_mem(start, field)?);
+                self.indexs_arr
+                    .write()
+                    .unwrap()
+                    .push(IndexEnum::NestedIndex(index));
+            }
             proto::core::field::Type::Geo => todo!(),
             proto::core::field::Type::Vector => todo!(),
         }
@@ -99,40 +131,79 @@ impl MemSegment {
         Ok(())
     }
 
+    pub(crate) fn has_index(&self, name: &str) -> bool {
+        self.indexs_arr
+            .read()
+            .unwrap()
+            .iter()
+            .any(|i| i.index_name() == name)
+    }
+
+    pub(crate) fn remove_index(&self, name: &str) {
+        self.indexs_arr
+            .write()
+            .unwrap()
+            .retain(|i| i.index_name() != name);
+        self.index_term.write().unwrap().remove(name);
+        self.index_fulltext.write().unwrap().remove(name);
+    }
+
+    /// add index of the field and index records written before, the segment must be
+    /// freezed so no record is written at the same time
+    pub(crate) fn build_index_field(&self, scope: &Scope, field: Arc<Field>) -> CoreResult<()> {
+        let name = field.index_name().into_owned();
+        self.add_index_field(self.start, field)?;
+
+        let records = self
+            .source_store
+            .read()
+            .unwrap()
+            .iter()
+            .map(|item| RecordWrapper::new(scope, item.1.clone(), ActionType::Append))
+            .filter(|r| r.valid_index())
+            .collect_vec();
+
+        if let Some(index) = self
+            .indexs_arr
+            .read()
+            .unwrap()
+            .iter()
+            .find(|i| i.index_name() == name)
+        {
+            index.write(&records);
+        }
+        Ok(())
+    }
+
     pub fn write_records(
         &self,
         records: Vec<RecordWrapper>,
         max: u64,
         marker: Option<String>,
     ) -> Vec<CoreError> {
+        // records with error or deleted are not written
+        let results = records.iter().map(|r| r.result.clone()).collect();
+        let records = records
+            .into_iter()
+            .filter(|r| r.valid_index())
+            .collect_vec();
+
         self.indexs_arr
             .read()
             .unwrap()
             .par_iter()
-            .for_each(|index| match index {
-                IndexEnum::TermIndex(i) => {
-                    i.write(&records);
-                }
-                IndexEnum::FulltextIndex(i) => {
-                    i.write(&records);
-                }
-            });
+            .for_each(|index| index.write(&records));
 
         let mut source_bw = BatchWrite::default();
         let mut name_bw = BatchWrite::default();
 
-        let results = records
-            .into_iter()
-            .map(|r| {
-                let id = r.abs_id(self.start);
-                if !r.record.name.is_empty() {
-                    name_bw.put(r.record.name.clone(), id);
-                }
-                source_bw.put(id, r.record);
-
-                r.result
-            })
-            .collect();
+        for r in records {
+            let id = r.abs_id(self.start);
+            if !r.record.name.is_empty() {
+                name_bw.put(r.record.name.clone(), id);
+            }
+            source_bw.put(id, r.record);
+        }
 
         //write name -> id mapping
         let mut name_store = { self.name_store.write().unwrap().clone() };
@@ -183,19 +254,40 @@ impl MemSegment {
     }
 }
 
+impl MemSegment {
+    pub(crate) fn set_analyzer(&self, field: &str, analyzer: Arc<Analyzer>) {
+        if let Some(fi) = self.index_fulltext.read().unwrap().get(field) {
+            fi.set_analyzer(analyzer);
+        }
+    }
+
+    pub(crate) fn analyzers(&self) -> HashMap<String, Arc<Analyzer>> {
+        self.index_fulltext
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(name, fi)| (name.clone(), fi.analyzer()))
+            .collect()
+    }
+}
+
 // read segment
 impl MemSegment {
     pub(crate) fn reader(&self) -> MemSegmentReader {
         let mut index_term = HashMap::new();
         let mut index_fulltext = HashMap::new();
+        let mut index_nested = HashMap::new();
 
         for index in self.indexs_arr.read().unwrap().iter() {
             match index {
                 IndexEnum::TermIndex(i) => {
-                    index_term.insert(i.field_name().to_string(), i.reader());
+                    index_term.insert(i.index_name().into_owned(), i.reader());
                 }
                 IndexEnum::FulltextIndex(f) => {
-                    index_fulltext.insert(f.field_name().to_string(), Arc::new(f.reader()));
+                    index_fulltext.insert(f.index_name().into_owned(), Arc::new(f.reader()));
+                }
+                IndexEnum::NestedIndex(n) => {
+                    index_nested.insert(n.index_name().into_owned(), Arc::new(n.reader()));
                 }
             }
         }
@@ -208,6 +300,7 @@ impl MemSegment {
             name_store: self.name_store.read().unwrap().clone(),
             index_term,
             index_fulltext,
+            index_nested,
             live_time: self.created_at.elapsed(),
             marker: self.marker.read().unwrap().clone(),
         }
@@ -222,6 +315,7 @@ pub struct MemSegmentReader {
     pub name_store: BTree<String, u32>,
     pub index_term: HashMap<String, TermIndexReader>,
     pub index_fulltext: HashMap<String, Arc<FulltextIndexReader>>,
+    pub index_nested: HashMap<String, Arc<NestedIndexReader>>,
     pub live_time: Duration,
     pub marker: Option<String>,
 }
@@ -229,13 +323,13 @@ pub struct MemSegmentReader {
 impl MemSegmentReader {
     pub fn term(&self, field: &Field, value: &Vec<u8>) -> CoreResult<Bitmap> {
         Ok(self
-            .term_reader(&field.name)?
+            .term_reader(&field.index_name())?
             .term(value)
             .unwrap_or_default())
     }
 
     pub fn all_record(&self) -> Bitmap {
-        if self.end <= self.start {
+        if self.end < self.start {
             return Bitmap::new();
         }
         let all_records = Bitmap::from_iter((0..self.end - self.start + 1).map(|v| v as u32));
@@ -250,12 +344,38 @@ impl MemSegmentReader {
         high: Option<&Vec<u8>>,
         high_eq: bool,
     ) -> CoreResult<Bitmap> {
-        self.term_reader(&field.name)?
+        self.term_reader(&field.index_name())?
             .between(low, low_eq, high, high_eq)
     }
 
     pub(crate) fn in_terms(&self, field: &Field, list: &[Vec<u8>]) -> CoreResult<Bitmap> {
-        Ok(self.term_reader(&field.name)?.in_terms(list))
+        Ok(self.term_reader(&field.index_name())?.in_terms(list))
+    }
+
+    pub(crate) fn exists(&self, field: &Field) -> CoreResult<Bitmap> {
+        Ok(self
+            .term_reader(&field.index_name())?
+            .exists
+            .as_ref()
+            .clone())
+    }
+
+    pub(crate) fn pattern(&self, field: &Field, pattern: &TermPattern) -> CoreResult<Bitmap> {
+        self.term_reader(&field.index_name())?.pattern(pattern)
+    }
+
+    pub(crate) fn terms<F>(&self, field: &Field, f: F) -> CoreResult<()>
+    where
+        F: FnMut(&[u8], &Bitmap),
+    {
+        self.term_reader(&field.index_name())?.terms(f);
+        Ok(())
+    }
+
+    pub(crate) fn has_index(&self, name: &str) -> bool {
+        self.index_term.contains_key(name)
+            || self.index_fulltext.contains_key(name)
+            || self.index_nested.contains_key(name)
     }
 
     fn term_reader(&self, name: &str) -> CoreResult<&TermIndexReader> {
@@ -286,13 +406,22 @@ impl MemSegmentReader {
 
     pub(crate) fn get_text_reader(&self, field: &Field) -> CoreResult<Arc<FulltextIndexReader>> {
         self.index_fulltext
-            .get(&field.name)
+            .get(field.index_name().as_ref())
             .cloned()
             .ok_or_else(|| {
                 CoreError::InvalidParam(format!("field:{:?} not found in text index", field.name))
             })
     }
 
+    pub(crate) fn get_nested_reader(&self, field: &Field) -> CoreResult<Arc<NestedIndexReader>> {
+        self.index_nested
+            .get(field.index_name().as_ref())
+            .cloned()
+            .ok_or_else(|| {
+                CoreError::InvalidParam(format!("field:{:?} not found in nested index", field.name))
+            })
+    }
+
     pub(crate) fn info(&self) -> CoreResult<super::SegmentInfo> {
         Ok(super::SegmentInfo {
             start: self.start,
@@ -302,6 +431,13 @@ impl MemSegmentReader {
             doc_count: self.source_store.len() as u32,
             del_count: self.dels.cardinality() as u32,
             marker: self.marker.clone(),
+            stale_fields: self
+                .index_fulltext
+                .iter()
+                .filter(|(_, fi)| fi.is_stale())
+                .map(|(name, _)| name.clone())
+                .sorted()
+                .collect(),
         })
     }
 }
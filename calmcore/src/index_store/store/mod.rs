@@ -5,6 +5,7 @@ use std::{
     sync::{Arc, RwLock},
 };
 
+use croaring::Bitmap;
 use disk::DiskInvertIndex;
 use mem_btree::{
     persist::{KVDeserializer, KVSerializer},
@@ -100,6 +101,21 @@ where
     }
 }
 
+impl<K> InvertIndexReader<K, Bitmap>
+where
+    K: Ord + Clone,
+{
+    /// union of all bitmaps, it walks the whole index
+    pub fn union_all(&self) -> Bitmap {
+        let mut result = Bitmap::new();
+        self.range(None, |_, v| {
+            result |= v;
+            true
+        });
+        result
+    }
+}
+
 pub enum IterKey<'a, K> {
     Memory(&'a K),
     Disk(&'a [u8]),
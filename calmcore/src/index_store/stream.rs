@@ -1,21 +1,22 @@
-use std::{
-    cmp::max,
-    collections::{BTreeMap, HashMap},
-    fmt::Debug,
-    sync::Arc,
-};
+use std::{cmp::max, fmt::Debug, sync::Arc};
 
-use croaring::bitmap::BitmapIterator;
+use croaring::{bitmap::BitmapIterator, Bitmap};
+use proto::core::Explanation;
 
 use crate::analyzer::Token;
 
-use super::{index_fulltext::reader::FulltextIndexReader, seacher::plan};
+use super::{
+    index_fulltext::reader::{FulltextIndexReader, TokenTerms},
+    seacher::plan,
+};
 
 pub trait HitStream: Send + Debug {
     fn next(&mut self);
     fn value(&self) -> Option<u64>;
     fn score(&self) -> f32;
     fn next_value(&mut self, value: u64) -> Option<u64>;
+    /// how the score of current value is made
+    fn explain(&self) -> Explanation;
 }
 
 #[derive(Debug)]
@@ -24,7 +25,10 @@ pub struct CombHitStream {
     operator: plan::LogicOperator,
     value: Option<u64>,
     score: f32,
-    map: BTreeMap<u64, f32>,
+    // for or, a value must be in this number of streams at least
+    min_match: usize,
+    // for or, score is max + tie_breaker * (sum - max) if set, else sum
+    tie_breaker: Option<f32>,
 }
 
 impl CombHitStream {
@@ -34,7 +38,24 @@ impl CombHitStream {
             operator,
             value: Some(0),
             score: 0.0,
-            map: BTreeMap::new(),
+            min_match: 1,
+            tie_breaker: None,
+        }
+    }
+
+    /// or stream scored by the best stream, others are added by tie_breaker
+    pub fn dis_max(capacity: usize, tie_breaker: f32) -> Self {
+        Self {
+            tie_breaker: Some(tie_breaker),
+            ..Self::new(capacity, plan::LogicOperator::Or)
+        }
+    }
+
+    /// or stream of k of n mode, a value is matched if it is in k streams at least
+    pub fn at_least(capacity: usize, k: usize) -> Self {
+        Self {
+            min_match: k.max(1),
+            ..Self::new(capacity, plan::LogicOperator::Or)
         }
     }
 
@@ -91,36 +112,43 @@ impl HitStream for CombHitStream {
                 }
             }
             plan::LogicOperator::Or => {
-                if let Some((value, score)) = self.map.pop_first() {
-                    self.value = Some(value);
-                    self.score = score;
-                    return;
-                }
-
-                let mut map = BTreeMap::new();
-
-                let mut all_none = true;
-                for stream in self.streams.iter_mut() {
-                    stream.next();
-                    if let Some(value) = stream.value() {
-                        if let Some(existing_score) = map.get_mut(&value) {
-                            *existing_score += stream.score();
-                        } else {
-                            map.insert(value, stream.score());
+                let mut value = value;
+                loop {
+                    // only streams on the last value or not started move on,
+                    // so a value in many streams is merged to one
+                    for stream in self.streams.iter_mut() {
+                        match stream.value() {
+                            Some(v) if v != value => {}
+                            _ => stream.next(),
                         }
-                        all_none = false;
                     }
-                }
 
-                if all_none {
-                    self.value = None;
-                    return;
+                    let min = match self.streams.iter().filter_map(|s| s.value()).min() {
+                        Some(min) => min,
+                        None => {
+                            self.value = None;
+                            return;
+                        }
+                    };
+
+                    let (count, score, best) = self
+                        .streams
+                        .iter()
+                        .filter(|s| s.value() == Some(min))
+                        .fold((0, 0.0, f32::MIN), |(c, score, best), s| {
+                            (c + 1, score + s.score(), best.max(s.score()))
+                        });
+
+                    if count >= self.min_match {
+                        self.value = Some(min);
+                        self.score = match self.tie_breaker {
+                            Some(t) => best + t * (score - best),
+                            None => score,
+                        };
+                        return;
+                    }
+                    value = min;
                 }
-
-                let (value, score) = map.pop_first().unwrap();
-                self.value = Some(value);
-                self.score = score;
-                self.map = map;
             }
         };
     }
@@ -180,43 +208,104 @@ impl HitStream for CombHitStream {
                     // if more than one value is not same, skip value to max . loop check for this
                 }
             }
-            plan::LogicOperator::Or => {
-                if let Some((value, score)) = self.map.pop_first() {
-                    if value >= skip {
-                        self.value = Some(value);
-                        self.score = score;
-                        return Some(value);
-                    }
+            plan::LogicOperator::Or => loop {
+                self.next();
+                match self.value {
+                    Some(v) if v < skip => continue,
+                    v => return v,
                 }
+            },
+        }
+    }
 
-                let mut map = BTreeMap::new();
+    fn explain(&self) -> Explanation {
+        let description = match (&self.operator, self.tie_breaker) {
+            (plan::LogicOperator::And, _) => "sum of".to_string(),
+            (plan::LogicOperator::Or, Some(t)) => format!("max plus {} times others of", t),
+            (plan::LogicOperator::Or, None) if self.min_match > 1 => {
+                format!("sum of, at least {} matched", self.min_match)
+            }
+            (plan::LogicOperator::Or, None) => "sum of matched".to_string(),
+        };
+        // for and, every stream is on the value
+        let and = matches!(self.operator, plan::LogicOperator::And);
+        Explanation {
+            value: self.score,
+            description,
+            details: self
+                .streams
+                .iter()
+                .filter(|s| and || (s.value().is_some() && s.value() == self.value))
+                .map(|s| s.explain())
+                .collect(),
+        }
+    }
+}
 
-                let mut all_none = true;
-                for stream in self.streams.iter_mut() {
-                    stream.next();
-                    if let Some(value) = stream.value() {
-                        if let Some(existing_score) = map.get_mut(&value) {
-                            *existing_score += stream.score();
-                        } else {
-                            map.insert(value, stream.score());
-                        }
-                        all_none = false;
-                    }
-                }
+/// values of the inner stream which are in the filter
+pub struct FilterStream {
+    start: u64,
+    inner: Box<dyn HitStream>,
+    filter: Bitmap,
+}
 
-                if all_none {
-                    self.value = None;
-                    return None;
-                }
+impl Debug for FilterStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FilterStream")
+            .field("start", &self.start)
+            .field("inner", &self.inner)
+            .field("filter", &self.filter.cardinality())
+            .finish()
+    }
+}
 
-                let (value, score) = map.pop_first().unwrap();
-                self.value = Some(value);
-                self.score = score;
-                self.map = map;
-                self.value
+impl FilterStream {
+    pub fn new(start: u64, inner: Box<dyn HitStream>, filter: Bitmap) -> Self {
+        Self {
+            start,
+            inner,
+            filter,
+        }
+    }
+
+    fn contains(&self, value: u64) -> bool {
+        self.filter.contains((value - self.start) as u32)
+    }
+}
+
+impl HitStream for FilterStream {
+    fn next(&mut self) {
+        loop {
+            self.inner.next();
+            match self.inner.value() {
+                Some(v) if !self.contains(v) => continue,
+                _ => return,
             }
         }
     }
+
+    fn value(&self) -> Option<u64> {
+        self.inner.value()
+    }
+
+    fn score(&self) -> f32 {
+        self.inner.score()
+    }
+
+    fn next_value(&mut self, value: u64) -> Option<u64> {
+        let mut skip = value;
+        loop {
+            let v = self.inner.next_value(skip)?;
+            if self.contains(v) {
+                return Some(v);
+            }
+            skip = v + 1;
+        }
+    }
+
+    fn explain(&self) -> Explanation {
+        self.inner.explain()
+    }
 }
 
 pub struct BitmapStream {
@@ -272,7 +361,7 @@ impl HitStream for BitmapStream {
     }
 
     fn next_value(&mut self, value: u64) -> Option<u64> {
-        let value = (value - self.start) as u32;
+        let value = value.saturating_sub(self.start) as u32;
         loop {
             let v = match self.value {
                 Some(v) => v,
@@ -284,14 +373,22 @@ impl HitStream for BitmapStream {
             self.iter.next();
         }
     }
+
+    fn explain(&self) -> Explanation {
+        Explanation {
+            value: self.score,
+            description: "constant score of matched records".to_string(),
+            details: vec![],
+        }
+    }
 }
 
 pub struct TextStream {
     reader: Arc<FulltextIndexReader>,
     boost: f32,
-    tokens: Vec<Token>,
+    paths: Vec<Vec<Token>>,
     iter: BitmapIterator<'static>,
-    token_doc_len: HashMap<String, usize>,
+    token_terms: TokenTerms,
     operator: bool,
     slop: i32,
     value: Option<u32>,
@@ -304,8 +401,8 @@ impl Debug for TextStream {
         f.debug_struct("TextStream")
             .field("reader", &self.reader.doc_count)
             .field("boost", &self.boost)
-            .field("tokens", &self.tokens)
-            .field("token_doc_len", &self.token_doc_len)
+            .field("paths", &self.paths)
+            .field("token_terms", &self.token_terms)
             .field("operator", &self.operator)
             .field("slop", &self.slop)
             .field("value", &self.value)
@@ -319,9 +416,9 @@ impl TextStream {
     pub(crate) fn new(
         reader: Arc<FulltextIndexReader>,
         boost: f32,
-        tokens: Vec<Token>,
+        paths: Vec<Vec<Token>>,
         iter: BitmapIterator<'_>,
-        token_doc_len: HashMap<String, usize>,
+        token_terms: TokenTerms,
         operator: bool,
         slop: i32,
     ) -> Self {
@@ -331,9 +428,9 @@ impl TextStream {
         Self {
             reader,
             boost,
-            tokens,
+            paths,
             iter,
-            token_doc_len,
+            token_terms,
             operator,
             slop,
             value: None,
@@ -372,7 +469,7 @@ impl HitStream for TextStream {
     }
 
     fn next_value(&mut self, value: u64) -> Option<u64> {
-        let value = (value - self.reader.start) as u32;
+        let value = value.saturating_sub(self.reader.start) as u32;
         loop {
             let id = match self.value {
                 Some(v) => v,
@@ -394,17 +491,24 @@ impl HitStream for TextStream {
             }
         }
     }
+
+    fn explain(&self) -> Explanation {
+        let bm25 = match self.value {
+            Some(id) => self.reader.explain(id, &self.token_terms),
+            None => Explanation::default(),
+        };
+        Explanation {
+            value: self.score * self.boost,
+            description: format!("boost {} times", self.boost),
+            details: vec![bm25],
+        }
+    }
 }
 
 impl TextStream {
     fn score(&self, id: u32) -> Option<f32> {
-        self.reader.score(
-            id,
-            &self.tokens,
-            &self.token_doc_len,
-            self.operator,
-            self.slop,
-        )
+        self.reader
+            .score(id, &self.paths, &self.token_terms, self.operator, self.slop)
     }
 
     fn full_id(&self, id: u32) -> u64 {
@@ -1,5 +1,8 @@
 use std::{
-    sync::{Arc, Mutex, RwLock},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, RwLock,
+    },
     time::Duration,
 };
 
@@ -8,17 +11,25 @@ use itertools::Itertools;
 use crate::{index_store::segment::SegmentReader, persist, util::CoreResult, Engine};
 
 /// 全局persisit 锁
-static PERSIST_LOCK: Mutex<()> = Mutex::new(());
+pub(crate) static PERSIST_LOCK: Mutex<()> = Mutex::new(());
 
 pub struct Job {
     engines: RwLock<Vec<Arc<Engine>>>,
     segment_max_size: usize,
     // segment persist interval default:3600
     pub flush_interval_secs: u64,
+    // interval of polling dicts of analyzers, 0 disables it
+    analyzer_interval_secs: u64,
+    // jobs end at the next round once it is set
+    stopped: AtomicBool,
 }
 
 impl Job {
-    pub fn new(segment_max_size: usize, flush_interval_secs: u64) -> Arc<Self> {
+    pub fn new(
+        segment_max_size: usize,
+        flush_interval_secs: u64,
+        analyzer_interval_secs: u64,
+    ) -> Arc<Self> {
         let segment_max_size = if segment_max_size == 0 {
             usize::MAX
         } else {
@@ -35,6 +46,8 @@ impl Job {
             engines: RwLock::new(vec![]),
             segment_max_size,
             flush_interval_secs,
+            analyzer_interval_secs,
+            stopped: AtomicBool::new(false),
         });
 
         let pjob = job.clone();
@@ -47,17 +60,40 @@ impl Job {
             sjob.clone().segment_job();
         });
 
+        if analyzer_interval_secs > 0 {
+            let ajob = job.clone();
+            std::thread::spawn(move || {
+                ajob.clone().analyzer_job();
+            });
+        }
+
         job
     }
 
     pub fn add_engine(&self, engine: Arc<Engine>) {
         self.engines.write().unwrap().push(engine);
     }
+
+    /// the engine is not held by jobs any more, so it can be closed
+    pub fn remove_engine(&self, name: &str) {
+        self.engines
+            .write()
+            .unwrap()
+            .retain(|e| e.scope().schema.name != name);
+    }
+
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::SeqCst);
+    }
+
+    fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::SeqCst)
+    }
 }
 
 impl Job {
     fn persist_job(self: Arc<Self>) {
-        loop {
+        while !self.is_stopped() {
             std::thread::sleep(Duration::from_secs(6));
             let engines = self.engines.read().unwrap().clone();
             for engine in engines {
@@ -70,7 +106,7 @@ impl Job {
     }
 
     fn segment_job(self: Arc<Self>) {
-        loop {
+        while !self.is_stopped() {
             std::thread::sleep(Duration::from_secs(6));
             let engines = self.engines.read().unwrap().clone();
             for engine in engines {
@@ -85,6 +121,30 @@ impl Job {
         }
     }
 
+    fn analyzer_job(self: Arc<Self>) {
+        loop {
+            std::thread::sleep(Duration::from_secs(self.analyzer_interval_secs));
+            if self.is_stopped() {
+                return;
+            }
+            // engines are not cloned, a closing engine waits for no one else to hold it
+            let engines = self.engines.read().unwrap();
+            for engine in engines.iter().filter(|e| !e.is_closeing()) {
+                match engine.poll_analyzers() {
+                    Ok(changed) if !changed.is_empty() => {
+                        log::info!(
+                            "engine:{} index dicts of fields:{:?} changed, old segments are stale",
+                            engine.scope().schema.name,
+                            changed
+                        );
+                    }
+                    Ok(_) => {}
+                    Err(e) => log::error!("reload analyzer error: {:?}", e),
+                }
+            }
+        }
+    }
+
     pub(crate) fn segment(engine: Arc<Engine>, max: u64, ttl: u64) -> CoreResult<()> {
         let segments = engine.segment_readers();
 
@@ -107,7 +167,9 @@ impl Job {
         let mut iter = segments.into_iter();
         let current = iter.next().unwrap(); // remove current
 
-        if current.end() - current.start() > max || current.live_time().as_secs() > ttl {
+        if (current.end() + 1).saturating_sub(current.start()) > max
+            || current.live_time().as_secs() > ttl
+        {
             log::info!(
                 "engine:{} active current segment:{}-{} freeze it",
                 engine_name,
@@ -141,7 +203,7 @@ impl Job {
                     let start_time = std::time::Instant::now();
                     let (start, end) = (reader.start, reader.end);
 
-                    if start == end {
+                    if end < start {
                         log::warn!("engine:{} segment:{}-{} is empty", engine_name, start, end);
                         continue;
                     }
@@ -31,12 +31,14 @@ use std::{
     sync::{Arc, RwLock},
 };
 
-use proto::core::{field, Field, Record, Schema, Value};
+use itertools::Itertools;
+use proto::core::{field, value::Kind, Field, Record, Schema, Value};
 use serde::{Deserialize, Serialize};
 use util::{CoreError, CoreResult};
 
 pub mod analyzer;
 mod calm_core;
+mod dynamic;
 mod embedding;
 mod engine;
 mod index_store;
@@ -44,11 +46,18 @@ mod job;
 pub mod persist;
 mod protocols;
 mod store;
+#[cfg(test)]
+mod test_util;
 pub mod util;
+mod validation;
 
 pub type Engine = engine::Engine;
 pub type CalmCore = calm_core::CalmCore;
 
+/// name of the catch-all field, if schema has it as a text field,
+/// values of all string and text fields of a record are indexed in it
+pub const ALL_FIELD: &str = "_all";
+
 #[derive(Debug, Serialize)]
 pub struct Scope {
     pub schema: Schema,
@@ -96,15 +105,12 @@ impl Action {
         }
     }
 
-    /// Convert Action to RecordWrapper
-    /// # Returns
-    /// * RecordWrapper
-    fn into_recordwrapper(self, scope: &Scope) -> RecordWrapper {
+    pub fn action_type(&self) -> ActionType {
         match self {
-            Action::Append(r) => RecordWrapper::new(scope, r, ActionType::Append),
-            Action::Insert(r) => RecordWrapper::new(scope, r, ActionType::Insert),
-            Action::Upsert(r) => RecordWrapper::new(scope, r, ActionType::Upsert),
-            Action::Delete(r) => RecordWrapper::new(scope, r, ActionType::Delete),
+            Action::Append(_) => ActionType::Append,
+            Action::Insert(_) => ActionType::Insert,
+            Action::Delete(_) => ActionType::Delete,
+            Action::Upsert(_) => ActionType::Upsert,
         }
     }
 
@@ -145,7 +151,7 @@ impl RecordWrapper {
         let result = if record.data.is_empty() {
             None
         } else {
-            Some(util::json_data_to_value(scope, &record.data))
+            Some(util::json_data_to_value(scope, &record.data).map(|v| fill_all_field(scope, v)))
         };
         let vectors = if record.vectors.is_empty() {
             None
@@ -153,6 +159,16 @@ impl RecordWrapper {
             Some(std::mem::take(&mut record.vectors))
         };
 
+        // values and vectors not matched to the schema are errors of the record
+        let result =
+            result.map(|r| r.and_then(|v| validation::validate_value(scope, &v).map(|_| v)));
+        if let Some(Err(e)) = vectors
+            .as_ref()
+            .map(|v| validation::validate_vectors(scope, v))
+        {
+            return Self::with_error(record, action_type, e);
+        }
+
         match result {
             Some(Ok(value)) => Self {
                 action_type,
@@ -178,6 +194,17 @@ impl RecordWrapper {
         }
     }
 
+    /// record is not written, result is the error
+    fn with_error(record: Record, action_type: ActionType, result: CoreError) -> Self {
+        Self {
+            action_type,
+            record,
+            value: None,
+            vectors: None,
+            result,
+        }
+    }
+
     /// Check if the record index valid
     /// # Returns
     /// * bool if true need index else skip
@@ -217,6 +244,50 @@ impl RecordWrapper {
     }
 }
 
+/// join values of string and text fields to the catch-all field, in order of field name
+fn fill_all_field(scope: &Scope, mut value: Value) -> Value {
+    if !matches!(scope.get_field(ALL_FIELD), Some(f) if f.r#type() == field::Type::Text) {
+        return value;
+    }
+    let Some(Kind::ObjectValue(obj)) = value.kind.as_mut() else {
+        return value;
+    };
+
+    let text = obj
+        .fields
+        .iter()
+        .filter(|(name, _)| {
+            name.as_str() != ALL_FIELD
+                && scope
+                    .get_field(name)
+                    .is_some_and(|f| matches!(f.r#type(), field::Type::String | field::Type::Text))
+        })
+        .sorted_by(|a, b| a.0.cmp(b.0))
+        .flat_map(|(_, v)| match &v.kind {
+            Some(Kind::StringValue(s)) => vec![s.as_str()],
+            Some(Kind::ListValue(list)) => list
+                .values
+                .iter()
+                .filter_map(|v| match &v.kind {
+                    Some(Kind::StringValue(s)) => Some(s.as_str()),
+                    _ => None,
+                })
+                .collect(),
+            _ => vec![],
+        })
+        .join("\n");
+
+    if !text.is_empty() {
+        obj.fields.insert(
+            ALL_FIELD.to_string(),
+            Value {
+                kind: Some(Kind::StringValue(text)),
+            },
+        );
+    }
+    value
+}
+
 pub fn easy_schema(
     name: &str,
     fields: Vec<(String, field::Type, Option<field::Option>)>,
@@ -232,12 +303,17 @@ pub fn easy_schema(
                         name,
                         r#type: r#type as i32,
                         option,
+                        source: String::new(),
+                        array: false,
+                        version: 0,
                     },
                 )
             })
             .collect(),
         metadata: None,
         schemaless: false,
+        dynamic: None,
+        validation: 0,
     }
 }
 
@@ -253,7 +329,6 @@ mod tests {
     fn test_scope() {
         let scope = Scope {
             schema: Default::default(),
-            space: Default::default(),
             user_fields: Default::default(),
         };
 
@@ -275,7 +350,6 @@ mod tests {
     fn test_record_wrapper() {
         let scope = Scope {
             schema: Default::default(),
-            space: Default::default(),
             user_fields: Default::default(),
         };
 
@@ -295,6 +369,7 @@ mod tests {
             name: "test".to_string(),
             data: r#"{"name":"test"}"#.as_bytes().to_vec(),
             vectors: vec![],
+            payload: vec![],
         };
 
         let record_wrapper = crate::RecordWrapper::new(&scope, record, crate::ActionType::Insert);
@@ -308,7 +383,6 @@ mod tests {
     fn test_record_wrapper_serialize() {
         let scope = Scope {
             schema: Default::default(),
-            space: Default::default(),
             user_fields: Default::default(),
         };
 
@@ -328,6 +402,7 @@ mod tests {
             name: "test".to_string(),
             data: r#"{"name":"test"}"#.as_bytes().to_vec(),
             vectors: vec![],
+            payload: vec![1, 2, 3],
         };
 
         let record_wrapper = crate::RecordWrapper::new(&scope, record, crate::ActionType::Insert);
@@ -342,5 +417,6 @@ mod tests {
         assert_eq!(record_wrapper.name(), "test");
         assert_eq!(record_wrapper.id(), 1);
         assert_eq!(record_wrapper.abs_id(0), 1);
+        assert_eq!(record_wrapper.record.payload, vec![1, 2, 3]);
     }
 }
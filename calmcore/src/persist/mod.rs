@@ -5,11 +5,18 @@
 //!    |-schema
 //!     |-schema.json
 //!     |-user_schema.json
+//!     |-field_history.json  //old versions of altered fields
 //!    |-segments
 //!     |-[start-end]
 //!         |-version
 //!         |-dels
-//!         |-[field_name]
+//!         |-_payload  //payload of records, not in _source so its layout is kept
+//!         |-_exists
+//!             |-[field_name]  //bitmap of records have value of the field
+//!         |-[nested_field_name]
+//!             |-_parents  //parent ids of objects, u32 array
+//!             |-[field_name]  //term index of objects
+//!         |-[field_name]  //`name@version` of an altered field
 //!             |-field_name.koffset //if variable_index it exist  , u64 array [k1.offset , k2.offset ....]
 //!             |-field_name.keys   //key values array [SEGMENT_VERSION][INDEX_TYPE][version, type, fixed_len, key_len]
 //!             |-field_name.offset
@@ -19,6 +26,10 @@
 //!
 
 pub mod schema;
+pub mod snapshot;
+
+pub(crate) const EXISTS: &str = "_exists";
+pub(crate) const PAYLOAD: &str = "_payload";
 
 use crate::{
     index_store::{
@@ -26,18 +37,19 @@ use crate::{
             reader::FulltextIndexReader,
             serializer::{DocSerializer, TokenSerializer, DOC_INDEX, INDEX_INFO, TERM_INDEX},
         },
+        index_nested::PARENTS,
         index_term::{reader::TermIndexReader, serializer::TermSerializer},
         segment_mem::MemSegmentReader,
     },
     store::Store,
     util::CoreResult,
 };
-use croaring::Bitmap;
+use croaring::{Bitmap, Portable};
 use mem_btree::{
     persist::{self, KVSerializer, TreeWriter},
     BTree, BatchWrite,
 };
-use proto::core::Record;
+use proto::core::{Record, Vector};
 use serde_json::json;
 use std::{
     borrow::Cow,
@@ -97,15 +109,24 @@ pub fn write_segment(store: &Store, reader: Box<MemSegmentReader>) -> CoreResult
 
     write_source(&data_path, &reader)?;
 
-    write_terms(&data_path, &reader)?;
-
-    wrrite_fulltext(&data_path, &reader)?;
+    write_index(&data_path, &reader)?;
 
     std::fs::rename(&data_path, active_path)?;
 
     Ok(())
 }
 
+/// index of all fields in the reader, it is also used to add an index to a written segment
+pub(crate) fn write_index(path: &Path, reader: &MemSegmentReader) -> CoreResult<()> {
+    write_terms(path, reader)?;
+
+    wrrite_fulltext(path, reader)?;
+
+    write_nested(path, reader)?;
+
+    write_exists(path, reader)
+}
+
 fn wrrite_fulltext(path: &Path, reader: &MemSegmentReader) -> CoreResult<()> {
     let write_fulltext =
         |path: PathBuf, ft: &FulltextIndexReader, dels: &Bitmap| -> std::io::Result<()> {
@@ -133,6 +154,7 @@ fn wrrite_fulltext(path: &Path, reader: &MemSegmentReader) -> CoreResult<()> {
             let info = json!({
                 "doc_count":ft.doc_count,
                 "total_term":ft.total_term,
+                "analyzer_digest":ft.index_digest,
             });
 
             pos_write(path.join(INDEX_INFO), info.to_string().as_bytes())
@@ -148,35 +170,38 @@ fn wrrite_fulltext(path: &Path, reader: &MemSegmentReader) -> CoreResult<()> {
     Ok(())
 }
 
-fn write_terms(path: &Path, reader: &MemSegmentReader) -> CoreResult<()> {
-    let write_term =
-        |path: PathBuf, term: &TermIndexReader, dels: &Bitmap| -> std::io::Result<()> {
-            let ser: Box<dyn KVSerializer<Vec<u8>, Bitmap>> = Box::new(TermSerializer {});
-
-            let mut persist_tree = BTree::new(1024);
+fn write_term(path: PathBuf, term: &TermIndexReader, dels: &Bitmap) -> std::io::Result<()> {
+    let ser: Box<dyn KVSerializer<Vec<u8>, Bitmap>> = Box::new(TermSerializer {});
 
-            let mut batch_write = BatchWrite::default();
+    let mut persist_tree = BTree::new(1024);
 
-            term.range(None, |k, v| {
-                let v = v - dels;
-                batch_write.put(k.to_vec(ser.as_ref()), v);
+    let mut batch_write = BatchWrite::default();
 
-                true
-            });
-            persist_tree.write(batch_write);
+    term.range(None, |k, v| {
+        let v = v - dels;
+        batch_write.put(k.to_vec(ser.as_ref()), v);
 
-            let len = match term.field().r#type() {
-                proto::core::field::Type::Bool => 1,
-                proto::core::field::Type::Int => 8,
-                proto::core::field::Type::Float => 8,
-                proto::core::field::Type::String => 0,
-                proto::core::field::Type::Text => 0,
-                _ => unreachable!(),
-            };
-
-            TreeWriter::new(persist_tree, len, ser).persist(&path)
-        };
+        true
+    });
+    persist_tree.write(batch_write);
+
+    let len = match term.field().r#type() {
+        proto::core::field::Type::Bool => 1,
+        proto::core::field::Type::Int => 8,
+        // millis in memcomparable i64
+        proto::core::field::Type::Date => 8,
+        // memcomparable f32
+        proto::core::field::Type::Float => 4,
+        proto::core::field::Type::String => 0,
+        proto::core::field::Type::Text => 0,
+        proto::core::field::Type::Bytes => 0,
+        _ => unreachable!(),
+    };
+
+    TreeWriter::new(persist_tree, len, ser).persist(&path)
+}
 
+fn write_terms(path: &Path, reader: &MemSegmentReader) -> CoreResult<()> {
     for e in reader.index_term.iter() {
         write_term(path.join(e.0), e.1, &reader.dels)?;
     }
@@ -184,6 +209,79 @@ fn write_terms(path: &Path, reader: &MemSegmentReader) -> CoreResult<()> {
     Ok(())
 }
 
+/// objects of deleted records are kept, they are filtered by parents at query time
+fn write_nested(path: &Path, reader: &MemSegmentReader) -> CoreResult<()> {
+    for (field, nested) in reader.index_nested.iter() {
+        let path = path.join(field);
+        std::fs::create_dir_all(&path)?;
+        for (name, term) in nested.children.iter() {
+            write_term(path.join(name), term, &Bitmap::new())?;
+        }
+        let parents = nested
+            .parents
+            .iter()
+            .flat_map(|p| p.to_be_bytes())
+            .collect::<Vec<u8>>();
+        pos_write(path.join(PARENTS), &parents)?;
+    }
+
+    Ok(())
+}
+
+fn write_exists(path: &Path, reader: &MemSegmentReader) -> CoreResult<()> {
+    let path = path.join(EXISTS);
+    std::fs::create_dir_all(&path)?;
+
+    let exists = reader
+        .index_term
+        .iter()
+        .map(|(field, term)| (field, &term.exists))
+        .chain(
+            reader
+                .index_fulltext
+                .iter()
+                .map(|(field, ft)| (field, &ft.exists)),
+        );
+
+    for (field, exists) in exists {
+        let bitmap = exists.as_ref() - &reader.dels;
+        pos_write(path.join(field), &bitmap.serialize::<Portable>())?;
+    }
+
+    Ok(())
+}
+
+/// record in `_source` without payload, payloads are in `_payload`. so the layout is
+/// the same as records of segments written before payload
+#[derive(Serialize, Deserialize)]
+struct SourceRecord<'a> {
+    name: Cow<'a, str>,
+    id: u64,
+    data: Cow<'a, [u8]>,
+    vectors: Cow<'a, [Vector]>,
+}
+
+pub(crate) fn encode_source(record: &Record) -> Vec<u8> {
+    bincode::serialize(&SourceRecord {
+        name: Cow::Borrowed(&record.name),
+        id: record.id,
+        data: Cow::Borrowed(&record.data),
+        vectors: Cow::Borrowed(&record.vectors),
+    })
+    .unwrap()
+}
+
+pub(crate) fn decode_source(v: &[u8]) -> CoreResult<Record> {
+    let source: SourceRecord = bincode::deserialize(v)?;
+    Ok(Record {
+        name: source.name.into_owned(),
+        id: source.id,
+        data: source.data.into_owned(),
+        vectors: source.vectors.into_owned(),
+        payload: vec![],
+    })
+}
+
 struct SourceSerializer;
 
 impl persist::KVSerializer<u32, Record> for SourceSerializer {
@@ -192,7 +290,19 @@ impl persist::KVSerializer<u32, Record> for SourceSerializer {
     }
 
     fn serialize_value<'a>(&self, v: &'a Record) -> std::borrow::Cow<'a, [u8]> {
-        Cow::Owned(bincode::serialize(v).unwrap())
+        Cow::Owned(encode_source(v))
+    }
+}
+
+struct PayloadSerializer;
+
+impl persist::KVSerializer<u32, Vec<u8>> for PayloadSerializer {
+    fn serialize_key<'a>(&self, k: &'a u32) -> std::borrow::Cow<'a, [u8]> {
+        Cow::Owned(k.to_be_bytes().into())
+    }
+
+    fn serialize_value<'a>(&self, v: &'a Vec<u8>) -> std::borrow::Cow<'a, [u8]> {
+        Cow::Borrowed(v)
     }
 }
 
@@ -243,6 +353,32 @@ fn write_source(path: &Path, reader: &MemSegmentReader) -> CoreResult<()> {
     TreeWriter::new(persist_tree, 4, Box::new(SourceSerializer {}))
         .persist(&path.join("_source"))?;
 
+    write_payload(path, reader)
+}
+
+/// payloads of records, it is not written if no record has payload
+fn write_payload(path: &Path, reader: &MemSegmentReader) -> CoreResult<()> {
+    let dels = &reader.dels;
+    let mut persist_tree = BTree::new(1024);
+
+    let mut bw = BatchWrite::default();
+
+    reader
+        .source_store
+        .iter()
+        .filter(|e| !dels.contains(e.0) && !e.1.payload.is_empty())
+        .for_each(|e| {
+            bw.put(e.0, e.1.payload.clone());
+        });
+
+    persist_tree.write(bw);
+    if persist_tree.is_empty() {
+        return Ok(());
+    }
+
+    TreeWriter::new(persist_tree, 4, Box::new(PayloadSerializer {}))
+        .persist(&path.join(PAYLOAD))?;
+
     Ok(())
 }
 
@@ -253,3 +389,43 @@ pub fn read_version(path: &Path) -> CoreResult<Version> {
     let version: Version = serde_json::from_str(&data)?;
     Ok(version)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_source() {
+        // layout of records written before payload
+        #[derive(Serialize)]
+        struct OldRecord {
+            name: String,
+            id: u64,
+            data: Vec<u8>,
+            vectors: Vec<Vector>,
+        }
+        let old = bincode::serialize(&OldRecord {
+            name: "r1".to_string(),
+            id: 3,
+            data: b"{}".to_vec(),
+            vectors: vec![Vector {
+                field_name: "v".to_string(),
+                vector: vec![0.5],
+            }],
+        })
+        .unwrap();
+
+        let record = decode_source(&old).unwrap();
+        assert_eq!(record.name, "r1");
+        assert_eq!(record.id, 3);
+        assert_eq!(record.vectors[0].vector, vec![0.5]);
+
+        // payload is not in `_source`
+        let new = encode_source(&Record {
+            payload: vec![1, 2],
+            ..record.clone()
+        });
+        assert_eq!(new, old);
+        assert_eq!(decode_source(&new).unwrap(), record);
+    }
+}
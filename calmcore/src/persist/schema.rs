@@ -15,6 +15,7 @@ pub struct SchemaStore {
 
 const SCHEMA: &str = "schema.json";
 const USER_SCHEMA: &str = "user_schema.json";
+const FIELD_HISTORY: &str = "field_history.json";
 
 impl SchemaStore {
     pub fn new(path: &Path) -> CoreResult<Self> {
@@ -37,6 +38,29 @@ impl SchemaStore {
         Ok(serde_json::from_str(data.as_str())?)
     }
 
+    /// old versions of altered fields, in the order they are replaced
+    pub(crate) fn append_field_history(&self, field: proto::core::Field) -> CoreResult<()> {
+        let mut history = self.read_field_history()?;
+        history.fields.push(field);
+        self.write_field_history(&history)
+    }
+
+    pub(crate) fn write_field_history(&self, history: &Fields) -> CoreResult<()> {
+        pos_write(
+            self.path.join(FIELD_HISTORY),
+            &serde_json::to_vec_pretty(history)?,
+        )
+    }
+
+    pub(crate) fn read_field_history(&self) -> CoreResult<Fields> {
+        let path = self.path.join(FIELD_HISTORY);
+        if !path.exists() {
+            return Ok(Fields { fields: vec![] });
+        }
+        let data = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(data.as_str())?)
+    }
+
     pub(crate) fn write_schema(&self, schema: &Schema) -> CoreResult<()> {
         pos_write(self.path.join(SCHEMA), &serde_json::to_vec_pretty(schema)?)
     }
@@ -46,3 +70,87 @@ impl SchemaStore {
         Ok(serde_json::from_str(data.as_str())?)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use proto::core::field::{self, Type};
+
+    use crate::Engine;
+
+    use super::*;
+
+    #[test]
+    fn test_read_old_schema() {
+        let test_dir = PathBuf::from("./calmcore_test_old_schema");
+        let _ = fs::remove_dir_all(&test_dir);
+
+        // schema files written before source, array, version, validation and the
+        // options added to fulltext fields and dicts
+        let path = test_dir.join("test").join("schema");
+        fs::create_dir_all(&path).unwrap();
+        let title = r#"{
+            "name": "title",
+            "type": 4,
+            "option": {"Fulltext": {
+                "tokenizer": 0,
+                "filters": [],
+                "stopwords": null,
+                "synonyms": {"name": "syn", "protocol": 0, "value": "[]"}
+            }}
+        }"#;
+        let tag = r#"{"name": "tag", "type": 3, "option": null}"#;
+        fs::write(
+            path.join(SCHEMA),
+            format!(
+                r#"{{"name": "test", "schemaless": false, "fields": {{"title": {}, "tag": {}}}, "metadata": null}}"#,
+                title, tag
+            ),
+        )
+        .unwrap();
+        fs::write(
+            path.join(USER_SCHEMA),
+            format!(r#"{{"fields": [{}, {}]}}"#, title, tag),
+        )
+        .unwrap();
+
+        let store = SchemaStore::new(&test_dir.join("test")).unwrap();
+        let schema = store.read_schema().unwrap();
+        assert_eq!(schema.validation(), proto::core::schema::Validation::Ignore);
+        let title = &schema.fields["title"];
+        assert_eq!(title.source_path(), "title");
+        assert!(!title.array);
+        assert_eq!(title.version, 0);
+        match &title.option {
+            Some(field::Option::Fulltext(f)) => {
+                assert_eq!(f.synonyms.as_ref().unwrap().reload_interval_secs, 0)
+            }
+            _ => panic!("title is not fulltext"),
+        }
+        assert_eq!(store.read_user_schema().unwrap().fields.len(), 2);
+
+        // the engine is opened and written as before
+        let engine = Engine::open(test_dir.to_str().unwrap(), "test").unwrap();
+        assert_eq!(
+            engine.scope().get_field("tag").unwrap().r#type(),
+            Type::String
+        );
+        engine
+            .mutate_json(
+                crate::ActionType::Append,
+                "r1".to_string(),
+                br#"{"title": "a book", "tag": "x"}"#,
+                None,
+            )
+            .unwrap();
+        assert_eq!(
+            engine
+                .sql("SELECT * FROM test WHERE tag = 'x'")
+                .unwrap()
+                .hits
+                .len(),
+            1
+        );
+
+        fs::remove_dir_all(&test_dir).unwrap();
+    }
+}
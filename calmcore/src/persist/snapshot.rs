@@ -0,0 +1,84 @@
+//! # snapshot of engine
+//! |-[snapshot]
+//!    |-manifest.json  //written at last, a snapshot without it is not finished
+//!    |-schema
+//!    |-segments
+//!     |-[start-end]  //files are hard linked to the segments of engine
+//!
+//! files of a warm segment are not changed in place, they are replaced by rename,
+//! so a linked file keeps the content of the time of snapshot
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::util::{CoreError, CoreResult};
+
+use super::pos_write;
+
+/// dir of snapshots in data path, a snapshot of engine is `_snapshots/[engine]/[id]`
+pub(crate) const SNAPSHOTS: &str = "_snapshots";
+const MANIFEST: &str = "manifest.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotManifest {
+    pub engine: String,
+    pub created_at: i64,
+    // start and end of segments in the snapshot
+    pub segments: Vec<(u64, u64)>,
+    #[serde(skip)]
+    pub path: PathBuf,
+}
+
+pub(crate) fn write_manifest(path: &Path, manifest: &SnapshotManifest) -> CoreResult<()> {
+    pos_write(path.join(MANIFEST), &serde_json::to_vec_pretty(manifest)?)
+}
+
+pub(crate) fn read_manifest(path: &Path) -> CoreResult<SnapshotManifest> {
+    let file = path.join(MANIFEST);
+    if !file.exists() {
+        return Err(CoreError::NotExisted(format!(
+            "snapshot manifest:{:?} not found",
+            file
+        )));
+    }
+    let mut manifest: SnapshotManifest = serde_json::from_str(&fs::read_to_string(file)?)?;
+    manifest.path = path.to_path_buf();
+    Ok(manifest)
+}
+
+/// finished snapshots in the dir, the oldest first
+pub(crate) fn list(path: &Path) -> CoreResult<Vec<SnapshotManifest>> {
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut manifests = Vec::new();
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        if entry.path().join(MANIFEST).exists() {
+            manifests.push(read_manifest(&entry.path())?);
+        }
+    }
+    manifests.sort_by_key(|m| m.created_at);
+    Ok(manifests)
+}
+
+/// hard link all files of src to dest, a file is copied if it can not be linked
+/// e.g. dest is in another device
+pub(crate) fn link_dir(src: &Path, dest: &Path) -> CoreResult<()> {
+    fs::create_dir_all(dest)?;
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let target = dest.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            link_dir(&entry.path(), &target)?;
+        } else if fs::hard_link(entry.path(), &target).is_err() {
+            fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
@@ -1,15 +1,34 @@
 pub mod statement;
 
-use sqlparser::ast::{BinaryOperator, Expr, FunctionArg, FunctionArgExpr, SelectItem};
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use itertools::Itertools;
+use sqlparser::ast::{
+    BinaryOperator, Expr, FunctionArg, FunctionArgExpr, Ident, SelectItem, UnaryOperator,
+};
 use statement::parse_where;
 
 use crate::{
-    index_store::seacher::plan::{ComparisonOperator, LogicOperator, Query},
-    util::{str_to_vec_fix_type, string_to_vec_fix_type, CoreError, CoreResult},
-    Scope,
+    analyzer::normalizer,
+    index_store::{
+        index_nested::nested_fields,
+        pattern::TermPattern,
+        seacher::{
+            function::{ArithOperator, Decay, DecayKind, MathFunction, Modifier, ScoreFunction},
+            plan::{
+                Collapse, ComparisonOperator, DateHistogram, Fuzziness, Highlight, LogicOperator,
+                Query,
+            },
+        },
+    },
+    util::{self, str_to_vec_fix_field, CoreError, CoreResult},
+    Scope, ALL_FIELD,
 };
 
-use proto::core::{field, Field, Query as PBQuery};
+use proto::core::{field, Field, Query as PBQuery, Schema};
 
 pub fn pbquery_to_query(scope: &Scope, req: PBQuery) -> CoreResult<Query> {
     let projection = req.fields;
@@ -22,30 +41,94 @@ pub fn pbquery_to_query(scope: &Scope, req: PBQuery) -> CoreResult<Query> {
     let order_by = parse_order_by(req.order_by)?;
     let limit = (req.offset as usize, req.limit as usize);
 
+    let highlight = req
+        .highlight
+        .into_iter()
+        .map(|h| {
+            let mut highlight = new_highlight(scope, &h.field)?;
+            if !h.pre_tag.is_empty() {
+                highlight.pre_tag = h.pre_tag;
+            }
+            if !h.post_tag.is_empty() {
+                highlight.post_tag = h.post_tag;
+            }
+            highlight.fragment_size = h.fragment_size as usize;
+            Ok(highlight)
+        })
+        .collect::<CoreResult<Vec<_>>>()?;
+
+    let collapse = match req.collapse {
+        Some(c) => Some(new_collapse(scope, &c.field, c.inner_hits as usize)?),
+        None => None,
+    };
+
+    let date_histogram = match req.date_histogram {
+        Some(h) => Some(new_date_histogram(
+            scope,
+            &h.field,
+            &h.interval,
+            &h.time_zone,
+        )?),
+        None => None,
+    };
+
     Ok(Query::Search {
         projection,
         query,
         order_by,
         limit,
+        highlight,
+        function: None,
+        explain: req.explain,
+        cache: !req.disable_cache,
+        track_total_hits: (req.track_total_hits > 0).then_some(req.track_total_hits as u64),
+        collapse,
+        date_histogram,
     })
 }
 
 pub fn sql_to_query(scope: &Scope, sql: &str) -> CoreResult<Query> {
     let statement = statement::sql_to_statement(sql)?;
 
+    // SELECT COUNT(*) only counts matched records
+    let count = matches!(
+        statement.projection.as_slice(),
+        [SelectItem::UnnamedExpr(Expr::Function(f))] if function_name(f) == "count"
+    );
+
     // process projection
-    let projection = statement
-        .projection
-        .into_iter()
-        .filter_map(|item| match item {
-            SelectItem::UnnamedExpr(Expr::Identifier(ident)) => Some(ident.value),
+    let mut highlight = Vec::new();
+    let mut projection = Vec::new();
+    let mut track_total_hits = None;
+    let mut date_histogram = None;
+    for item in statement.projection {
+        match item {
+            SelectItem::UnnamedExpr(Expr::Function(f))
+                if function_name(&f) == "track_total_hits" =>
+            {
+                track_total_hits = parse_track_total_hits(&f)?;
+            }
+            SelectItem::UnnamedExpr(Expr::Function(f)) if function_name(&f) == "date_histogram" => {
+                date_histogram = Some(parse_date_histogram(scope, &f)?);
+            }
+            SelectItem::UnnamedExpr(Expr::Identifier(ident)) => projection.push(ident.value),
+            SelectItem::UnnamedExpr(Expr::CompoundIdentifier(idents)) => {
+                projection.push(compound_name(&idents))
+            }
             SelectItem::ExprWithAlias {
                 expr: Expr::Identifier(ident),
                 ..
-            } => Some(ident.value),
-            _ => None,
-        })
-        .collect();
+            } => projection.push(ident.value),
+            SelectItem::ExprWithAlias {
+                expr: Expr::CompoundIdentifier(idents),
+                ..
+            } => projection.push(compound_name(&idents)),
+            SelectItem::UnnamedExpr(Expr::Function(f)) if !count => {
+                highlight.push(parse_highlight(scope, &f)?);
+            }
+            _ => {}
+        }
+    }
 
     // process filter and query
     let query = if let Some(query) = statement.query {
@@ -54,15 +137,33 @@ pub fn sql_to_query(scope: &Scope, sql: &str) -> CoreResult<Query> {
         None
     };
 
-    // process order by
+    if count {
+        return Ok(Query::Count {
+            query,
+            cache: !statement.no_cache,
+        });
+    }
+
+    // process order by, an expression is the function score and sorted as _score
+    let mut function = None;
     let order_by = statement
         .order_by
         .into_iter()
-        .map(|o| match o.expr {
-            Expr::Identifier(ident) => Ok((ident.value, matches!(o.asc, Some(true) | None))),
-            _ => Err(CoreError::InvalidParam(
-                "order by only support identifier".to_string(),
-            )),
+        .map(|o| {
+            let asc = matches!(o.asc, Some(true) | None);
+            match o.expr {
+                Expr::Identifier(ident) => Ok((ident.value, asc)),
+                Expr::CompoundIdentifier(idents) => Ok((compound_name(&idents), asc)),
+                expr => {
+                    if function.is_some() {
+                        return Err(CoreError::InvalidParam(
+                            "order by only support one expression".to_string(),
+                        ));
+                    }
+                    function = Some(parse_score_function(&expr)?);
+                    Ok(("_score".to_string(), asc))
+                }
+            }
         })
         .collect::<CoreResult<Vec<_>>>()?;
 
@@ -86,17 +187,371 @@ pub fn sql_to_query(scope: &Scope, sql: &str) -> CoreResult<Query> {
             };
             (0, limit)
         }
+        (None, Some(o)) => {
+            let offset = match o.value {
+                Expr::Value(sqlparser::ast::Value::Number(n, _)) => n.parse::<usize>()?,
+                _ => return Err(CoreError::InvalidParam("offset must be number".to_string())),
+            };
+            (offset, 10)
+        }
         _ => (0, 10), // 默认值
     };
 
+    let collapse = match statement.collapse {
+        Some((field, inner_hits)) => Some(new_collapse(scope, &field.value, inner_hits as usize)?),
+        None => None,
+    };
+
     Ok(Query::Search {
         projection,
         query,
         order_by,
         limit,
+        highlight,
+        function,
+        explain: statement.explain,
+        cache: !statement.no_cache,
+        track_total_hits,
+        collapse,
+        date_histogram,
     })
 }
 
+/// expression of ORDER BY like `_score * log(1 + likes)`, functions are
+/// math functions, `field_value_factor(likes, factor=1.2, modifier='log1p', missing=1)`,
+/// `gauss|linear|exp(field, origin=0, scale=10, offset=0, decay=0.5)` and `random_score(seed=1, field='id')`
+fn parse_score_function(expr: &Expr) -> CoreResult<ScoreFunction> {
+    let f = match expr {
+        Expr::Identifier(ident) if ident.value.eq_ignore_ascii_case("_score") => {
+            return Ok(ScoreFunction::Score)
+        }
+        Expr::Identifier(ident) => return Ok(ScoreFunction::Field(ident.value.clone())),
+        Expr::CompoundIdentifier(idents) => return Ok(ScoreFunction::Field(compound_name(idents))),
+        Expr::Value(sqlparser::ast::Value::Number(n, _)) => {
+            return Ok(ScoreFunction::Number(n.parse::<f64>()?))
+        }
+        Expr::Nested(nested) => return parse_score_function(nested),
+        Expr::UnaryOp {
+            op: UnaryOperator::Minus,
+            expr,
+        } => return Ok(ScoreFunction::Neg(Box::new(parse_score_function(expr)?))),
+        Expr::UnaryOp {
+            op: UnaryOperator::Plus,
+            expr,
+        } => return parse_score_function(expr),
+        Expr::BinaryOp { left, op, right } => {
+            let op = match op {
+                BinaryOperator::Plus => ArithOperator::Plus,
+                BinaryOperator::Minus => ArithOperator::Minus,
+                BinaryOperator::Multiply => ArithOperator::Multiply,
+                BinaryOperator::Divide => ArithOperator::Divide,
+                BinaryOperator::Modulo => ArithOperator::Modulo,
+                _ => {
+                    return Err(CoreError::InvalidParam(format!(
+                        "operator:{:?} is not support in order by",
+                        op
+                    )))
+                }
+            };
+            return Ok(ScoreFunction::Binary(
+                Box::new(parse_score_function(left)?),
+                op,
+                Box::new(parse_score_function(right)?),
+            ));
+        }
+        Expr::Function(f) => f,
+        _ => {
+            return Err(CoreError::InvalidParam(format!(
+                "expression is not support in order by:{:?}",
+                expr
+            )))
+        }
+    };
+
+    let name = function_name(f);
+    let args = match &f.args {
+        sqlparser::ast::FunctionArguments::List(args) => args
+            .args
+            .iter()
+            .map(|arg| match arg {
+                FunctionArg::Unnamed(FunctionArgExpr::Expr(expr)) => Ok(expr),
+                _ => Err(CoreError::InvalidParam(format!(
+                    "function arg is not support:{:?}",
+                    arg
+                ))),
+            })
+            .collect::<CoreResult<Vec<_>>>()?,
+        sqlparser::ast::FunctionArguments::None => vec![],
+        _ => {
+            return Err(CoreError::InvalidParam(format!(
+                "function args is not support:{:?}",
+                f
+            )))
+        }
+    };
+
+    // `name=value` args
+    let named = |args: &[&Expr]| -> CoreResult<HashMap<String, String>> {
+        args.iter()
+            .map(|arg| match arg {
+                Expr::BinaryOp {
+                    left,
+                    op: BinaryOperator::Eq,
+                    right,
+                } => Ok((expr_liternal(left)?.to_lowercase(), expr_liternal(right)?)),
+                _ => Err(CoreError::InvalidParam(format!(
+                    "function {} arg must be name=value:{:?}",
+                    name, arg
+                ))),
+            })
+            .collect()
+    };
+    let unknown = |args: &HashMap<String, String>, known: &[&str]| match args
+        .keys()
+        .find(|k| !known.contains(&k.as_str()))
+    {
+        Some(k) => Err(CoreError::InvalidParam(format!(
+            "function {} arg:{} is not support",
+            name, k
+        ))),
+        None => Ok(()),
+    };
+
+    match name.as_str() {
+        "field_value_factor" => {
+            let field = match args.first() {
+                Some(expr) => expr_liternal(expr)?,
+                None => {
+                    return Err(CoreError::InvalidParam(
+                        "field_value_factor need a field".to_string(),
+                    ))
+                }
+            };
+            let args = named(&args[1..])?;
+            unknown(&args, &["factor", "modifier", "missing"])?;
+            Ok(ScoreFunction::FieldValueFactor {
+                field,
+                factor: args.get("factor").map_or(Ok(1.0), |v| v.parse::<f64>())?,
+                modifier: Modifier::from_name(
+                    &args
+                        .get("modifier")
+                        .map_or("none".to_string(), |v| v.to_lowercase()),
+                )?,
+                missing: args.get("missing").map_or(Ok(0.0), |v| v.parse::<f64>())?,
+            })
+        }
+        "gauss" | "linear" | "exp" if args.len() > 1 => {
+            let kind = match name.as_str() {
+                "gauss" => DecayKind::Gauss,
+                "linear" => DecayKind::Linear,
+                _ => DecayKind::Exp,
+            };
+            let field = expr_liternal(args[0])?;
+            let args = named(&args[1..])?;
+            unknown(&args, &["origin", "scale", "offset", "decay"])?;
+            let (origin, scale) = match (args.get("origin"), args.get("scale")) {
+                (Some(origin), Some(scale)) => (origin, scale),
+                _ => {
+                    return Err(CoreError::InvalidParam(format!(
+                        "{} need origin and scale",
+                        name
+                    )))
+                }
+            };
+            Ok(ScoreFunction::Decay(Decay::new(
+                kind,
+                field,
+                origin,
+                scale,
+                args.get("offset").map(|s| s.as_str()),
+                args.get("decay").map_or(Ok(0.5), |v| v.parse::<f64>())?,
+            )?))
+        }
+        "random_score" => {
+            let args = named(&args)?;
+            unknown(&args, &["seed", "field"])?;
+            Ok(ScoreFunction::Random {
+                seed: args.get("seed").map_or(Ok(0), |v| v.parse::<u64>())?,
+                field: args.get("field").cloned(),
+            })
+        }
+        _ => match MathFunction::from_name(&name) {
+            Some((m, n)) if n == args.len() => Ok(ScoreFunction::Math(
+                m,
+                args.into_iter()
+                    .map(parse_score_function)
+                    .collect::<CoreResult<Vec<_>>>()?,
+            )),
+            Some((_, n)) => Err(CoreError::InvalidParam(format!(
+                "function {} need {} args",
+                name, n
+            ))),
+            None => Err(CoreError::InvalidParam(format!(
+                "function {} is not support in order by",
+                name
+            ))),
+        },
+    }
+}
+
+fn new_highlight(scope: &Scope, name: &str) -> CoreResult<Highlight> {
+    let field = scope
+        .get_field(name)
+        .ok_or_else(|| CoreError::InvalidParam(format!("field not found: {}", name)))?;
+    check_text(&field)?;
+    Ok(Highlight {
+        field,
+        pre_tag: "<em>".to_string(),
+        post_tag: "</em>".to_string(),
+        fragment_size: 0,
+    })
+}
+
+/// hits are collapsed by a field which has a single comparable value
+fn new_collapse(scope: &Scope, name: &str, inner_hits: usize) -> CoreResult<Collapse> {
+    let field = scope
+        .get_field(name)
+        .ok_or_else(|| CoreError::InvalidParam(format!("field not found: {}", name)))?;
+    match field.r#type() {
+        field::Type::Text | field::Type::Geo | field::Type::Vector | field::Type::Nested => Err(
+            CoreError::InvalidParam(format!("field can not be collapsed: {}", name)),
+        ),
+        _ => Ok(Collapse { field, inner_hits }),
+    }
+}
+
+fn new_date_histogram(
+    scope: &Scope,
+    name: &str,
+    interval: &str,
+    time_zone: &str,
+) -> CoreResult<DateHistogram> {
+    let field = scope
+        .get_field(name)
+        .ok_or_else(|| CoreError::InvalidParam(format!("field not found: {}", name)))?;
+    if field.r#type() != field::Type::Date {
+        return Err(CoreError::InvalidParam(format!(
+            "date_histogram field is not date: {}",
+            name
+        )));
+    }
+    Ok(DateHistogram {
+        field,
+        interval: util::parse_interval(interval)?,
+        time_zone: util::parse_time_zone(time_zone)?,
+    })
+}
+
+/// date_histogram(created, '1d') or date_histogram(created, '1M', '+08:00')
+fn parse_date_histogram(scope: &Scope, f: &sqlparser::ast::Function) -> CoreResult<DateHistogram> {
+    let args = match &f.args {
+        sqlparser::ast::FunctionArguments::List(args) => args
+            .args
+            .iter()
+            .map(|arg| match arg {
+                FunctionArg::Unnamed(FunctionArgExpr::Expr(expr)) => expr_liternal(expr),
+                _ => Err(CoreError::InvalidParam(format!(
+                    "date_histogram arg not support:{:?}",
+                    arg
+                ))),
+            })
+            .collect::<CoreResult<Vec<_>>>()?,
+        _ => vec![],
+    };
+    match args.as_slice() {
+        [field, interval] => new_date_histogram(scope, field, interval, ""),
+        [field, interval, time_zone] => new_date_histogram(scope, field, interval, time_zone),
+        _ => Err(CoreError::InvalidParam(format!(
+            "date_histogram must be date_histogram(field, interval[, time_zone]):{:?}",
+            f
+        ))),
+    }
+}
+
+/// track_total_hits(1000) counts hits up to 1000, track_total_hits(true) or 0 is exact
+fn parse_track_total_hits(f: &sqlparser::ast::Function) -> CoreResult<Option<u64>> {
+    let value = match &f.args {
+        sqlparser::ast::FunctionArguments::List(args) => match args.args.as_slice() {
+            [FunctionArg::Unnamed(FunctionArgExpr::Expr(expr))] => expr_liternal(expr)?,
+            _ => String::new(),
+        },
+        _ => String::new(),
+    };
+    match value.as_str() {
+        "true" => Ok(None),
+        v => match v.parse::<u64>() {
+            Ok(0) => Ok(None),
+            Ok(n) => Ok(Some(n)),
+            Err(_) => Err(CoreError::InvalidParam(format!(
+                "track_total_hits must be a number or true:{:?}",
+                f
+            ))),
+        },
+    }
+}
+
+/// highlight(field, pre='<em>', post='</em>', fragment_size=100)
+fn parse_highlight(scope: &Scope, f: &sqlparser::ast::Function) -> CoreResult<Highlight> {
+    let name = function_name(f);
+    if name != "highlight" {
+        return Err(CoreError::InvalidParam(format!(
+            "function name:{:?} is not support in select only support highlight",
+            name
+        )));
+    }
+
+    let args = match &f.args {
+        sqlparser::ast::FunctionArguments::List(args) => &args.args,
+        _ => {
+            return Err(CoreError::InvalidParam(format!(
+                "highlight args is empty:{:?}",
+                f
+            )))
+        }
+    };
+
+    let mut iter = args.iter();
+    let mut highlight = match iter.next() {
+        Some(FunctionArg::Unnamed(FunctionArgExpr::Expr(expr))) => {
+            new_highlight(scope, &expr_liternal(expr)?)?
+        }
+        _ => {
+            return Err(CoreError::InvalidParam(format!(
+                "highlight field is empty:{:?}",
+                f
+            )))
+        }
+    };
+
+    for arg in iter {
+        match arg {
+            FunctionArg::Unnamed(FunctionArgExpr::Expr(Expr::BinaryOp { left, right, .. })) => {
+                match expr_liternal(left)?.as_ref() {
+                    "pre" | "pre_tag" => highlight.pre_tag = expr_liternal(right)?,
+                    "post" | "post_tag" => highlight.post_tag = expr_liternal(right)?,
+                    "fragment_size" => {
+                        highlight.fragment_size = expr_liternal(right)?.parse::<usize>()?
+                    }
+                    _ => {
+                        return Err(CoreError::InvalidParam(format!(
+                            "highlight arg is not support:{:?}",
+                            arg
+                        )));
+                    }
+                }
+            }
+            _ => {
+                return Err(CoreError::InvalidParam(format!(
+                    "highlight arg is not support:{:?}",
+                    arg
+                )));
+            }
+        }
+    }
+
+    Ok(highlight)
+}
+
 fn parse_order_by(input: Vec<String>) -> CoreResult<Vec<(String, bool)>> {
     if input.is_empty() {
         return Ok(vec![]);
@@ -134,6 +589,22 @@ fn parse_order_by(input: Vec<String>) -> CoreResult<Vec<(String, bool)>> {
 
 fn parse_filter_expr(scope: &Scope, expr: &Expr) -> CoreResult<Query> {
     match expr {
+        // `author.name = 'x'` is on the field named by the dotted path
+        Expr::BinaryOp { left, op, right }
+            if matches!(left.as_ref(), Expr::CompoundIdentifier(_)) =>
+        {
+            let Expr::CompoundIdentifier(idents) = left.as_ref() else {
+                unreachable!()
+            };
+            parse_filter_expr(
+                scope,
+                &Expr::BinaryOp {
+                    left: Box::new(Expr::Identifier(Ident::new(compound_name(idents)))),
+                    op: op.clone(),
+                    right: right.clone(),
+                },
+            )
+        }
         Expr::BinaryOp { left, op, right } => {
             match (left.as_ref(), right.as_ref()) {
                 (Expr::Identifier(c), Expr::Function(f)) => {
@@ -143,6 +614,8 @@ fn parse_filter_expr(scope: &Scope, expr: &Expr) -> CoreResult<Query> {
                         boost,
                         slop,
                         operator,
+                        fuzziness,
+                        case_insensitive,
                     } = parse_function(f)?;
 
                     let query = match name.as_ref() {
@@ -173,8 +646,32 @@ fn parse_filter_expr(scope: &Scope, expr: &Expr) -> CoreResult<Query> {
                                 boost,
                                 operator,
                                 field,
+                                fuzziness,
                             }
                         }
+                        "fuzzy" => {
+                            let field = scope.get_field(&c.value).ok_or_else(|| {
+                                CoreError::InvalidParam(format!("field not found: {}", c.value))
+                            })?;
+
+                            check_text(&field)?;
+
+                            let value = expr_liternal(&value)?;
+                            Query::Text {
+                                value,
+                                boost,
+                                operator,
+                                field,
+                                fuzziness: Some(fuzziness.unwrap_or_default()),
+                            }
+                        }
+                        "prefix" | "wildcard" | "regexp" => {
+                            let field = scope.get_field(&c.value).ok_or_else(|| {
+                                CoreError::InvalidParam(format!("field not found: {}", c.value))
+                            })?;
+                            let value = expr_liternal(&value)?;
+                            new_pattern(&name, value, case_insensitive, field)?
+                        }
                         "score" => {
                             let expr = Expr::BinaryOp {
                                 left: Box::new(Expr::Identifier(c.clone())),
@@ -187,7 +684,7 @@ fn parse_filter_expr(scope: &Scope, expr: &Expr) -> CoreResult<Query> {
                         }
                         _ => {
                             return Err(CoreError::InvalidParam(format!(
-                                "function name:{:?} is not support only support phrase,text",
+                                "function name:{:?} is not support only support phrase,text,fuzzy,prefix,wildcard,regexp,score",
                                 name
                             )));
                         }
@@ -214,10 +711,11 @@ fn parse_filter_expr(scope: &Scope, expr: &Expr) -> CoreResult<Query> {
                             boost: 1.0,
                             operator: "or".to_string(),
                             field,
+                            fuzziness: None,
                         });
                     }
 
-                    let value = str_to_vec_fix_type(value, &field.r#type())?;
+                    let value = str_to_vec_fix_field(value, &field)?;
 
                     let query = match op {
                         BinaryOperator::Eq => Query::Term {
@@ -278,38 +776,13 @@ fn parse_filter_expr(scope: &Scope, expr: &Expr) -> CoreResult<Query> {
                     let field = scope.get_field(&c.value).ok_or_else(|| {
                         CoreError::InvalidParam(format!("field not found: {}", c.value))
                     })?;
-                    let mut values = Vec::with_capacity(list.len());
-                    for v in list.iter() {
-                        let value = string_to_vec_fix_type(expr_liternal(v)?, &field.r#type())?;
-                        values.push(value);
-                    }
-
-                    return Ok(Query::InList {
-                        list: values,
-                        boost: 1.0,
-                        field,
-                    });
+                    return new_in_list(list, field);
                 }
                 (Expr::Identifier(c), Expr::Between { low, high, .. }) => {
                     let field = scope.get_field(&c.value).ok_or_else(|| {
                         CoreError::InvalidParam(format!("field not found: {}", c.value))
                     })?;
-                    let low = Some(string_to_vec_fix_type(
-                        expr_liternal(low)?,
-                        &field.r#type(),
-                    )?);
-                    let high = Some(string_to_vec_fix_type(
-                        expr_liternal(high)?,
-                        &field.r#type(),
-                    )?);
-                    return Ok(Query::Between {
-                        low,
-                        low_eq: true,
-                        high,
-                        high_eq: false,
-                        boost: 1.0,
-                        field,
-                    });
+                    return new_between(low, high, field);
                 }
                 _ => {}
             };
@@ -317,6 +790,48 @@ fn parse_filter_expr(scope: &Scope, expr: &Expr) -> CoreResult<Query> {
             let l = parse_filter_expr(scope, left)?;
             let r = parse_filter_expr(scope, right)?;
 
+            // `a AND FILTER(b)` is one bool query
+            if BinaryOperator::And == *op {
+                match (l, r) {
+                    (
+                        Query::Bool {
+                            mut must,
+                            should,
+                            must_not,
+                            filter,
+                            minimum_should_match,
+                        },
+                        other,
+                    )
+                    | (
+                        other,
+                        Query::Bool {
+                            mut must,
+                            should,
+                            must_not,
+                            filter,
+                            minimum_should_match,
+                        },
+                    ) => {
+                        must.push(other);
+                        return Ok(Query::Bool {
+                            must,
+                            should,
+                            must_not,
+                            filter,
+                            minimum_should_match,
+                        });
+                    }
+                    (l, r) => {
+                        return Ok(Query::Logical {
+                            left: Box::new(l),
+                            right: Box::new(r),
+                            operator: LogicOperator::And,
+                        })
+                    }
+                }
+            }
+
             Ok(Query::Logical {
                 left: Box::new(l),
                 right: Box::new(r),
@@ -329,11 +844,578 @@ fn parse_filter_expr(scope: &Scope, expr: &Expr) -> CoreResult<Query> {
                 },
             })
         }
+        Expr::Function(f) if function_name(f) == "multi_match" => parse_multi_match(scope, f),
+        Expr::Function(f) if function_name(f) == "more_like_this" => parse_more_like_this(scope, f),
+        Expr::Function(f) if function_name(f) == "nested" => parse_nested(scope, f),
+        Expr::Function(f) => parse_bool_function(scope, f),
+        Expr::InList {
+            expr: target,
+            list,
+            negated,
+        } => Ok(negate(
+            new_in_list(list, ident_field(scope, target)?)?,
+            *negated,
+        )),
+        Expr::Between {
+            expr: target,
+            negated,
+            low,
+            high,
+        } => Ok(negate(
+            new_between(low, high, ident_field(scope, target)?)?,
+            *negated,
+        )),
+        Expr::IsNotNull(target) | Expr::IsNull(target) => {
+            let query = Query::Exists {
+                boost: 1.0,
+                field: ident_field(scope, target)?,
+            };
+            Ok(negate(query, matches!(expr, Expr::IsNull(_))))
+        }
+        Expr::UnaryOp {
+            op: UnaryOperator::Not,
+            expr: target,
+        } => Ok(negate(parse_filter_expr(scope, target)?, true)),
+        Expr::Like {
+            negated,
+            any: false,
+            expr: target,
+            pattern,
+            escape_char,
+        }
+        | Expr::ILike {
+            negated,
+            any: false,
+            expr: target,
+            pattern,
+            escape_char,
+        } => {
+            if escape_char.as_ref().is_some_and(|c| c != "\\") {
+                return Err(CoreError::InvalidParam(format!(
+                    "only support escape char '\\' in like: {:?}",
+                    expr
+                )));
+            }
+            let case_insensitive = matches!(expr, Expr::ILike { .. });
+            let field = ident_field(scope, target)?;
+            let value = expr_liternal(pattern)?;
+
+            // `abc%` is a prefix query
+            let query = match value.strip_suffix('%') {
+                Some(v) if !v.contains(['%', '_', '\\']) => {
+                    new_pattern("prefix", v.to_string(), case_insensitive, field)?
+                }
+                _ => new_pattern("wildcard", value, case_insensitive, field)?,
+            };
+            Ok(negate(query, *negated))
+        }
+        Expr::RLike {
+            negated,
+            expr: target,
+            pattern,
+            ..
+        } => {
+            let field = ident_field(scope, target)?;
+            let query = new_pattern("regexp", expr_liternal(pattern)?, false, field)?;
+            Ok(negate(query, *negated))
+        }
         Expr::Nested(nested) => parse_filter_expr(scope, nested.as_ref()),
         _ => Err(CoreError::InvalidParam(format!("unsupport: {:?}", expr))),
     }
 }
 
+/// `FILTER(expr)` matches records without score,
+/// `SHOULD(expr, expr, ..., minimum_should_match=1)` matches records in some of the exprs
+fn parse_bool_function(scope: &Scope, f: &sqlparser::ast::Function) -> CoreResult<Query> {
+    let name = function_name(f);
+
+    let args = match &f.args {
+        sqlparser::ast::FunctionArguments::List(args) => &args.args,
+        _ => {
+            return Err(CoreError::InvalidParam(format!(
+                "function args is not support:{:?}",
+                f
+            )))
+        }
+    };
+
+    let mut clauses = Vec::with_capacity(args.len());
+    let mut minimum_should_match = 1;
+    for arg in args {
+        match arg {
+            FunctionArg::Unnamed(FunctionArgExpr::Expr(Expr::BinaryOp { left, right, .. })) if matches!(left.as_ref(), Expr::Identifier(i) if i.value == "minimum_should_match") =>
+            {
+                minimum_should_match = expr_liternal(right)?.parse::<usize>()?;
+            }
+            FunctionArg::Unnamed(FunctionArgExpr::Expr(expr)) => {
+                clauses.push(parse_filter_expr(scope, expr)?);
+            }
+            _ => {
+                return Err(CoreError::InvalidParam(format!(
+                    "function value is not unnamed:{:?}",
+                    f
+                )));
+            }
+        }
+    }
+
+    if clauses.is_empty() {
+        return Err(CoreError::InvalidParam(format!(
+            "function has no query:{:?}",
+            f
+        )));
+    }
+
+    match name.as_str() {
+        "filter" => Ok(Query::Bool {
+            must: vec![],
+            should: vec![],
+            must_not: vec![],
+            filter: clauses,
+            minimum_should_match: 0,
+        }),
+        "should" => Ok(Query::Bool {
+            must: vec![],
+            should: clauses,
+            must_not: vec![],
+            filter: vec![],
+            minimum_should_match,
+        }),
+        _ => Err(CoreError::InvalidParam(format!(
+            "function name:{:?} is not support only support filter,should",
+            name
+        ))),
+    }
+}
+
+/// `more_like_this(like='record-123', fields=['title','body'], max_query_terms=25, min_term_freq=1, min_doc_freq=1, boost=1.0)`,
+/// like is a record name or a text, fields are all text fields by default
+fn parse_more_like_this(scope: &Scope, f: &sqlparser::ast::Function) -> CoreResult<Query> {
+    let args = match &f.args {
+        sqlparser::ast::FunctionArguments::List(args) => &args.args,
+        _ => {
+            return Err(CoreError::InvalidParam(format!(
+                "more_like_this args is empty:{:?}",
+                f
+            )))
+        }
+    };
+
+    let mut like = None;
+    let mut fields = Vec::new();
+    let mut max_query_terms = 25;
+    let mut min_term_freq = 1;
+    let mut min_doc_freq = 1;
+    let mut boost = 1.0;
+    for arg in args {
+        match arg {
+            FunctionArg::Unnamed(FunctionArgExpr::Expr(Expr::BinaryOp { left, right, .. })) => {
+                match expr_liternal(left)?.as_ref() {
+                    "like" => like = Some(expr_liternal(right)?),
+                    "fields" => {
+                        let names = match right.as_ref() {
+                            Expr::Array(array) => array
+                                .elem
+                                .iter()
+                                .map(expr_liternal)
+                                .collect::<CoreResult<Vec<_>>>()?,
+                            expr => expr_liternal(expr)?
+                                .split(',')
+                                .map(|s| s.trim().to_string())
+                                .filter(|s| !s.is_empty())
+                                .collect(),
+                        };
+                        for name in names {
+                            let field = scope.get_field(&name).ok_or_else(|| {
+                                CoreError::InvalidParam(format!("field not found: {}", name))
+                            })?;
+                            check_text(&field)?;
+                            fields.push(field);
+                        }
+                    }
+                    "max_query_terms" => max_query_terms = expr_liternal(right)?.parse()?,
+                    "min_term_freq" => min_term_freq = expr_liternal(right)?.parse()?,
+                    "min_doc_freq" => min_doc_freq = expr_liternal(right)?.parse()?,
+                    "boost" | "score" => boost = expr_liternal(right)?.parse::<f32>()?,
+                    _ => {
+                        return Err(CoreError::InvalidParam(format!(
+                            "more_like_this arg is not support:{:?}",
+                            arg
+                        )));
+                    }
+                }
+            }
+            _ => {
+                return Err(CoreError::InvalidParam(format!(
+                    "more_like_this arg is not support:{:?}",
+                    arg
+                )));
+            }
+        }
+    }
+
+    let like = match like {
+        Some(like) if !like.is_empty() => like,
+        _ => {
+            return Err(CoreError::InvalidParam(format!(
+                "more_like_this like is empty:{:?}",
+                f
+            )))
+        }
+    };
+
+    // all field is not in source of records
+    if fields.is_empty() {
+        fields = scope
+            .user_fields
+            .read()
+            .unwrap()
+            .values()
+            .filter(|f| f.name != ALL_FIELD && check_text(f).is_ok())
+            .sorted_by(|a, b| a.name.cmp(&b.name))
+            .cloned()
+            .collect();
+    }
+    if fields.is_empty() {
+        return Err(CoreError::InvalidParam(
+            "more_like_this has no text field".to_string(),
+        ));
+    }
+
+    Ok(Query::MoreLikeThis {
+        like,
+        fields,
+        max_query_terms,
+        min_term_freq,
+        min_doc_freq,
+        boost,
+        resolved: None,
+    })
+}
+
+/// `nested(variants, color = 'red' AND size = 'L')` matches records have an object
+/// of the nested field matched by the filter, fields in the filter are fields of objects
+fn parse_nested(scope: &Scope, f: &sqlparser::ast::Function) -> CoreResult<Query> {
+    let args = match &f.args {
+        sqlparser::ast::FunctionArguments::List(args) => args.args.as_slice(),
+        _ => &[],
+    };
+    let (path, filter) = match args {
+        [FunctionArg::Unnamed(FunctionArgExpr::Expr(path)), FunctionArg::Unnamed(FunctionArgExpr::Expr(filter))] => {
+            (ident_field(scope, path)?, filter)
+        }
+        _ => {
+            return Err(CoreError::InvalidParam(format!(
+                "nested args must be path and filter:{:?}",
+                f
+            )))
+        }
+    };
+    if path.r#type() != field::Type::Nested {
+        return Err(CoreError::InvalidParam(format!(
+            "field:{} is not nested",
+            path.name
+        )));
+    }
+
+    let nested_scope = Scope {
+        schema: Schema::default(),
+        user_fields: RwLock::new(
+            nested_fields(&path)?
+                .into_iter()
+                .map(|f| (f.name.clone(), f))
+                .collect(),
+        ),
+    };
+    Ok(Query::Nested {
+        query: Box::new(parse_filter_expr(&nested_scope, filter)?),
+        path,
+        boost: 1.0,
+    })
+}
+
+/// `multi_match('query', fields=['title^3','body'], type='best_fields', operator='or', tie_breaker=0.0)`
+/// fields are `_all` if not set and schema has it, else all text fields.
+/// best_fields is scored by the best field, most_fields by the sum of fields,
+/// cross_fields takes fields as one, every word (split by whitespace) is scored by its best field
+fn parse_multi_match(scope: &Scope, f: &sqlparser::ast::Function) -> CoreResult<Query> {
+    let args = match &f.args {
+        sqlparser::ast::FunctionArguments::List(args) => &args.args,
+        _ => {
+            return Err(CoreError::InvalidParam(format!(
+                "multi_match args is empty:{:?}",
+                f
+            )))
+        }
+    };
+
+    let mut iter = args.iter();
+    let value = match iter.next() {
+        Some(FunctionArg::Unnamed(FunctionArgExpr::Expr(expr))) => expr_liternal(expr)?,
+        _ => {
+            return Err(CoreError::InvalidParam(format!(
+                "multi_match value is empty:{:?}",
+                f
+            )))
+        }
+    };
+
+    let mut fields = Vec::new();
+    let mut match_type = "best_fields".to_string();
+    let mut operator = "or".to_string();
+    let mut tie_breaker = None;
+    let mut boost = 1.0;
+    for arg in iter {
+        match arg {
+            FunctionArg::Unnamed(FunctionArgExpr::Expr(Expr::BinaryOp { left, right, .. })) => {
+                match expr_liternal(left)?.as_ref() {
+                    "fields" => {
+                        let names = match right.as_ref() {
+                            Expr::Array(array) => array
+                                .elem
+                                .iter()
+                                .map(expr_liternal)
+                                .collect::<CoreResult<Vec<_>>>()?,
+                            expr => expr_liternal(expr)?
+                                .split(',')
+                                .map(|s| s.trim().to_string())
+                                .filter(|s| !s.is_empty())
+                                .collect(),
+                        };
+                        for name in names {
+                            fields.push(field_with_boost(scope, &name)?);
+                        }
+                    }
+                    "type" => match_type = expr_liternal(right)?.to_lowercase(),
+                    "operator" => operator = expr_liternal(right)?,
+                    "tie_breaker" => tie_breaker = Some(expr_liternal(right)?.parse::<f32>()?),
+                    "boost" | "score" => boost = expr_liternal(right)?.parse::<f32>()?,
+                    _ => {
+                        return Err(CoreError::InvalidParam(format!(
+                            "multi_match arg is not support:{:?}",
+                            arg
+                        )));
+                    }
+                }
+            }
+            _ => {
+                return Err(CoreError::InvalidParam(format!(
+                    "multi_match arg is not support:{:?}",
+                    arg
+                )));
+            }
+        }
+    }
+
+    if fields.is_empty() {
+        fields = match scope.get_field(ALL_FIELD) {
+            Some(f) if check_text(&f).is_ok() => vec![(f, 1.0)],
+            _ => scope
+                .user_fields
+                .read()
+                .unwrap()
+                .values()
+                .filter(|f| check_text(f).is_ok())
+                .sorted_by(|a, b| a.name.cmp(&b.name))
+                .map(|f| (f.clone(), 1.0))
+                .collect(),
+        };
+    }
+    if fields.is_empty() {
+        return Err(CoreError::InvalidParam(
+            "multi_match has no text field to search".to_string(),
+        ));
+    }
+
+    let texts = |value: &str, operator: &str| {
+        fields
+            .iter()
+            .map(|(field, b)| Query::Text {
+                value: value.to_string(),
+                boost: boost * b,
+                operator: operator.to_string(),
+                field: field.clone(),
+                fuzziness: None,
+            })
+            .collect_vec()
+    };
+
+    match match_type.as_str() {
+        "best_fields" => Ok(Query::DisMax {
+            queries: texts(&value, &operator),
+            tie_breaker: tie_breaker.unwrap_or(0.0),
+        }),
+        "most_fields" => Ok(Query::DisMax {
+            queries: texts(&value, &operator),
+            tie_breaker: tie_breaker.unwrap_or(1.0),
+        }),
+        "cross_fields" => {
+            let words = value
+                .split_whitespace()
+                .map(|w| Query::DisMax {
+                    queries: texts(w, "or"),
+                    tie_breaker: tie_breaker.unwrap_or(0.0),
+                })
+                .collect_vec();
+            if words.is_empty() {
+                return Err(CoreError::InvalidParam(format!(
+                    "multi_match value is empty:{:?}",
+                    f
+                )));
+            }
+            if "and".eq_ignore_ascii_case(&operator) {
+                Ok(Query::Bool {
+                    must: words,
+                    should: vec![],
+                    must_not: vec![],
+                    filter: vec![],
+                    minimum_should_match: 0,
+                })
+            } else {
+                Ok(Query::DisMax {
+                    queries: words,
+                    tie_breaker: 1.0,
+                })
+            }
+        }
+        _ => Err(CoreError::InvalidParam(format!(
+            "multi_match type:{:?} is not support only support best_fields,most_fields,cross_fields",
+            match_type
+        ))),
+    }
+}
+
+/// text field of `name^boost`
+fn field_with_boost(scope: &Scope, name: &str) -> CoreResult<(Arc<Field>, f32)> {
+    let (name, boost) = match name.split_once('^') {
+        Some((name, boost)) => (name, boost.parse::<f32>()?),
+        None => (name, 1.0),
+    };
+    let field = scope
+        .get_field(name)
+        .ok_or_else(|| CoreError::InvalidParam(format!("field not found: {}", name)))?;
+    check_text(&field)?;
+    Ok((field, boost))
+}
+
+fn function_name(f: &sqlparser::ast::Function) -> String {
+    f.name
+        .0
+        .first()
+        .map(|n| n.value.to_lowercase())
+        .unwrap_or_default()
+}
+
+fn negate(query: Query, negated: bool) -> Query {
+    match negated {
+        true => Query::Not {
+            query: Box::new(query),
+            boost: 1.0,
+        },
+        false => query,
+    }
+}
+
+fn new_in_list(list: &[Expr], field: Arc<Field>) -> CoreResult<Query> {
+    let mut values = Vec::with_capacity(list.len());
+    for v in list.iter() {
+        let value = str_to_vec_fix_field(&expr_liternal(v)?, &field)?;
+        values.push(value);
+    }
+
+    Ok(Query::InList {
+        list: values,
+        boost: 1.0,
+        field,
+    })
+}
+
+/// `BETWEEN low AND high` includes both ends
+fn new_between(low: &Expr, high: &Expr, field: Arc<Field>) -> CoreResult<Query> {
+    let low = Some(str_to_vec_fix_field(&expr_liternal(low)?, &field)?);
+    let high = Some(str_to_vec_fix_field(&expr_liternal(high)?, &field)?);
+    Ok(Query::Between {
+        low,
+        low_eq: true,
+        high,
+        high_eq: true,
+        boost: 1.0,
+        field,
+    })
+}
+
+fn ident_field(scope: &Scope, expr: &Expr) -> CoreResult<Arc<Field>> {
+    let name = match expr {
+        Expr::Identifier(c) => c.value.clone(),
+        Expr::CompoundIdentifier(idents) => compound_name(idents),
+        _ => {
+            return Err(CoreError::InvalidParam(format!(
+                "only support field name: {:?}",
+                expr
+            )))
+        }
+    };
+    scope
+        .get_field(&name)
+        .ok_or_else(|| CoreError::InvalidParam(format!("field not found: {}", name)))
+}
+
+/// field name of `author.name`
+fn compound_name(idents: &[Ident]) -> String {
+    idents.iter().map(|i| i.value.as_str()).join(".")
+}
+
+/// prefix, wildcard or regexp query, only on string and text field
+fn new_pattern(
+    name: &str,
+    value: String,
+    case_insensitive: bool,
+    field: Arc<Field>,
+) -> CoreResult<Query> {
+    match field.r#type() {
+        field::Type::String | field::Type::Text => {}
+        _ => {
+            return Err(CoreError::InvalidParam(format!(
+                "{} only support string and text field:{:?}",
+                name, field.name
+            )))
+        }
+    }
+
+    // terms of string field are normalized, so are literals of prefix and wildcard,
+    // regexp is kept as it is, or its syntax may be broken
+    let value = match normalizer::keyword_option(&field) {
+        Some(option) if name != "regexp" => normalizer::normalize(&value, option),
+        _ => value,
+    };
+
+    let query = match name {
+        "prefix" => Query::Prefix {
+            value,
+            case_insensitive,
+            boost: 1.0,
+            field,
+        },
+        "wildcard" => Query::Wildcard {
+            value,
+            case_insensitive,
+            boost: 1.0,
+            field,
+        },
+        _ => {
+            // check it early, or it fails in every segment
+            TermPattern::regexp(&value, case_insensitive)?;
+            Query::Regexp {
+                value,
+                case_insensitive,
+                boost: 1.0,
+                field,
+            }
+        }
+    };
+    Ok(query)
+}
+
 fn check_text(f: &Field) -> CoreResult<()> {
     if field::Type::Text == f.r#type() {
         Ok(())
@@ -351,23 +1433,20 @@ struct Function {
     value: Expr,
     slop: i32,
     operator: String, // default is "or"
+    fuzziness: Option<Fuzziness>,
+    case_insensitive: bool,
 }
 
 fn parse_function(f: &sqlparser::ast::Function) -> CoreResult<Function> {
-    let sqlparser::ast::Function { name, args, .. } = f;
+    let sqlparser::ast::Function { args, .. } = f;
 
-    let name = name
-        .0
-        .first()
-        .ok_or_else(|| CoreError::InvalidParam(format!("function name is empty:{:?}", f)))?
-        .value
-        .to_lowercase();
+    let name = function_name(f);
 
     match name.as_str() {
-        "phrase" | "text" | "score" => {}
+        "phrase" | "text" | "fuzzy" | "prefix" | "wildcard" | "regexp" | "score" => {}
         _ => {
             return Err(CoreError::InvalidParam(format!(
-                "function name:{:?} is not support only support phrase,text,fn",
+                "function name:{:?} is not support only support phrase,text,fuzzy,prefix,wildcard,regexp,score",
                 name
             )));
         }
@@ -395,6 +1474,8 @@ fn parse_function(f: &sqlparser::ast::Function) -> CoreResult<Function> {
             boost: 1.0,
             slop: 0,
             operator: "or".to_string(),
+            fuzziness: None,
+            case_insensitive: false,
         };
 
         for value in iter {
@@ -413,6 +1494,36 @@ fn parse_function(f: &sqlparser::ast::Function) -> CoreResult<Function> {
                     "operator" => {
                         function.operator = expr_liternal(right)?;
                     }
+                    "fuzziness" | "distance" => {
+                        let fuzziness = function.fuzziness.get_or_insert_with(Default::default);
+                        fuzziness.distance = match expr_liternal(right)?.to_lowercase().as_str() {
+                            "auto" => None,
+                            v => match v.parse::<u32>()? {
+                                d @ 0..=2 => Some(d),
+                                d => {
+                                    return Err(CoreError::InvalidParam(format!(
+                                        "fuzzy distance must be in 0..=2, got:{}",
+                                        d
+                                    )))
+                                }
+                            },
+                        };
+                    }
+                    "case_insensitive" => {
+                        function.case_insensitive = expr_liternal(right)?.parse::<bool>()?;
+                    }
+                    "prefix_length" => {
+                        function
+                            .fuzziness
+                            .get_or_insert_with(Default::default)
+                            .prefix_length = expr_liternal(right)?.parse::<usize>()?;
+                    }
+                    "max_expansions" => {
+                        function
+                            .fuzziness
+                            .get_or_insert_with(Default::default)
+                            .max_expansions = expr_liternal(right)?.parse::<usize>()?;
+                    }
                     _ => {
                         return Err(CoreError::InvalidParam(format!(
                             "function value is not supprot:{:?}",
@@ -440,6 +1551,7 @@ fn parse_function(f: &sqlparser::ast::Function) -> CoreResult<Function> {
 fn expr_liternal(expr: &Expr) -> CoreResult<String> {
     match expr {
         Expr::Identifier(v) => Ok(v.value.to_string()),
+        Expr::CompoundIdentifier(idents) => Ok(compound_name(idents)),
         Expr::Value(v) => Ok(value_to_str(v)?.to_string()),
         _ => Err(CoreError::InvalidParam(format!("{:?}", expr))),
     }
@@ -449,6 +1561,7 @@ fn value_to_str(value: &sqlparser::ast::Value) -> CoreResult<&str> {
     match value {
         sqlparser::ast::Value::Number(v, _) => Ok(v),
         sqlparser::ast::Value::SingleQuotedString(v) => Ok(v),
+        sqlparser::ast::Value::Boolean(v) => Ok(if *v { "true" } else { "false" }),
         _ => Err(CoreError::InvalidParam(format!("{:?}", value))),
     }
 }
@@ -482,7 +1595,6 @@ mod tests {
 
         let scope = Scope {
             schema,
-            space: Default::default(),
             user_fields: RwLock::new(user_field),
         };
 
@@ -502,6 +1614,7 @@ mod tests {
                 query,
                 order_by,
                 limit,
+                ..
             } => {
                 assert_eq!(projection, vec!["id", "name"]);
                 assert!(query.is_none());
@@ -531,7 +1644,9 @@ mod tests {
                         ..
                     } => {
                         assert_eq!(field.name, "age");
-                        assert_eq!(low, Some(vec![20]));
+                        // keys of int fields are encoded for order
+                        let key = crate::util::str_to_vec_fix_field("20", &field).unwrap();
+                        assert_eq!(low, Some(key));
                         assert!(!low_eq);
                         assert!(high.is_none());
                         assert!(!high_eq);
@@ -559,8 +1674,10 @@ mod tests {
             _ => panic!("Unexpected query type"),
         }
 
-        // 测试错误情况
+        // 测试错误情况, projection is a path of records and not checked by schema
         let sql = "SELECT invalid_field FROM test";
+        assert!(sql_to_query(&scope, sql).is_ok());
+        let sql = "SELECT * FROM test WHERE invalid_field = 1";
         assert!(sql_to_query(&scope, sql).is_err());
     }
 
@@ -600,4 +1717,413 @@ mod tests {
         assert!(query.contains("operator: \"or\""));
         assert!(query.contains("boost: 1.0"));
     }
+
+    #[test]
+    fn test_sql_fuzzy() {
+        let scope = create_test_scope();
+        let sql = "SELECT * FROM test where content = fuzzy('wrold', distance=1, prefix_length=1)";
+        let query = sql_to_query(&scope, sql).unwrap();
+        let query = format!("{:?}", query);
+        assert!(query.contains("Text {"));
+        assert!(query.contains("distance: Some(1)"));
+        assert!(query.contains("prefix_length: 1"));
+        assert!(query.contains("max_expansions: 50"));
+
+        let sql = "SELECT * FROM test where content = text('hello wrold', fuzziness='auto')";
+        let query = format!("{:?}", sql_to_query(&scope, sql).unwrap());
+        assert!(query.contains("distance: None"));
+
+        let sql = "SELECT * FROM test where content = fuzzy('wrold', distance=3)";
+        assert!(sql_to_query(&scope, sql).is_err());
+    }
+
+    #[test]
+    fn test_sql_pattern() {
+        let scope = create_test_scope();
+        let query = |sql: &str| format!("{:?}", sql_to_query(&scope, sql).unwrap());
+
+        let q = query("SELECT * FROM test where name LIKE 'ab%'");
+        assert!(q.contains("Prefix { value: \"ab\", case_insensitive: false"));
+
+        let q = query("SELECT * FROM test where name ILIKE 'a_b%'");
+        assert!(q.contains("Wildcard { value: \"a_b%\", case_insensitive: true"));
+
+        let q = query("SELECT * FROM test where content REGEXP 'ab.*'");
+        assert!(q.contains("Regexp { value: \"ab.*\""));
+
+        let q = query("SELECT * FROM test where name = prefix('ab', case_insensitive=true)");
+        assert!(q.contains("Prefix { value: \"ab\", case_insensitive: true"));
+
+        // bad regex, pattern on int field
+        assert!(sql_to_query(&scope, "SELECT * FROM test where name REGEXP 'a(b'").is_err());
+        assert!(sql_to_query(&scope, "SELECT * FROM test where age LIKE '1%'").is_err());
+    }
+
+    #[test]
+    fn test_sql_not() {
+        let scope = create_test_scope();
+        let query = |sql: &str| format!("{:?}", sql_to_query(&scope, sql).unwrap());
+
+        let q = query("SELECT * FROM test where NOT (age > 20 AND name = 'a')");
+        assert!(q.contains("Not { query: Logical {"));
+
+        let q = query("SELECT * FROM test where name IS NULL");
+        assert!(q.contains("Not { query: Exists {"));
+
+        let q = query("SELECT * FROM test where content IS NOT NULL");
+        assert!(q.contains("query: Some(Exists {"));
+
+        let q = query("SELECT * FROM test where age NOT IN (1, 2)");
+        assert!(q.contains("Not { query: InList {"));
+
+        let q = query("SELECT * FROM test where age NOT BETWEEN 1 AND 3");
+        assert!(q.contains("Not { query: Between {"));
+        assert!(q.contains("high_eq: true"));
+
+        let q = query("SELECT * FROM test where name NOT LIKE 'a%'");
+        assert!(q.contains("Not { query: Prefix {"));
+    }
+
+    #[test]
+    fn test_sql_bool() {
+        let scope = create_test_scope();
+        let query = |sql: &str| sql_to_query(&scope, sql).unwrap();
+
+        match query("SELECT * FROM test where content = 'a' AND FILTER(age > 20, name = 'b')") {
+            Query::Search {
+                query: Some(query), ..
+            } => match *query {
+                Query::Bool {
+                    must,
+                    filter,
+                    minimum_should_match,
+                    ..
+                } => {
+                    assert_eq!(must.len(), 1);
+                    assert_eq!(filter.len(), 2);
+                    assert_eq!(minimum_should_match, 0);
+                }
+                q => panic!("Expected Bool query: {:?}", q),
+            },
+            _ => panic!("Unexpected query type"),
+        }
+
+        let q = format!(
+            "{:?}",
+            query("SELECT * FROM test where SHOULD(age = 1, age = 2, age = 3, minimum_should_match=2)")
+        );
+        assert!(q.contains("minimum_should_match: 2"));
+
+        assert!(sql_to_query(&scope, "SELECT * FROM test where FILTER()").is_err());
+    }
+
+    #[test]
+    fn test_sql_multi_match() {
+        let scope = create_test_scope();
+        let query = |sql: &str| format!("{:?}", sql_to_query(&scope, sql).unwrap());
+
+        let q = query("SELECT * FROM test where multi_match('a b', fields=['content^3'])");
+        assert!(q.contains("DisMax"));
+        assert!(q.contains("boost: 3.0"));
+        assert!(q.contains("tie_breaker: 0.0"));
+
+        // all text fields by default
+        let q = query("SELECT * FROM test where multi_match('a b', type='most_fields')");
+        assert!(q.contains("tie_breaker: 1.0"));
+        assert!(q.contains("name: \"content\""));
+
+        let q = query(
+            "SELECT * FROM test where multi_match('a b', fields='content', type='cross_fields', operator='and')",
+        );
+        assert!(q.contains("Bool"));
+        assert_eq!(q.matches("Text {").count(), 2);
+
+        assert!(sql_to_query(
+            &scope,
+            "SELECT * FROM test where multi_match('a', fields=['name'])"
+        )
+        .is_err());
+        assert!(sql_to_query(
+            &scope,
+            "SELECT * FROM test where multi_match('a', type='phrase')"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_sql_more_like_this() {
+        let scope = create_test_scope();
+        let query = |sql: &str| match sql_to_query(&scope, sql).unwrap() {
+            Query::Search {
+                query: Some(query), ..
+            } => *query,
+            _ => panic!("Unexpected query type"),
+        };
+
+        match query(
+            "SELECT * FROM test where more_like_this(like='r1', fields=['content'], max_query_terms=5) AND age > 1",
+        ) {
+            Query::Logical { left, .. } => match *left {
+                Query::MoreLikeThis {
+                    like,
+                    fields,
+                    max_query_terms,
+                    resolved,
+                    ..
+                } => {
+                    assert_eq!(like, "r1");
+                    assert_eq!(fields.len(), 1);
+                    assert_eq!(fields[0].name, "content");
+                    assert_eq!(max_query_terms, 5);
+                    assert!(resolved.is_none());
+                }
+                _ => panic!("Unexpected query type"),
+            },
+            _ => panic!("Unexpected query type"),
+        }
+
+        // all text fields by default
+        match query("SELECT * FROM test where more_like_this(like='rust search')") {
+            Query::MoreLikeThis { like, fields, .. } => {
+                assert_eq!(like, "rust search");
+                assert_eq!(fields.len(), 1);
+                assert_eq!(fields[0].name, "content");
+            }
+            _ => panic!("Unexpected query type"),
+        }
+
+        for sql in [
+            "SELECT * FROM test where more_like_this(fields=['content'])",
+            "SELECT * FROM test where more_like_this(like='r1', fields=['name'])",
+            "SELECT * FROM test where more_like_this(like='r1', max_terms=5)",
+        ] {
+            assert!(sql_to_query(&scope, sql).is_err(), "{}", sql);
+        }
+    }
+
+    #[test]
+    fn test_sql_nested() {
+        let scope = create_test_scope();
+        let variants = Field {
+            name: "variants".to_string(),
+            r#type: field::Type::Nested as i32,
+            option: Some(field::Option::Nested(field::NestedOption {
+                fields: vec![Field {
+                    name: "color".to_string(),
+                    r#type: field::Type::String as i32,
+                    option: None,
+                    source: String::new(),
+                    array: false,
+                    version: 0,
+                }],
+            })),
+            source: String::new(),
+            array: false,
+            version: 0,
+        };
+        scope
+            .user_fields
+            .write()
+            .unwrap()
+            .insert(variants.name.clone(), Arc::new(variants));
+
+        let query = sql_to_query(
+            &scope,
+            "SELECT * FROM test where nested(variants, color = 'red') AND age > 1",
+        )
+        .unwrap();
+        match query {
+            Query::Search {
+                query: Some(query), ..
+            } => match *query {
+                Query::Logical { left, .. } => match *left {
+                    Query::Nested { path, query, .. } => {
+                        assert_eq!(path.name, "variants");
+                        match *query {
+                            Query::Term { field, .. } => assert_eq!(field.name, "color"),
+                            _ => panic!("Unexpected query type"),
+                        }
+                    }
+                    _ => panic!("Unexpected query type"),
+                },
+                _ => panic!("Unexpected query type"),
+            },
+            _ => panic!("Unexpected query type"),
+        }
+
+        for sql in [
+            "SELECT * FROM test where nested(name, color = 'red')",
+            "SELECT * FROM test where nested(variants, age > 1)",
+            "SELECT * FROM test where nested(variants)",
+        ] {
+            assert!(sql_to_query(&scope, sql).is_err(), "{}", sql);
+        }
+    }
+
+    #[test]
+    fn test_sql_score_function() {
+        let scope = create_test_scope();
+
+        match sql_to_query(
+            &scope,
+            "SELECT * FROM test ORDER BY _score * log(1 + age) DESC, id",
+        )
+        .unwrap()
+        {
+            Query::Search {
+                order_by,
+                function: Some(function),
+                ..
+            } => {
+                assert_eq!(
+                    order_by,
+                    vec![("_score".to_string(), false), ("id".to_string(), true)]
+                );
+                let q = format!("{:?}", function);
+                assert!(q.starts_with("Binary(Score, Multiply, Math(Ln"));
+            }
+            q => panic!("Unexpected query:{:?}", q),
+        }
+
+        let ok = |sql: &str| sql_to_query(&scope, sql).is_ok();
+        assert!(ok("SELECT * FROM test ORDER BY field_value_factor(age, factor=1.2, modifier='log1p', missing=1) DESC"));
+        assert!(ok(
+            "SELECT * FROM test ORDER BY -gauss(age, origin=30, scale=5, decay=0.3)"
+        ));
+        assert!(ok(
+            "SELECT * FROM test ORDER BY exp(age, origin='2024-01-01', scale='7d', offset='1d')"
+        ));
+        assert!(ok(
+            "SELECT * FROM test ORDER BY linear(loc, origin='30.1,120.2', scale='10km') + exp(1)"
+        ));
+        assert!(ok("SELECT * FROM test ORDER BY random_score(seed=42)"));
+
+        assert!(!ok("SELECT * FROM test ORDER BY pow(age)"));
+        assert!(!ok("SELECT * FROM test ORDER BY gauss(age, origin=30)"));
+        assert!(!ok(
+            "SELECT * FROM test ORDER BY gauss(age, origin=30, scale=5, bad=1)"
+        ));
+        assert!(!ok("SELECT * FROM test ORDER BY unknown(age)"));
+        assert!(!ok("SELECT * FROM test ORDER BY age + 1, age * 2"));
+    }
+
+    #[test]
+    fn test_sql_explain() {
+        let scope = create_test_scope();
+        let explain = |sql: &str| match sql_to_query(&scope, sql).unwrap() {
+            Query::Search { explain, .. } => explain,
+            _ => panic!("Unexpected query type"),
+        };
+        assert!(explain("EXPLAIN SELECT * FROM test WHERE age > 1"));
+        assert!(!explain("SELECT * FROM test WHERE age > 1"));
+    }
+
+    #[test]
+    fn test_sql_count() {
+        let scope = create_test_scope();
+        match sql_to_query(&scope, "SELECT COUNT(*) FROM test WHERE age > 1").unwrap() {
+            Query::Count { query, cache } => {
+                assert!(query.is_some());
+                assert!(cache);
+            }
+            _ => panic!("Unexpected query type"),
+        }
+        match sql_to_query(&scope, "select sql_no_cache count(*) from test").unwrap() {
+            Query::Count { query, cache } => {
+                assert!(query.is_none());
+                assert!(!cache);
+            }
+            _ => panic!("Unexpected query type"),
+        }
+    }
+
+    #[test]
+    fn test_sql_track_total_hits() {
+        let scope = create_test_scope();
+        let track = |sql: &str| match sql_to_query(&scope, sql).unwrap() {
+            Query::Search {
+                track_total_hits,
+                projection,
+                ..
+            } => {
+                assert!(projection.is_empty());
+                track_total_hits
+            }
+            _ => panic!("Unexpected query type"),
+        };
+        assert_eq!(track("SELECT * FROM test"), None);
+        assert_eq!(
+            track("SELECT track_total_hits(100), * FROM test"),
+            Some(100)
+        );
+        assert_eq!(track("SELECT track_total_hits(true), * FROM test"), None);
+        assert_eq!(track("SELECT track_total_hits(0), * FROM test"), None);
+        assert!(sql_to_query(&scope, "SELECT track_total_hits('a'), * FROM test").is_err());
+    }
+
+    #[test]
+    fn test_sql_collapse() {
+        let scope = create_test_scope();
+        let collapse = |sql: &str| match sql_to_query(&scope, sql).unwrap() {
+            Query::Search {
+                collapse, order_by, ..
+            } => {
+                assert!(order_by.len() <= 1);
+                collapse.map(|c| (c.field.name.clone(), c.inner_hits))
+            }
+            _ => panic!("Unexpected query type"),
+        };
+        assert_eq!(collapse("SELECT * FROM test"), None);
+        assert_eq!(
+            collapse("SELECT * FROM test COLLAPSE BY name"),
+            Some(("name".to_string(), 0))
+        );
+        assert_eq!(
+            collapse("SELECT * FROM test WHERE age > 1 collapse by age INNER_HITS 3 ORDER BY age"),
+            Some(("age".to_string(), 3))
+        );
+        assert_eq!(
+            collapse("SELECT * FROM test t COLLAPSE BY name LIMIT 5"),
+            Some(("name".to_string(), 0))
+        );
+        assert!(sql_to_query(&scope, "SELECT * FROM test COLLAPSE BY content").is_err());
+        assert!(sql_to_query(&scope, "SELECT * FROM test COLLAPSE BY unknown").is_err());
+    }
+
+    #[test]
+    fn test_sql_no_cache() {
+        let scope = create_test_scope();
+        let cache = |sql: &str| match sql_to_query(&scope, sql).unwrap() {
+            Query::Search { cache, .. } => cache,
+            _ => panic!("Unexpected query type"),
+        };
+        assert!(cache("SELECT * FROM test WHERE age > 1"));
+        assert!(!cache("SELECT SQL_NO_CACHE * FROM test WHERE age > 1"));
+        assert!(!cache("EXPLAIN SELECT sql_no_cache age FROM test"));
+    }
+
+    #[test]
+    fn test_sql_highlight() {
+        let scope = create_test_scope();
+        let sql = "SELECT id, highlight(content, pre='<b>', post='</b>', fragment_size=50) FROM test where content = 'hello'";
+        match sql_to_query(&scope, sql).unwrap() {
+            Query::Search {
+                projection,
+                highlight,
+                ..
+            } => {
+                assert_eq!(projection, vec!["id"]);
+                assert_eq!(highlight.len(), 1);
+                assert_eq!(highlight[0].field.name, "content");
+                assert_eq!(highlight[0].pre_tag, "<b>");
+                assert_eq!(highlight[0].post_tag, "</b>");
+                assert_eq!(highlight[0].fragment_size, 50);
+            }
+            _ => panic!("Unexpected query type"),
+        }
+
+        // only text field can be highlighted
+        let sql = "SELECT highlight(name) FROM test";
+        assert!(sql_to_query(&scope, sql).is_err());
+    }
 }
@@ -8,6 +8,12 @@ lazy_static::lazy_static! {
 
 #[derive(Debug)]
 pub struct Statement {
+    /// EXPLAIN SELECT
+    pub explain: bool,
+    /// SELECT SQL_NO_CACHE, filters are not got from or put to the filter cache
+    pub no_cache: bool,
+    /// COLLAPSE BY field [INNER_HITS n]
+    pub collapse: Option<(Ident, u64)>,
     /// projection expressions
     pub projection: Vec<SelectItem>,
     /// FROM
@@ -34,6 +40,8 @@ pub fn parse_where(sql: &str) -> CoreResult<Option<Expr>> {
 pub fn sql_to_statement(sql: &str) -> CoreResult<Statement> {
     let mut parser = Parser::new(&*DIALECT).try_with_sql(sql)?;
 
+    let explain = parser.parse_keyword(Keyword::EXPLAIN);
+
     if !parser.parse_keyword(Keyword::SELECT) {
         return Err(crate::util::CoreError::InvalidParam(format!(
             "only support start with SELECT sql:{:?}",
@@ -41,20 +49,52 @@ pub fn sql_to_statement(sql: &str) -> CoreResult<Statement> {
         )));
     }
 
+    let no_cache = parse_word(&mut parser, "SQL_NO_CACHE");
+
     let projection = parser.parse_projection()?;
 
-    let from = if parser.parse_keyword(Keyword::FROM) {
+    let mut from = if parser.parse_keyword(Keyword::FROM) {
         parser.parse_comma_separated(Parser::parse_table_and_joins)?
     } else {
         vec![]
     };
 
-    let query = if parser.parse_keyword(Keyword::WHERE) {
+    // without WHERE, COLLAPSE is parsed as alias of the table
+    let mut collapse_by = false;
+    if let Some(TableFactor::Table { alias, .. }) = from.last_mut().map(|t| &mut t.relation) {
+        if alias
+            .as_ref()
+            .is_some_and(|a| a.name.value.eq_ignore_ascii_case("COLLAPSE"))
+            && parser.parse_keyword(Keyword::BY)
+        {
+            *alias = None;
+            collapse_by = true;
+        }
+    }
+
+    let query = if !collapse_by && parser.parse_keyword(Keyword::WHERE) {
         Some(parser.parse_expr()?)
     } else {
         None
     };
 
+    if !collapse_by && parse_word(&mut parser, "COLLAPSE") {
+        parser.expect_keyword(Keyword::BY)?;
+        collapse_by = true;
+    }
+    let collapse = if collapse_by {
+        // a dotted path like author.name
+        let name = parser.parse_object_name(false)?;
+        let field = Ident::new(super::compound_name(&name.0));
+        let inner_hits = match parse_word(&mut parser, "INNER_HITS") {
+            true => parser.parse_literal_uint()?,
+            false => 0,
+        };
+        Some((field, inner_hits))
+    } else {
+        None
+    };
+
     let order_by = if parser.parse_keywords(&[Keyword::ORDER, Keyword::BY]) {
         parser
             .parse_comma_separated(Parser::parse_order_by_expr)
@@ -78,9 +118,16 @@ pub fn sql_to_statement(sql: &str) -> CoreResult<Statement> {
             });
             limit = Some(parser.parse_expr()?);
         }
+
+        if offset.is_none() && parser.parse_keyword(Keyword::OFFSET) {
+            offset = Some(parser.parse_offset()?);
+        }
     }
 
     Ok(Statement {
+        explain,
+        no_cache,
+        collapse,
         projection,
         from,
         query,
@@ -90,6 +137,18 @@ pub fn sql_to_statement(sql: &str) -> CoreResult<Statement> {
     })
 }
 
+/// consume a word which is not a keyword of sqlparser
+fn parse_word(parser: &mut Parser, word: &str) -> bool {
+    let found = matches!(
+        parser.peek_token().token,
+        Token::Word(ref w) if w.value.eq_ignore_ascii_case(word)
+    );
+    if found {
+        parser.next_token();
+    }
+    found
+}
+
 #[test]
 fn test_statement() {
     let sql = "select id,name from a where a > 1";
@@ -1,12 +1,14 @@
 use std::{
+    collections::HashMap,
     path::PathBuf,
     sync::{atomic::AtomicU64, Arc, Mutex, RwLock},
 };
 
-use proto::core::Record;
+use proto::core::{Field, Record};
 
 use crate::{
-    index_store::{segment::SegmentReader, IndexStore, StoreInfo},
+    analyzer::Analyzer,
+    index_store::{index_fulltext::FulltextIndex, segment::SegmentReader, IndexStore, StoreInfo},
     util::{CoreError, CoreResult},
     RecordWrapper, Scope,
 };
@@ -17,6 +19,8 @@ pub struct Store {
     index_store: Arc<RwLock<IndexStore>>,
     base_path: PathBuf,
     write_lock: Mutex<()>,
+    // new versions of fields in reindex, keyed by name
+    altering: RwLock<HashMap<String, Arc<Field>>>,
 }
 
 impl Store {
@@ -26,21 +30,23 @@ impl Store {
             scope.user_fields.read().unwrap().clone(),
         )?));
 
-        let max_start = index_store
+        // ids go on from the end of segments, the first id is start of current segment
+        let max_end = index_store
             .read()
             .unwrap()
             .segment_readers()
             .iter()
-            .map(|r| r.start())
+            .map(|r| r.end())
             .max()
             .unwrap();
 
         let store = Store {
             scope,
-            increment_id: AtomicU64::new(max_start),
+            increment_id: AtomicU64::new(max_end),
             index_store,
             base_path,
             write_lock: Mutex::new(()),
+            altering: RwLock::new(HashMap::new()),
         };
         Ok(store)
     }
@@ -121,20 +127,220 @@ impl Store {
     }
 
     pub(crate) fn new_current_segment(&self) -> CoreResult<()> {
-        self.index_store
-            .write()
-            .unwrap()
-            .new_current_segment(self.scope.user_fields.read().unwrap().clone())
+        let mut index_store = self.index_store.write().unwrap();
+        index_store.new_current_segment(self.segment_fields())
+    }
+
+    /// fields indexed by new segments, an altering field is indexed by both versions
+    fn segment_fields(&self) -> HashMap<String, Arc<Field>> {
+        let mut fields = self.scope.user_fields.read().unwrap().clone();
+        for field in self.altering.read().unwrap().values() {
+            fields.insert(field.index_name().into_owned(), field.clone());
+        }
+        fields
+    }
+
+    /// register the new version of the field, records written from now on are indexed
+    /// by it in the current segment. return the field with its version
+    pub(crate) fn begin_alter(&self, mut field: Field) -> CoreResult<Arc<Field>> {
+        let old = self.scope.get_field(&field.name).ok_or_else(|| {
+            CoreError::NotExisted(format!("field:{:?} not exist in user schema", field.name))
+        })?;
+        // an alter aborted may leave its index in segments
+        let readers = self.segment_readers();
+        field.version = old.version + 1;
+        while readers.iter().any(|r| r.has_index(&field)) {
+            field.version += 1;
+        }
+
+        {
+            let mut altering = self.altering.write().unwrap();
+            if altering.contains_key(&field.name) {
+                return Err(CoreError::InvalidParam(format!(
+                    "field:{:?} is altering",
+                    field.name
+                )));
+            }
+            altering.insert(field.name.clone(), Arc::new(field.clone()));
+        }
+
+        if let Err(e) = self.new_current_segment() {
+            self.abort_alter(&field.name);
+            return Err(e);
+        }
+        Ok(Arc::new(field))
+    }
+
+    pub(crate) fn abort_alter(&self, name: &str) {
+        self.altering.write().unwrap().remove(name);
+    }
+
+    /// index the field in all freezed segments from their records. hot segments are indexed
+    /// in place, index of warm segments is written to their dirs and they are opened again
+    pub(crate) fn build_index_field(&self, field: Arc<Field>) -> CoreResult<()> {
+        // hot segments are not persisted while they are indexed, they are in memory so
+        // the lock is short. a hot segment persisted after has the index
+        let warms = {
+            let _lock = crate::job::PERSIST_LOCK.lock().unwrap();
+            self.index_store
+                .read()
+                .unwrap()
+                .index_hot_segments(&self.scope, field.clone())?
+        };
+
+        // a snapshot links warm dirs under the lock, so it never has a half written index
+        for warm in warms {
+            let _lock = crate::job::PERSIST_LOCK.lock().unwrap();
+            IndexStore::write_index_field(&self.base_path, &self.scope, field.clone(), &warm)?;
+            self.reopen_segment(warm.start(), warm.end())?;
+        }
+        Ok(())
+    }
+
+    // the persist lock must be held
+    fn reopen_segment(&self, start: u64, end: u64) -> CoreResult<()> {
+        let segment = self.index_store.read().unwrap().open_disk_segment(
+            start,
+            end,
+            self.segment_fields(),
+        )?;
+        self.index_store.write().unwrap().replace_segment(segment)
+    }
+
+    /// queries use the new version of the field from now on, persist gets the new fields
+    /// and the old one. nothing is switched if persist fails. the persist lock is held so
+    /// a snapshot has the schema of its segments
+    pub(crate) fn switch_index_field<F>(
+        &self,
+        field: Arc<Field>,
+        persist: F,
+    ) -> CoreResult<Arc<Field>>
+    where
+        F: FnOnce(&HashMap<String, Arc<Field>>, Arc<Field>) -> CoreResult<()>,
+    {
+        let _lock = crate::job::PERSIST_LOCK.lock().unwrap();
+        let _index_store = self.index_store.read().unwrap();
+        let mut user_fields = self.scope.user_fields.write().unwrap();
+
+        let old = user_fields.get(&field.name).cloned().ok_or_else(|| {
+            CoreError::NotExisted(format!("field:{:?} not exist in user schema", field.name))
+        })?;
+
+        let mut fields = user_fields.clone();
+        fields.insert(field.name.clone(), field.clone());
+        persist(&fields, old.clone())?;
+
+        *user_fields = fields;
+        self.altering.write().unwrap().remove(&field.name);
+        Ok(old)
+    }
+
+    /// remove the index of an old version of field from all segments, dirs of it in warm
+    /// segments are deleted after they are opened again without it
+    pub(crate) fn drop_index_field(&self, old: &Field) -> CoreResult<()> {
+        let name = old.index_name().into_owned();
+        let warms = self.index_store.read().unwrap().drop_index(&name);
+
+        for warm in warms {
+            let _lock = crate::job::PERSIST_LOCK.lock().unwrap();
+            self.reopen_segment(warm.start(), warm.end())?;
+            IndexStore::remove_index_dir(&self.base_path, &warm, &name)?;
+        }
+        Ok(())
+    }
+
+    /// add new fields to scope and index them in the current segment, older segments
+    /// have no value of them. the lock of index store is held, so a new current segment
+    /// is made with them. a field added by another writer must have the same type
+    pub(crate) fn add_index_fields<F>(&self, fields: Vec<Field>, persist: F) -> CoreResult<()>
+    where
+        F: FnOnce(&HashMap<String, Arc<Field>>) -> CoreResult<()>,
+    {
+        let index_store = self.index_store.read().unwrap();
+        let mut user_fields = self.scope.user_fields.write().unwrap();
+
+        // all fields are checked before any is added
+        let mut new_fields: Vec<Arc<Field>> = Vec::new();
+        for field in fields {
+            let exist = user_fields
+                .get(&field.name)
+                .or_else(|| new_fields.iter().find(|f| f.name == field.name));
+            match exist {
+                Some(f) if f.r#type() == field.r#type() => continue,
+                Some(f) => {
+                    return Err(CoreError::InvalidParam(format!(
+                        "field:{} type:{} conflicts with type:{}",
+                        field.name,
+                        f.r#type().as_str_name(),
+                        field.r#type().as_str_name()
+                    )))
+                }
+                None => new_fields.push(Arc::new(field)),
+            }
+        }
+
+        if new_fields.is_empty() {
+            return Ok(());
+        }
+
+        // fields indexed before a failure are kept in schema with their indexes
+        let mut result = Ok(());
+        let mut added = false;
+        for field in new_fields {
+            if let Err(e) = index_store.add_index_field(field.clone()) {
+                result = Err(e);
+                break;
+            }
+            user_fields.insert(field.name.clone(), field);
+            added = true;
+        }
+
+        if added {
+            persist(&user_fields)?;
+        }
+        result
     }
 
     pub(crate) fn hot_to_warm(&self, start: u64, end: u64) -> CoreResult<()> {
         let segment = self.index_store.read().unwrap().open_disk_segment(
             start,
             end,
-            self.scope.user_fields.read().unwrap().clone(),
+            self.segment_fields(),
         )?;
 
-        self.index_store.write().unwrap().hot_to_warm(segment)
+        self.index_store.write().unwrap().replace_segment(segment)
+    }
+
+    pub(crate) fn analyzers(&self) -> HashMap<String, Arc<Analyzer>> {
+        self.index_store.read().unwrap().analyzers()
+    }
+
+    /// load the dicts of fields again and put new analyzers to all segments,
+    /// return the fields whose index digest changed
+    pub(crate) fn reload_analyzers(&self, fields: Vec<Arc<Field>>) -> CoreResult<Vec<String>> {
+        let current = self.analyzers();
+        let mut changed = Vec::new();
+        let mut analyzers = HashMap::new();
+        for f in fields {
+            let analyzer = FulltextIndex::make_analyzer(&f)?;
+            let index_name = f.index_name().into_owned();
+            if current
+                .get(&index_name)
+                .is_none_or(|c| c.index_digest() != analyzer.index_digest())
+            {
+                changed.push(f.name.clone());
+            }
+            analyzers.insert(index_name, analyzer);
+        }
+        changed.sort();
+
+        let fields = self.segment_fields();
+        self.index_store
+            .write()
+            .unwrap()
+            .set_analyzers(analyzers, fields)?;
+
+        Ok(changed)
     }
 
     pub(crate) fn segment_readers(&self) -> Vec<SegmentReader> {
@@ -148,3 +354,149 @@ impl Store {
             .info(self.scope.schema.name.clone())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, sync::Arc};
+
+    use proto::core::{field::Type as FieldType, Field};
+
+    use crate::{test_util::test_engine, Engine};
+
+    #[test]
+    fn test_segment_ids() {
+        let schema =
+            crate::easy_schema("test", vec![("name".to_string(), FieldType::String, None)]);
+        let (test_dir, engine) = test_engine("ids", schema);
+        let write = |engine: &Engine, name: &str| {
+            engine
+                .mutate_json(
+                    crate::ActionType::Append,
+                    name.to_string(),
+                    br#"{"name": "a"}"#,
+                    None,
+                )
+                .unwrap();
+            engine.get(&name.to_string()).unwrap().id
+        };
+        let total = |engine: &Engine| engine.sql("SELECT * FROM test").unwrap().total_hits;
+
+        // ids start at the segment start, no slot is left without a record
+        assert_eq!(write(&engine, "r1"), 1);
+        assert_eq!(write(&engine, "r2"), 2);
+        assert_eq!(total(&engine), 2);
+
+        // an empty current segment is not freezed
+        engine.store.new_current_segment().unwrap();
+        engine.store.new_current_segment().unwrap();
+        assert_eq!(engine.segment_readers().len(), 2);
+        assert_eq!(write(&engine, "r3"), 3);
+
+        // ids go on from the end of persisted segments
+        engine.persist().unwrap();
+        drop(engine);
+        let engine = Engine::open(test_dir.path_str(), "test").unwrap();
+        assert_eq!(write(&engine, "r4"), 4);
+        assert_eq!(total(&engine), 4);
+    }
+
+    #[test]
+    fn test_upgrade_segment_ids() {
+        let schema =
+            crate::easy_schema("test", vec![("name".to_string(), FieldType::String, None)]);
+        let (test_dir, engine) = test_engine("upgrade_ids", schema);
+        let write = |engine: &Engine, name: &str| {
+            engine
+                .mutate_json(
+                    crate::ActionType::Append,
+                    name.to_string(),
+                    format!(r#"{{"name": "{}"}}"#, name).as_bytes(),
+                    None,
+                )
+                .unwrap();
+            engine.get(&name.to_string()).unwrap().id
+        };
+        let names = |engine: &Engine, sql: &str| {
+            let mut names = engine
+                .sql(sql)
+                .unwrap()
+                .hits
+                .into_iter()
+                .map(|h| h.record.unwrap().name)
+                .collect::<Vec<_>>();
+            names.sort();
+            names
+        };
+
+        // old versions went on from the start of the last segment, so the first id
+        // was one after the segment start, the segment is written to disk as 1-3
+        engine
+            .store
+            .increment_id
+            .store(1, std::sync::atomic::Ordering::SeqCst);
+        assert_eq!(write(&engine, "r1"), 2);
+        assert_eq!(write(&engine, "r2"), 3);
+        engine.persist().unwrap();
+        drop(engine);
+
+        let engine = Arc::new(Engine::open(test_dir.path_str(), "test").unwrap());
+        assert_eq!(write(&engine, "r3"), 4);
+        assert_eq!(engine.sql("SELECT * FROM test").unwrap().total_hits, 3);
+        // the empty slot at the start of the old segment is not a record
+        assert_eq!(
+            names(&engine, "SELECT * FROM test WHERE NOT name = 'r2'"),
+            vec!["r1", "r3"]
+        );
+        engine.persist().unwrap();
+        assert_eq!(engine.sql("SELECT * FROM test").unwrap().total_hits, 3);
+    }
+
+    #[test]
+    fn test_add_index_fields() {
+        let schema =
+            crate::easy_schema("test", vec![("name".to_string(), FieldType::String, None)]);
+        let (_test_dir, engine) = test_engine("add_fields", schema);
+        let field = |name: &str, t: FieldType| Field {
+            name: name.to_string(),
+            r#type: t as i32,
+            option: None,
+            source: String::new(),
+            array: false,
+            version: 0,
+        };
+        let persisted = Cell::new(0);
+        let add = |fields: Vec<Field>| {
+            engine.store.add_index_fields(fields, |_| {
+                persisted.set(persisted.get() + 1);
+                Ok(())
+            })
+        };
+        let has_field = |name: &str| engine.store.scope.get_field(name).is_some();
+
+        // a conflict after a new field adds nothing
+        assert!(add(vec![
+            field("age", FieldType::Int),
+            field("name", FieldType::Int)
+        ])
+        .is_err());
+        assert!(!has_field("age"));
+        assert!(add(vec![
+            field("age", FieldType::Int),
+            field("age", FieldType::Text)
+        ])
+        .is_err());
+        assert!(!has_field("age"));
+        assert_eq!(persisted.get(), 0);
+
+        // new fields are added and persisted once
+        add(vec![
+            field("age", FieldType::Int),
+            field("name", FieldType::String),
+        ])
+        .unwrap();
+        assert!(has_field("age"));
+        assert_eq!(persisted.get(), 1);
+        add(vec![field("age", FieldType::Int)]).unwrap();
+        assert_eq!(persisted.get(), 1);
+    }
+}
@@ -0,0 +1,97 @@
+//! helpers shared by tests of modules
+
+use std::{
+    collections::HashMap,
+    ops::Deref,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+};
+
+use proto::core::{Record, Schema};
+
+use crate::{
+    index_store::{segment::SegmentReader, segment_mem::MemSegment},
+    ActionType, Engine, RecordWrapper, Scope,
+};
+
+/// a clean directory under the temp dir for a test, removed when dropped
+pub(crate) struct TestDir(PathBuf);
+
+impl TestDir {
+    pub(crate) fn new(name: &str) -> Self {
+        let path =
+            std::env::temp_dir().join(format!("calmcore_test_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+
+    pub(crate) fn path_str(&self) -> &str {
+        self.0.to_str().unwrap()
+    }
+}
+
+impl Deref for TestDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// create an engine in a clean directory, bind the directory before the engine
+/// so that the engine is dropped first
+pub(crate) fn test_engine(name: &str, schema: Schema) -> (TestDir, Arc<Engine>) {
+    let test_dir = TestDir::new(name);
+    let engine = Arc::new(Engine::create(test_dir.path_str(), schema).unwrap());
+    (test_dir, engine)
+}
+
+pub(crate) fn append(engine: &Engine, name: &str, data: &str) {
+    engine
+        .mutate_json(ActionType::Append, name.to_string(), data.as_bytes(), None)
+        .unwrap();
+}
+
+/// scope of the schema, user fields are the fields of schema
+pub(crate) fn test_scope(schema: Schema) -> Scope {
+    let user_fields = schema
+        .fields
+        .values()
+        .map(|f| (f.name.clone(), Arc::new(f.clone())))
+        .collect::<HashMap<_, _>>();
+    Scope {
+        schema,
+        user_fields: RwLock::new(user_fields),
+    }
+}
+
+/// a memory segment of the records, no engine or directory is made
+pub(crate) fn mem_segment(scope: &Scope, records: &[(&str, &str)]) -> SegmentReader {
+    let segment = MemSegment::new(0, scope.user_fields.read().unwrap().clone()).unwrap();
+    let records = records
+        .iter()
+        .enumerate()
+        .map(|(i, (name, data))| {
+            let record = Record {
+                id: segment.start() + i as u64,
+                name: name.to_string(),
+                data: data.as_bytes().to_vec(),
+                vectors: vec![],
+                payload: vec![],
+            };
+            RecordWrapper::new(scope, record, ActionType::Append)
+        })
+        .collect::<Vec<_>>();
+    let max = segment.start() + records.len() as u64 - 1;
+    for e in segment.write_records(records, max, None) {
+        assert!(e.is_ok(), "{:?}", e);
+    }
+    SegmentReader::Hot(Box::new(segment.reader()))
+}
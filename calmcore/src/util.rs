@@ -1,10 +1,18 @@
 use std::{borrow::Cow, collections::HashMap, sync::RwLock};
 
-use proto::core::{field, value::Kind, ListValue, ObjectValue, Value};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use proto::{
+    core::{
+        field::{self, DateOption, KeywordOption},
+        value::Kind,
+        Field, ListValue, ObjectValue, Value,
+    },
+    DottedPath,
+};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-use crate::Scope;
+use crate::{analyzer::normalizer, Scope};
 
 pub type CoreResult<T> = Result<T, CoreError>;
 
@@ -140,9 +148,16 @@ pub fn value_to_json(value: Value) -> serde_json::Value {
                 })
                 .collect(),
         ),
+        Kind::BytesValue(v) => serde_json::Value::String(STANDARD.encode(v)),
     }
 }
 
+pub fn decode_base64(value: &str) -> CoreResult<Vec<u8>> {
+    STANDARD.decode(value).map_err(|e| {
+        CoreError::InvalidParam(format!("value:{:?} is not base64 err:{:?}", value, e))
+    })
+}
+
 // make json value to proto value
 
 pub fn json_data_to_value(scope: &Scope, data: &[u8]) -> CoreResult<Value> {
@@ -152,6 +167,14 @@ pub fn json_data_to_value(scope: &Scope, data: &[u8]) -> CoreResult<Value> {
     json_to_value(scope, json)
 }
 
+/// json data of a record, a record may only have payload so empty data is null
+pub fn record_json(data: &[u8]) -> CoreResult<serde_json::Value> {
+    if data.is_empty() {
+        return Ok(serde_json::Value::Null);
+    }
+    Ok(serde_json::from_slice(data)?)
+}
+
 pub fn json_value_to_string(value: &serde_json::Value) -> Cow<'_, str> {
     match value {
         serde_json::Value::Null => Cow::Borrowed(""),
@@ -186,7 +209,24 @@ pub fn json_to_value_none_schema(json: serde_json::Value) -> CoreResult<Value> {
     )
 }
 
+/// bytes and vector fields are decoded in place by their dotted paths, a value can not
+/// be decoded is kept as it is and left to the validation of the schema
 pub fn json_to_value(scope: &Scope, json: serde_json::Value) -> CoreResult<Value> {
+    let mut value = json_to_plain_value(json)?;
+    for field in scope.user_fields.read().unwrap().values() {
+        if !field.source.is_empty() {
+            continue;
+        }
+        match field.r#type() {
+            field::Type::Bytes => value.path_mut(&field.name, &mut decode_bytes_value),
+            field::Type::Vector => value.path_mut(&field.name, &mut to_vector_value),
+            _ => {}
+        }
+    }
+    Ok(value)
+}
+
+fn json_to_plain_value(json: serde_json::Value) -> CoreResult<Value> {
     let value = match json {
         serde_json::Value::Bool(b) => Value {
             kind: Some(Kind::BoolValue(b)),
@@ -212,42 +252,18 @@ pub fn json_to_value(scope: &Scope, json: serde_json::Value) -> CoreResult<Value
             kind: Some(Kind::ListValue(ListValue {
                 values: arr
                     .into_iter()
-                    .map(|v| json_to_value(scope, v))
+                    .map(json_to_plain_value)
                     .collect::<CoreResult<Vec<Value>>>()?,
             })),
         },
-        serde_json::Value::Object(o) => {
-            let mut obj = HashMap::new();
-            for (k, v) in o {
-                if let Some(f) = scope.get_field(&k) {
-                    if field::Type::Vector == f.r#type() && v.is_array() {
-                        obj.insert(
-                            k,
-                            Value {
-                                kind: Some(Kind::VectorValue(proto::core::Embedding {
-                                    e: v.as_array()
-                                        .ok_or(CoreError::InvalidParam(format!(
-                                            "field:{:?} value is not array",
-                                            v
-                                        )))?
-                                        .iter()
-                                        .map(|v| v.as_f64().unwrap() as f32)
-                                        .collect::<Vec<f32>>(),
-                                })),
-                            },
-                        );
-                    } else {
-                        obj.insert(k, json_to_value(scope, v)?);
-                    }
-                } else {
-                    obj.insert(k, json_to_value(scope, v)?);
-                }
-            }
-
-            Value {
-                kind: Some(Kind::ObjectValue(ObjectValue { fields: obj })),
-            }
-        }
+        serde_json::Value::Object(o) => Value {
+            kind: Some(Kind::ObjectValue(ObjectValue {
+                fields: o
+                    .into_iter()
+                    .map(|(k, v)| Ok((k, json_to_plain_value(v)?)))
+                    .collect::<CoreResult<HashMap<String, Value>>>()?,
+            })),
+        },
         serde_json::Value::Null => {
             return Err(CoreError::Notsupport(format!("json value:{:?}", json)))
         }
@@ -256,6 +272,38 @@ pub fn json_to_value(scope: &Scope, json: serde_json::Value) -> CoreResult<Value
     Ok(value)
 }
 
+// base64 strings are decoded to bytes, one by one in a list
+fn decode_bytes_value(value: &mut Value) {
+    match &mut value.kind {
+        Some(Kind::StringValue(v)) => {
+            if let Ok(bytes) = STANDARD.decode(v.as_bytes()) {
+                value.kind = Some(Kind::BytesValue(bytes));
+            }
+        }
+        Some(Kind::ListValue(list)) => list.values.iter_mut().for_each(decode_bytes_value),
+        _ => {}
+    }
+}
+
+// a list of numbers is made a vector
+fn to_vector_value(value: &mut Value) {
+    let Some(Kind::ListValue(list)) = &value.kind else {
+        return;
+    };
+    let e = list
+        .values
+        .iter()
+        .map(|v| match v.kind {
+            Some(Kind::IntValue(v)) => Some(v as f32),
+            Some(Kind::FloatValue(v)) => Some(v),
+            _ => None,
+        })
+        .collect::<Option<Vec<f32>>>();
+    if let Some(e) = e {
+        value.kind = Some(Kind::VectorValue(proto::core::Embedding { e }));
+    }
+}
+
 pub enum KindType<T> {
     Single(T),
     Array(Vec<T>),
@@ -267,6 +315,7 @@ pub fn kind_to_string(kind: &Kind) -> Result<KindType<String>, CoreError> {
         Kind::IntValue(v) => v.to_string(),
         Kind::FloatValue(v) => v.to_string(),
         Kind::StringValue(v) => v.clone(),
+        Kind::BytesValue(v) => STANDARD.encode(v),
         Kind::ListValue(v) => {
             let mut vec = Vec::with_capacity(v.values.len());
             for v in &v.values {
@@ -303,7 +352,7 @@ pub fn fix_kind_type(kind: &Kind, tp: &field::Type) -> CoreResult<Kind> {
         }
         (field::Type::Float, Kind::IntValue(v)) => Ok(Kind::FloatValue(*v as f32)),
         (field::Type::Float, Kind::FloatValue(v)) => Ok(Kind::FloatValue(*v)),
-        (field::Type::Float, Kind::StringValue(v)) => Ok(Kind::StringValue(v.parse()?)),
+        (field::Type::Float, Kind::StringValue(v)) => Ok(Kind::FloatValue(v.parse()?)),
         (field::Type::String, Kind::BoolValue(v)) => Ok(Kind::StringValue(v.to_string())),
         (field::Type::String, Kind::IntValue(v)) => Ok(Kind::StringValue(v.to_string())),
         (field::Type::String, Kind::FloatValue(v)) => Ok(Kind::StringValue(v.to_string())),
@@ -312,6 +361,9 @@ pub fn fix_kind_type(kind: &Kind, tp: &field::Type) -> CoreResult<Kind> {
         (field::Type::Text, Kind::IntValue(v)) => Ok(Kind::StringValue(v.to_string())),
         (field::Type::Text, Kind::FloatValue(v)) => Ok(Kind::StringValue(v.to_string())),
         (field::Type::Text, Kind::StringValue(v)) => Ok(Kind::StringValue(v.to_string())),
+        (field::Type::Date, _) => fix_date_kind(kind, None),
+        (field::Type::Bytes, Kind::BytesValue(v)) => Ok(Kind::BytesValue(v.clone())),
+        (field::Type::Bytes, Kind::StringValue(v)) => Ok(Kind::BytesValue(decode_base64(v)?)),
         (_, Kind::ListValue(v)) => {
             let mut vec = Vec::with_capacity(v.values.len());
             for v in &v.values {
@@ -353,12 +405,83 @@ pub fn kind_to_vec_fix_type(kind: &Kind, tp: &field::Type) -> CoreResult<KindTyp
     kind_to_vec(&kind)
 }
 
+/// like kind_to_vec_fix_type, values of date field are parsed by its option and
+/// values of string field are normalized by its normalizers
+pub fn kind_to_vec_fix_field(kind: &Kind, field: &Field) -> CoreResult<KindType<Vec<u8>>> {
+    match field.r#type() {
+        field::Type::Date => kind_to_vec(&fix_date_kind(kind, date_option(field))?),
+        field::Type::String => match normalizer::keyword_option(field) {
+            Some(option) => {
+                let kind = fix_kind_type(kind, &field::Type::String)?;
+                kind_to_vec(&normalize_kind(kind, option))
+            }
+            None => kind_to_vec_fix_type(kind, &field::Type::String),
+        },
+        tp => kind_to_vec_fix_type(kind, &tp),
+    }
+}
+
+fn normalize_kind(kind: Kind, option: &KeywordOption) -> Kind {
+    match kind {
+        Kind::StringValue(v) => Kind::StringValue(normalizer::normalize(&v, option)),
+        Kind::ListValue(v) => Kind::ListValue(ListValue {
+            values: v
+                .values
+                .into_iter()
+                .map(|v| Value {
+                    kind: v.kind.map(|k| normalize_kind(k, option)),
+                })
+                .collect(),
+        }),
+        kind => kind,
+    }
+}
+
+pub fn str_to_vec_fix_field(value: &str, field: &Field) -> CoreResult<Vec<u8>> {
+    match kind_to_vec_fix_field(&Kind::StringValue(value.to_string()), field)? {
+        KindType::Single(v) => Ok(v),
+        KindType::Array(_) => unreachable!(),
+    }
+}
+
+pub fn date_option(field: &Field) -> Option<&DateOption> {
+    match &field.option {
+        Some(field::Option::Date(d)) => Some(d),
+        _ => None,
+    }
+}
+
+// date is millis in IntValue
+fn fix_date_kind(kind: &Kind, option: Option<&DateOption>) -> CoreResult<Kind> {
+    match kind {
+        Kind::IntValue(v) => Ok(Kind::IntValue(*v)),
+        Kind::FloatValue(v) => Ok(Kind::IntValue(*v as i64)),
+        Kind::StringValue(v) => Ok(Kind::IntValue(parse_date_value(v, option)?)),
+        Kind::ListValue(v) => {
+            let mut vec = Vec::with_capacity(v.values.len());
+            for v in &v.values {
+                if let Some(v) = &v.kind {
+                    vec.push(Value {
+                        kind: Some(fix_date_kind(v, option)?),
+                    });
+                }
+            }
+            Ok(Kind::ListValue(ListValue { values: vec }))
+        }
+        _ => Err(CoreError::Notsupport(format!(
+            "kind:{:?} cast to date not support",
+            kind
+        ))),
+    }
+}
+
 pub fn kind_to_vec(kind: &Kind) -> CoreResult<KindType<Vec<u8>>> {
     let v = match kind {
         Kind::BoolValue(b) => Ok(vec![*b as u8]),
         Kind::IntValue(v) => memcomparable::to_vec(v),
         Kind::FloatValue(v) => memcomparable::to_vec(v),
         Kind::StringValue(v) => Ok(v.as_bytes().to_vec()),
+        Kind::BytesValue(v) => Ok(v.clone()),
         Kind::ListValue(v) => {
             let mut vec = Vec::with_capacity(v.values.len());
             for v in &v.values {
@@ -387,3 +510,470 @@ pub fn kind_to_vec(kind: &Kind) -> CoreResult<KindType<Vec<u8>>> {
         ))),
     }
 }
+
+const DAY_MILLIS: i64 = 86_400_000;
+
+/// milliseconds since unix epoch of `YYYY-MM-DD`, `YYYY-MM-DD HH:MM:SS` or rfc3339 like
+/// `YYYY-MM-DDTHH:MM:SS.fff+08:00`, no zone means utc
+pub fn parse_date_millis(value: &str) -> Option<i64> {
+    parse_date_parts(value).and_then(|(millis, offset)| millis.checked_sub(offset.unwrap_or(0)))
+}
+
+/// millis of a date value with date math like `now-7d/d` or `2024-01-01||+1M/M`.
+/// the value is tried by formats of option, then epoch millis and rfc3339, a value
+/// without zone is in time_zone of option. math is `+n`, `-n` or `/` rounding down
+/// by units y M w d h H m s
+pub fn parse_date_value(value: &str, option: Option<&DateOption>) -> CoreResult<i64> {
+    let err = || CoreError::InvalidParam(format!("invalid date value:{:?}", value));
+    let zone = parse_time_zone(option.map_or("", |o| o.time_zone.as_str()))?;
+
+    let value = value.trim();
+    let (mut millis, mut math) = match value.strip_prefix("now") {
+        Some(math) => (now_millis(), math),
+        None => {
+            let (anchor, math) = value.split_once("||").unwrap_or((value, ""));
+            (
+                parse_date_anchor(anchor, option, zone).ok_or_else(err)?,
+                math,
+            )
+        }
+    };
+
+    while let Some(op) = math.chars().next() {
+        if !matches!(op, '+' | '-' | '/') {
+            return Err(err());
+        }
+        let rest = &math[op.len_utf8()..];
+        let digits = rest.bytes().take_while(|b| b.is_ascii_digit()).count();
+        let unit = rest[digits..].chars().next().ok_or_else(err)?;
+        let n = match digits {
+            0 => 1,
+            _ if op == '/' => return Err(err()),
+            _ => rest[..digits].parse::<i64>().map_err(|_| err())?,
+        };
+        millis = match op {
+            '+' => add_date(millis, n, unit, zone),
+            '-' => add_date(millis, -n, unit, zone),
+            '/' => round_date(millis, 1, unit, zone),
+            _ => None,
+        }
+        .ok_or_else(err)?;
+        math = &rest[digits + unit.len_utf8()..];
+    }
+    Ok(millis)
+}
+
+fn parse_date_anchor(value: &str, option: Option<&DateOption>, zone: i64) -> Option<i64> {
+    if let Some(o) = option {
+        if let Some(millis) = o.formats.iter().find_map(|f| parse_date_format(value, f)) {
+            return millis.checked_sub(zone);
+        }
+    }
+    if let Ok(millis) = value.parse::<i64>() {
+        return Some(millis);
+    }
+    parse_date_parts(value).and_then(|(millis, offset)| millis.checked_sub(offset.unwrap_or(zone)))
+}
+
+/// local millis of value by format, `%Y %m %d %H %M %S` are numbers and `%f` is millis
+fn parse_date_format(value: &str, format: &str) -> Option<i64> {
+    let (v, f) = (value.as_bytes(), format.as_bytes());
+    let (mut i, mut j) = (0, 0);
+    // year month day hour minute second millis
+    let mut parts = [1970i64, 1, 1, 0, 0, 0, 0];
+    while j < f.len() {
+        let spec = match (f[j], f.get(j + 1)) {
+            (b'%', Some(b'%')) | (b'%', None) => None,
+            (b'%', Some(c)) => Some(*c),
+            _ => None,
+        };
+        match spec {
+            Some(c) => {
+                let (slot, width) = match c {
+                    b'Y' => (0, 4),
+                    b'm' => (1, 2),
+                    b'd' => (2, 2),
+                    b'H' => (3, 2),
+                    b'M' => (4, 2),
+                    b'S' => (5, 2),
+                    b'f' => (6, 3),
+                    _ => return None,
+                };
+                let n = v[i..]
+                    .iter()
+                    .take(width)
+                    .take_while(|b| b.is_ascii_digit())
+                    .count();
+                if n == 0 {
+                    return None;
+                }
+                let mut num = std::str::from_utf8(&v[i..i + n])
+                    .ok()?
+                    .parse::<i64>()
+                    .ok()?;
+                if slot == 6 {
+                    num *= 10i64.pow((width - n) as u32);
+                }
+                parts[slot] = num;
+                i += n;
+                j += 2;
+            }
+            None => {
+                if v.get(i) != Some(&f[j]) {
+                    return None;
+                }
+                i += 1;
+                j += if f[j] == b'%' && j + 1 < f.len() {
+                    2
+                } else {
+                    1
+                };
+            }
+        }
+    }
+
+    let [year, month, day, hour, minute, second, millis] = parts;
+    if i != v.len()
+        || !(1..=12).contains(&month)
+        || !(1..=31).contains(&day)
+        || hour > 23
+        || minute > 59
+        || second > 60
+    {
+        return None;
+    }
+    date_millis(year, month as u32, day as u32)?
+        .checked_add(((hour * 60 + minute) * 60 + second) * 1000 + millis)
+}
+
+/// offset millis of a zone like `+08:00`, `-0530`, `Z` or `UTC`, empty is utc
+pub fn parse_time_zone(zone: &str) -> CoreResult<i64> {
+    let zone = zone.trim();
+    if zone.is_empty() || zone.eq_ignore_ascii_case("z") || zone.eq_ignore_ascii_case("utc") {
+        return Ok(0);
+    }
+    let err = || CoreError::InvalidParam(format!("invalid time zone:{:?}, like +08:00", zone));
+    let sign = match zone.as_bytes()[0] {
+        b'+' => 1,
+        b'-' => -1,
+        _ => return Err(err()),
+    };
+    let offset = &zone[1..];
+    let (h, m) = match offset.split_once(':') {
+        Some(hm) => hm,
+        None if offset.len() == 4 => match (offset.get(..2), offset.get(2..)) {
+            (Some(h), Some(m)) => (h, m),
+            _ => return Err(err()),
+        },
+        None => (offset, "0"),
+    };
+    match (h.parse::<i64>(), m.parse::<i64>()) {
+        (Ok(h), Ok(m)) if (0..=18).contains(&h) && (0..60).contains(&m) => {
+            Ok(sign * (h * 60 + m) * 60_000)
+        }
+        _ => Err(err()),
+    }
+}
+
+/// interval like `1d` or `3h` to number and unit
+pub fn parse_interval(interval: &str) -> CoreResult<(i64, char)> {
+    let err = || CoreError::InvalidParam(format!("invalid interval:{:?}, like 1d", interval));
+    let interval = interval.trim();
+    let unit = interval.chars().last().ok_or_else(err)?;
+    let n = match &interval[..interval.len() - unit.len_utf8()] {
+        "" => 1,
+        n => n.parse::<i64>().map_err(|_| err())?,
+    };
+    if n <= 0 || round_date(0, n, unit, 0).is_none() {
+        return Err(err());
+    }
+    Ok((n, unit))
+}
+
+fn unit_millis(unit: char) -> Option<i64> {
+    match unit {
+        'w' => Some(7 * DAY_MILLIS),
+        'd' => Some(DAY_MILLIS),
+        'h' | 'H' => Some(3_600_000),
+        'm' => Some(60_000),
+        's' => Some(1000),
+        _ => None,
+    }
+}
+
+// months and years are added by calendar in the zone, day is clamped to the month.
+// none if the date is out of range of i64 millis
+fn add_date(millis: i64, n: i64, unit: char, zone: i64) -> Option<i64> {
+    let months = match unit {
+        'y' => n.checked_mul(12)?,
+        'M' => n,
+        _ => return millis.checked_add(n.checked_mul(unit_millis(unit)?)?),
+    };
+    let local = millis.checked_add(zone)?;
+    let (year, month, day) = civil_from_days(local.div_euclid(DAY_MILLIS));
+    let total = (year * 12 + month as i64 - 1).checked_add(months)?;
+    let (year, month) = (total.div_euclid(12), total.rem_euclid(12) as u32 + 1);
+    let day = day.min(days_in_month(year, month)?);
+    date_millis(year, month, day)?
+        .checked_add(local.rem_euclid(DAY_MILLIS))?
+        .checked_sub(zone)
+}
+
+/// start of the interval of n units which the millis is in, in the zone.
+/// none if the start is out of range of i64 millis
+pub fn round_date(millis: i64, n: i64, unit: char, zone: i64) -> Option<i64> {
+    let local = millis.checked_add(zone)?;
+    let start = match unit {
+        'y' | 'M' => {
+            let (year, month, _) = civil_from_days(local.div_euclid(DAY_MILLIS));
+            let size = if unit == 'y' { n.checked_mul(12)? } else { n };
+            let total = (year * 12 + month as i64 - 1)
+                .div_euclid(size)
+                .checked_mul(size)?;
+            date_millis(total.div_euclid(12), total.rem_euclid(12) as u32 + 1, 1)?
+        }
+        // weeks start on monday, 1970-01-01 is thursday
+        'w' => {
+            let size = n.checked_mul(7 * DAY_MILLIS)?;
+            local
+                .checked_add(3 * DAY_MILLIS)?
+                .div_euclid(size)
+                .checked_mul(size)?
+                .checked_sub(3 * DAY_MILLIS)?
+        }
+        unit => {
+            let size = n.checked_mul(unit_millis(unit)?)?;
+            local.div_euclid(size).checked_mul(size)?
+        }
+    };
+    start.checked_sub(zone)
+}
+
+/// rfc3339 of millis in the zone, like `2024-01-01T00:00:00.000+08:00`
+pub fn format_date_millis(millis: i64, zone: i64) -> String {
+    let local = millis + zone;
+    let (year, month, day) = civil_from_days(local.div_euclid(DAY_MILLIS));
+    let time = local.rem_euclid(DAY_MILLIS);
+    let zone = match zone {
+        0 => "Z".to_string(),
+        z => format!(
+            "{}{:02}:{:02}",
+            if z < 0 { '-' } else { '+' },
+            z.abs() / 3_600_000,
+            z.abs() / 60_000 % 60
+        ),
+    };
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}{}",
+        year,
+        month,
+        day,
+        time / 3_600_000,
+        time / 60_000 % 60,
+        time / 1000 % 60,
+        time % 1000,
+        zone
+    )
+}
+
+// local millis and offset of zone if the value has one
+fn parse_date_parts(value: &str) -> Option<(i64, Option<i64>)> {
+    let value = value.trim();
+    let (date, time) = match value.find(['T', 't', ' ']) {
+        Some(i) => (&value[..i], Some(&value[i + 1..])),
+        None => (value, None),
+    };
+
+    let mut parts = date.splitn(3, '-');
+    let year = parts.next()?.parse::<i64>().ok()?;
+    let month = parts.next()?.parse::<u32>().ok()?;
+    let day = parts.next()?.parse::<u32>().ok()?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    let millis = date_millis(year, month, day)?;
+
+    let time = match time {
+        Some(t) => t,
+        None => return Some((millis, None)),
+    };
+
+    // split zone from time
+    let (time, offset) = if let Some(t) = time.strip_suffix(['Z', 'z']) {
+        (t, Some(0))
+    } else if let Some(i) = time.rfind(['+', '-']) {
+        let (h, m) = time[i + 1..]
+            .split_once(':')
+            .unwrap_or((&time[i + 1..], "0"));
+        let offset = (h.parse::<i64>().ok()?.checked_mul(60)?)
+            .checked_add(m.parse::<i64>().ok()?)?
+            .checked_mul(60_000)?;
+        (
+            &time[..i],
+            Some(if &time[i..i + 1] == "-" {
+                -offset
+            } else {
+                offset
+            }),
+        )
+    } else {
+        (time, None)
+    };
+
+    let (time, frac) = time.split_once('.').unwrap_or((time, ""));
+    let mut parts = time.splitn(3, ':');
+    let hour = parts.next()?.parse::<i64>().ok()?;
+    let minute = parts.next().map_or(Some(0), |m| m.parse::<i64>().ok())?;
+    let second = parts.next().map_or(Some(0), |s| s.parse::<i64>().ok())?;
+    if hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+    let frac = match frac {
+        "" => 0,
+        f if f.bytes().all(|b| b.is_ascii_digit()) => format!("{:0<3}", &f[..f.len().min(3)])
+            .parse::<i64>()
+            .ok()?,
+        _ => return None,
+    };
+
+    let millis = millis.checked_add(((hour * 60 + minute) * 60 + second) * 1000 + frac)?;
+    Some((millis, offset))
+}
+
+pub fn now_millis() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as i64)
+}
+
+// days since 1970-01-01 of a proleptic gregorian date, none if it is out of range
+fn days_from_civil(year: i64, month: u32, day: u32) -> Option<i64> {
+    let year = if month <= 2 {
+        year.checked_sub(1)?
+    } else {
+        year
+    };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let mp = (month as i64 + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era.checked_mul(146_097)?.checked_add(doe - 719_468)
+}
+
+// millis since unix epoch of the start of a date, none if it is out of range
+fn date_millis(year: i64, month: u32, day: u32) -> Option<i64> {
+    days_from_civil(year, month, day)?.checked_mul(DAY_MILLIS)
+}
+
+// proleptic gregorian date of days since 1970-01-01
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    (yoe + era * 400 + (month <= 2) as i64, month, day)
+}
+
+fn days_in_month(year: i64, month: u32) -> Option<u32> {
+    let (next_year, next_month) = if month == 12 {
+        (year.checked_add(1)?, 1)
+    } else {
+        (year, month + 1)
+    };
+    Some((days_from_civil(next_year, next_month, 1)? - days_from_civil(year, month, 1)?) as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_date_value() {
+        let utc = |v: &str| parse_date_value(v, None).unwrap();
+        assert_eq!(utc("2024-01-01"), 1_704_067_200_000);
+        assert_eq!(utc("1704067200000"), 1_704_067_200_000);
+        assert_eq!(utc("2024-01-01T08:00:00+08:00"), 1_704_067_200_000);
+        assert_eq!(utc("2024-01-31||+1M"), utc("2024-02-29"));
+        assert_eq!(utc("2024-03-15T10:20:00Z||-1y/M"), utc("2023-03-01"));
+        assert_eq!(utc("2024-01-03T10:00:00Z||/w"), utc("2024-01-01"));
+        assert_eq!(utc("2024-01-01||+36h/d"), utc("2024-01-02"));
+        assert!(parse_date_value("2024-01-01||+1x", None).is_err());
+        assert!(parse_date_value("yesterday", None).is_err());
+        assert!(parse_date_value("now€", None).is_err());
+        assert!(parse_date_value("now*1d", None).is_err());
+
+        let now = now_millis();
+        let day = utc("now/d");
+        assert!(day <= now && now - day < DAY_MILLIS);
+        assert_eq!(utc("now-7d/d"), day - 7 * DAY_MILLIS);
+
+        // formats and zone of option
+        let option = DateOption {
+            formats: vec!["%Y/%m/%d %H:%M".to_string()],
+            time_zone: "+08:00".to_string(),
+        };
+        let zoned = |v: &str| parse_date_value(v, Some(&option)).unwrap();
+        assert_eq!(zoned("2024/01/01 08:00"), 1_704_067_200_000);
+        assert_eq!(zoned("2024-01-01T08:00:00"), 1_704_067_200_000);
+        assert_eq!(zoned("2024-01-01T00:00:00Z"), 1_704_067_200_000);
+        assert_eq!(
+            zoned("2024-01-01T09:30:00||/d"),
+            utc("2023-12-31T16:00:00Z")
+        );
+    }
+
+    #[test]
+    fn test_date_overflow() {
+        let invalid = |v: &str| {
+            assert!(
+                matches!(parse_date_value(v, None), Err(CoreError::InvalidParam(_))),
+                "{}",
+                v
+            )
+        };
+        invalid("now+999999999999d");
+        invalid("now-999999999999w");
+        invalid("now+999999999999999999y");
+        invalid("now+99999999999999999999s");
+        invalid("9223372036854775807||+1s");
+        invalid("-9223372036854775808||/w");
+        invalid("9999999999999999-01-01");
+        invalid("2024-01-01T00:00:00+99999999999999999:00");
+        assert!(parse_date_value("now+9999d", None).is_ok());
+        assert_eq!(parse_date_millis("99999999999999999-01-01"), None);
+    }
+
+    #[test]
+    fn test_date_interval() {
+        assert_eq!(parse_interval("1d").unwrap(), (1, 'd'));
+        assert_eq!(parse_interval("12h").unwrap(), (12, 'h'));
+        assert_eq!(parse_interval("M").unwrap(), (1, 'M'));
+        assert!(parse_interval("0d").is_err());
+        assert!(parse_interval("1q").is_err());
+        assert!(parse_interval("999999999999999999w").is_err());
+        assert!(parse_interval("999999999999999999y").is_err());
+
+        assert_eq!(parse_time_zone("-0530").unwrap(), -330 * 60_000);
+        assert!(parse_time_zone("Asia/Shanghai").is_err());
+        assert!(parse_time_zone("+€1").is_err());
+        assert!(parse_time_zone("+-5:00").is_err());
+
+        let millis = parse_date_millis("2024-05-17T15:30:00Z").unwrap();
+        let round =
+            |n, unit, zone| format_date_millis(round_date(millis, n, unit, zone).unwrap(), zone);
+        assert_eq!(round(1, 'd', 0), "2024-05-17T00:00:00.000Z");
+        assert_eq!(
+            round(1, 'd', 9 * 3_600_000),
+            "2024-05-18T00:00:00.000+09:00"
+        );
+        assert_eq!(round(6, 'h', 0), "2024-05-17T12:00:00.000Z");
+        assert_eq!(round(1, 'w', 0), "2024-05-13T00:00:00.000Z");
+        assert_eq!(round(3, 'M', 0), "2024-04-01T00:00:00.000Z");
+        assert_eq!(round(1, 'y', -3_600_000), "2024-01-01T00:00:00.000-01:00");
+    }
+}
@@ -0,0 +1,271 @@
+//! validation of records by the schema, in coerce or strict mode a record with
+//! a value not matched to the type of its field is an error and not written
+
+use proto::{
+    core::{field, schema::Validation, value::Kind, Field, Value, Vector},
+    DottedPath,
+};
+
+use crate::{
+    index_store::index_nested::nested_fields,
+    util::{kind_to_string, kind_to_vec_fix_field, CoreError, CoreResult},
+    Scope,
+};
+
+/// values of user fields in the record, `_` fields are filled by engine
+pub(crate) fn validate_value(scope: &Scope, value: &Value) -> CoreResult<()> {
+    let validation = scope.schema.validation();
+    if validation == Validation::Ignore {
+        return Ok(());
+    }
+
+    let user_fields = scope.user_fields.read().unwrap();
+    for field in user_fields.values().filter(|f| !f.name.starts_with('_')) {
+        if let Some(v) = value.path(field.source_path()) {
+            check(validation, field, &v)?;
+        }
+    }
+    Ok(())
+}
+
+/// vectors of the record must be of vector fields and their dimension
+pub(crate) fn validate_vectors(scope: &Scope, vectors: &[Vector]) -> CoreResult<()> {
+    if scope.schema.validation() == Validation::Ignore {
+        return Ok(());
+    }
+
+    for vector in vectors {
+        match scope.get_field(&vector.field_name) {
+            Some(field) if field.r#type() == field::Type::Vector => {
+                check_dimension(&field, vector.vector.len())?
+            }
+            _ => {
+                return Err(CoreError::InvalidParam(format!(
+                    "vector of field:{:?} is not in schema",
+                    vector.field_name
+                )))
+            }
+        }
+    }
+    Ok(())
+}
+
+fn check(validation: Validation, field: &Field, value: &Value) -> CoreResult<()> {
+    let Some(kind) = value.kind.as_ref() else {
+        return Ok(());
+    };
+
+    // values of an array field are a list, and a list in it is not flattened
+    if field.array {
+        let Kind::ListValue(list) = kind else {
+            return Err(mismatch(field, kind));
+        };
+        let mut kinds = list.values.iter().filter_map(|v| v.kind.as_ref());
+        if let Some(kind) = kinds.find(|k| matches!(k, Kind::ListValue(_))) {
+            return Err(mismatch(field, kind));
+        }
+    }
+    check_value(validation, field, value)
+}
+
+fn check_value(validation: Validation, field: &Field, value: &Value) -> CoreResult<()> {
+    let Some(kind) = value.kind.as_ref() else {
+        return Ok(());
+    };
+
+    use field::Type::*;
+    let matched = match (field.r#type(), kind) {
+        (Geo, _) => true,
+        (Vector, Kind::VectorValue(v)) => return check_dimension(field, v.e.len()),
+        // a list of numbers is made a vector when the record is read
+        (Vector, Kind::ListValue(_)) => false,
+        // other values are embedded by the embedding of the field
+        (Vector, _) => true,
+        (Nested, Kind::ObjectValue(_)) => return check_nested(validation, field, value),
+        (Nested, Kind::ListValue(list)) => {
+            for v in list.values.iter() {
+                match &v.kind {
+                    Some(Kind::ObjectValue(_)) => check_nested(validation, field, v)?,
+                    Some(kind) => return Err(mismatch(field, kind)),
+                    None => {}
+                }
+            }
+            return Ok(());
+        }
+        (Nested, _) | (_, Kind::ObjectValue(_) | Kind::VectorValue(_)) => false,
+        (_, Kind::ListValue(list)) => {
+            for v in list.values.iter() {
+                check_value(validation, field, v)?;
+            }
+            return Ok(());
+        }
+        (tp, kind) if validation == Validation::Strict && !strict_type(tp, kind) => false,
+        (Text, kind) => kind_to_string(kind).is_ok(),
+        // dates are parsed by the formats and strings are normalized
+        (_, kind) => kind_to_vec_fix_field(kind, field).is_ok(),
+    };
+
+    match matched {
+        true => Ok(()),
+        false => Err(mismatch(field, kind)),
+    }
+}
+
+// the value is of the type without cast
+fn strict_type(tp: field::Type, kind: &Kind) -> bool {
+    use field::Type::*;
+    matches!(
+        (tp, kind),
+        (Bool, Kind::BoolValue(_))
+            | (Int, Kind::IntValue(_))
+            | (Float, Kind::IntValue(_) | Kind::FloatValue(_))
+            | (String | Text, Kind::StringValue(_))
+            | (Date, Kind::StringValue(_) | Kind::IntValue(_))
+            | (Bytes, Kind::BytesValue(_))
+    )
+}
+
+// values of an object are checked by the fields of nested option
+fn check_nested(validation: Validation, field: &Field, obj: &Value) -> CoreResult<()> {
+    for sub in nested_fields(field)? {
+        if let Some(v) = obj.path(sub.source_path()) {
+            check(validation, &sub, &v).map_err(|e| {
+                CoreError::InvalidParam(format!("nested field:{:?} {}", field.name, e))
+            })?;
+        }
+    }
+    Ok(())
+}
+
+fn check_dimension(field: &Field, len: usize) -> CoreResult<()> {
+    match &field.option {
+        Some(field::Option::Embedding(e)) if e.dimension > 0 && e.dimension as usize != len => {
+            Err(CoreError::InvalidParam(format!(
+                "field:{:?} vector of dimension:{} is not {}",
+                field.name, len, e.dimension
+            )))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn mismatch(field: &Field, kind: &Kind) -> CoreError {
+    CoreError::InvalidParam(format!(
+        "field:{:?} type:{} not match value:{:?}",
+        field.name,
+        field.r#type().as_str_name(),
+        kind
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::Arc};
+
+    use proto::core::{field::Type, Record};
+
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        let mut schema = crate::easy_schema(
+            "test",
+            vec![
+                ("age".to_string(), Type::Int, None),
+                ("price".to_string(), Type::Float, None),
+                ("tags".to_string(), Type::String, None),
+                ("hash".to_string(), Type::Bytes, None),
+                (
+                    "embedding".to_string(),
+                    Type::Vector,
+                    Some(field::Option::Embedding(field::EmbeddingOption {
+                        dimension: 3,
+                        ..Default::default()
+                    })),
+                ),
+                (
+                    "variants".to_string(),
+                    Type::Nested,
+                    Some(field::Option::Nested(field::NestedOption {
+                        fields: vec![Field {
+                            name: "size".to_string(),
+                            r#type: Type::Int as i32,
+                            ..Default::default()
+                        }],
+                    })),
+                ),
+            ],
+        );
+        schema.fields.get_mut("tags").unwrap().array = true;
+        let write = |schema: &proto::core::Schema, data: &str, vectors: Vec<Vector>| {
+            let scope = Scope {
+                user_fields: std::sync::RwLock::new(
+                    schema
+                        .fields
+                        .iter()
+                        .map(|(k, v)| (k.clone(), Arc::new(v.clone())))
+                        .collect::<HashMap<_, _>>(),
+                ),
+                schema: schema.clone(),
+            };
+            let record = Record {
+                name: "r".to_string(),
+                data: data.as_bytes().to_vec(),
+                vectors,
+                ..Default::default()
+            };
+            crate::RecordWrapper::new(&scope, record, crate::ActionType::Append)
+                .result
+                .is_ok()
+        };
+        let vector = |n: usize| Vector {
+            field_name: "embedding".to_string(),
+            vector: vec![0.5; n],
+        };
+
+        // ignore writes all of them
+        assert!(write(&schema, r#"{"age": "old"}"#, vec![]));
+        assert!(write(&schema, r#"{"age": 1}"#, vec![vector(2)]));
+        assert!(write(
+            &schema,
+            r#"{"tags": "a", "hash": "not base64!"}"#,
+            vec![]
+        ));
+        assert!(write(&schema, r#"{"embedding": [1, "a"]}"#, vec![]));
+
+        schema.set_validation(Validation::Coerce);
+        assert!(write(&schema, r#"{"age": "1", "price": 2}"#, vec![]));
+        assert!(!write(&schema, r#"{"age": "old"}"#, vec![]));
+        assert!(!write(&schema, r#"{"age": [1, "old"]}"#, vec![]));
+        assert!(!write(&schema, r#"{"age": {"n": 1}}"#, vec![]));
+        assert!(write(
+            &schema,
+            r#"{"embedding": [1, 2, 3]}"#,
+            vec![vector(3)]
+        ));
+        assert!(!write(&schema, r#"{"embedding": [1, 2]}"#, vec![]));
+        assert!(!write(&schema, r#"{"embedding": [1, "a", 3]}"#, vec![]));
+        assert!(write(&schema, r#"{"tags": ["a"], "hash": "YWJj"}"#, vec![]));
+        assert!(!write(&schema, r#"{"tags": "a"}"#, vec![]));
+        assert!(!write(&schema, r#"{"tags": [["a"]]}"#, vec![]));
+        assert!(!write(&schema, r#"{"hash": "not base64!"}"#, vec![]));
+        assert!(!write(&schema, r#"{}"#, vec![vector(4)]));
+
+        schema.set_validation(Validation::Strict);
+        assert!(write(&schema, r#"{"age": 1, "price": 2}"#, vec![]));
+        assert!(!write(&schema, r#"{"age": "1"}"#, vec![]));
+        assert!(!write(&schema, r#"{"age": 1.5}"#, vec![]));
+        assert!(!write(&schema, r#"{"price": true}"#, vec![]));
+        assert!(write(
+            &schema,
+            r#"{"variants": [{"size": 1}, {"size": 2}]}"#,
+            vec![]
+        ));
+        assert!(!write(
+            &schema,
+            r#"{"variants": [{"size": 1}, {"size": "L"}]}"#,
+            vec![]
+        ));
+        assert!(!write(&schema, r#"{"variants": [1]}"#, vec![]));
+    }
+}
@@ -67,6 +67,9 @@ fn make_schema(schema_name: &str) -> Schema {
             name,
             r#type: proto::core::field::Type::String as i32,
             option: None,
+            source: String::new(),
+            array: false,
+            version: 0,
         },
     );
 
@@ -77,6 +80,9 @@ fn make_schema(schema_name: &str) -> Schema {
             name,
             r#type: proto::core::field::Type::Int as i32,
             option: None,
+            source: String::new(),
+            array: false,
+            version: 0,
         },
     );
 
@@ -92,6 +98,9 @@ fn make_schema(schema_name: &str) -> Schema {
                 metric: Metric::Euclidean as i32,
                 batch_size: 1000,
             })),
+            source: String::new(),
+            array: false,
+            version: 0,
         },
     );
 
@@ -106,7 +115,11 @@ fn make_schema(schema_name: &str) -> Schema {
                 filters: Vec::new(),
                 stopwords: None,
                 synonyms: None,
+                synonym_expand: fulltext_option::SynonymExpand::Query as i32,
             })),
+            source: String::new(),
+            array: false,
+            version: 0,
         },
     );
 
@@ -116,5 +129,7 @@ fn make_schema(schema_name: &str) -> Schema {
         fields,
         metadata: None,
         schemaless: false,
+        dynamic: None,
+        validation: 0,
     }
 }
@@ -42,6 +42,9 @@ fn make_schema(schema_name: &str) -> Schema {
             name,
             r#type: proto::core::field::Type::String as i32,
             option: None,
+            source: String::new(),
+            array: false,
+            version: 0,
         },
     );
 
@@ -52,6 +55,9 @@ fn make_schema(schema_name: &str) -> Schema {
             name,
             r#type: proto::core::field::Type::Int as i32,
             option: None,
+            source: String::new(),
+            array: false,
+            version: 0,
         },
     );
 
@@ -67,6 +73,9 @@ fn make_schema(schema_name: &str) -> Schema {
                 metric: Metric::Euclidean as i32,
                 batch_size: 1000,
             })),
+            source: String::new(),
+            array: false,
+            version: 0,
         },
     );
 
@@ -76,5 +85,7 @@ fn make_schema(schema_name: &str) -> Schema {
         fields,
         metadata: None,
         schemaless: false,
+        dynamic: None,
+        validation: 0,
     }
 }
@@ -86,6 +86,9 @@ fn make_schema(schema_name: &str) -> Schema {
             name,
             r#type: proto::core::field::Type::String as i32,
             option: None,
+            source: String::new(),
+            array: false,
+            version: 0,
         },
     );
 
@@ -96,6 +99,9 @@ fn make_schema(schema_name: &str) -> Schema {
             name,
             r#type: proto::core::field::Type::Text as i32,
             option: None,
+            source: String::new(),
+            array: false,
+            version: 0,
         },
     );
 
@@ -106,6 +112,9 @@ fn make_schema(schema_name: &str) -> Schema {
             name,
             r#type: proto::core::field::Type::Int as i32,
             option: None,
+            source: String::new(),
+            array: false,
+            version: 0,
         },
     );
 
@@ -114,5 +123,7 @@ fn make_schema(schema_name: &str) -> Schema {
         fields,
         metadata: None,
         schemaless: false,
+        dynamic: None,
+        validation: 0,
     }
 }
@@ -277,6 +277,10 @@ impl<K, V> TreeReader<K, V> {
 
     /// Find offset of a key in the node file using binary search
     fn find_key_offset(&self, key: &K) -> Option<i64> {
+        // the root of an empty tree has no items
+        if self.is_empty() {
+            return None;
+        }
         let mut current_offset = self.root_offset;
 
         let key = &*self.deserializer.serialize_key(key);
@@ -947,6 +951,24 @@ mod tests {
 
         assert!(tree.get(&s.to_be_bytes().to_vec()).is_some());
     }
+
+    #[test]
+    fn test_get_empty() {
+        let dir = PathBuf::from("data_empty");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let tree: BTree<Vec<u8>, Vec<u8>> = BTree::new(128);
+        let writer = TreeWriter::new(tree, 0, Box::new(DefaultSerializer {}));
+        writer.persist(&dir).unwrap();
+
+        let tree: TreeReader<Vec<u8>, Vec<u8>> =
+            TreeReader::new(&dir, Box::new(DefaultSerializer {})).unwrap();
+        assert!(tree.is_empty());
+        assert!(tree.get(&1_i32.to_be_bytes().to_vec()).is_none());
+        assert!(tree.iter().next().is_none());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
 }
 
 mod main {}
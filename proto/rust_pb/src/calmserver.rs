@@ -88,7 +88,7 @@ pub mod search_request {
     #[derive(Clone, PartialEq, ::prost::Oneof)]
     pub enum Query {
         #[prost(message, tag = "2")]
-        Cql(super::super::core::Query),
+        Cql(::prost::alloc::boxed::Box<super::super::core::Query>),
         #[prost(string, tag = "3")]
         Sql(::prost::alloc::string::String),
     }
@@ -103,6 +103,26 @@ pub struct SearchResponse {
     #[prost(uint32, tag = "3")]
     pub timeuse_mill: u32,
 }
+/// count records matched by query, hits are not fetched
+#[derive(serde::Serialize, serde::Deserialize)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CountRequest {
+    #[prost(string, tag = "1")]
+    pub name: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "2")]
+    pub query: ::core::option::Option<super::core::Query>,
+    /// stop at the first hit, count is 1 if any record matched else 0
+    #[prost(bool, tag = "3")]
+    pub exists: bool,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CountResponse {
+    #[prost(message, optional, tag = "1")]
+    pub status: ::core::option::Option<Status>,
+    #[prost(uint64, tag = "2")]
+    pub count: u64,
+}
 #[derive(serde::Serialize, serde::Deserialize)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct GetRequest {
@@ -302,6 +322,24 @@ pub mod server_client {
             req.extensions_mut().insert(GrpcMethod::new("calmserver.Server", "Search"));
             self.inner.unary(req, path, codec).await
         }
+        pub async fn count(
+            &mut self,
+            request: impl tonic::IntoRequest<super::CountRequest>,
+        ) -> std::result::Result<tonic::Response<super::CountResponse>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/calmserver.Server/Count");
+            let mut req = request.into_request();
+            req.extensions_mut().insert(GrpcMethod::new("calmserver.Server", "Count"));
+            self.inner.unary(req, path, codec).await
+        }
         pub async fn create_engine(
             &mut self,
             request: impl tonic::IntoRequest<super::CreateEngineRequest>,
@@ -401,6 +439,10 @@ pub mod server_server {
             &self,
             request: tonic::Request<super::SearchRequest>,
         ) -> std::result::Result<tonic::Response<super::SearchResponse>, tonic::Status>;
+        async fn count(
+            &self,
+            request: tonic::Request<super::CountRequest>,
+        ) -> std::result::Result<tonic::Response<super::CountResponse>, tonic::Status>;
         async fn create_engine(
             &self,
             request: tonic::Request<super::CreateEngineRequest>,
@@ -628,6 +670,49 @@ pub mod server_server {
                     };
                     Box::pin(fut)
                 }
+                "/calmserver.Server/Count" => {
+                    #[allow(non_camel_case_types)]
+                    struct CountSvc<T: Server>(pub Arc<T>);
+                    impl<T: Server> tonic::server::UnaryService<super::CountRequest>
+                    for CountSvc<T> {
+                        type Response = super::CountResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::CountRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as Server>::count(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = CountSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 "/calmserver.Server/CreateEngine" => {
                     #[allow(non_camel_case_types)]
                     struct CreateEngineSvc<T: Server>(pub Arc<T>);
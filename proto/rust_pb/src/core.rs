@@ -2,7 +2,7 @@
 #[derive(serde::Serialize, serde::Deserialize)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Value {
-    #[prost(oneof = "value::Kind", tags = "1, 2, 3, 4, 5, 6, 7")]
+    #[prost(oneof = "value::Kind", tags = "1, 2, 3, 4, 5, 6, 7, 8")]
     pub kind: ::core::option::Option<value::Kind>,
 }
 /// Nested message and enum types in `Value`.
@@ -24,6 +24,8 @@ pub mod value {
         ObjectValue(super::ObjectValue),
         #[prost(message, tag = "7")]
         VectorValue(super::Embedding),
+        #[prost(bytes, tag = "8")]
+        BytesValue(::prost::alloc::vec::Vec<u8>),
     }
 }
 #[derive(serde::Serialize, serde::Deserialize)]
@@ -78,6 +80,71 @@ pub struct Schema {
     pub fields: ::std::collections::HashMap<::prost::alloc::string::String, Field>,
     #[prost(message, optional, tag = "4")]
     pub metadata: ::core::option::Option<ObjectValue>,
+    #[prost(message, optional, tag = "5")]
+    pub dynamic: ::core::option::Option<DynamicOption>,
+    /// `Ignore` for schemas written before it
+    #[prost(enumeration = "schema::Validation", tag = "6")]
+    #[serde(default)]
+    pub validation: i32,
+}
+/// Nested message and enum types in `Schema`.
+pub mod schema {
+    /// how values of indexed fields are checked when records are written
+    #[derive(serde::Serialize, serde::Deserialize)]
+    #[derive(
+        Clone,
+        Copy,
+        Debug,
+        PartialEq,
+        Eq,
+        Hash,
+        PartialOrd,
+        Ord,
+        ::prost::Enumeration
+    )]
+    #[repr(i32)]
+    pub enum Validation {
+        /// values not of the type are cast, or not indexed if they can not be cast
+        Ignore = 0,
+        /// values not of the type are cast, a record with a value can not be cast or a
+        /// vector not of the dimension is an error
+        Coerce = 1,
+        /// a record with a value not of the type or a vector not of the dimension is an error
+        Strict = 2,
+    }
+    impl Validation {
+        /// String value of the enum field names used in the ProtoBuf definition.
+        ///
+        /// The values are not transformed in any way and thus are considered stable
+        /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+        pub fn as_str_name(&self) -> &'static str {
+            match self {
+                Self::Ignore => "Ignore",
+                Self::Coerce => "Coerce",
+                Self::Strict => "Strict",
+            }
+        }
+        /// Creates an enum from field names used in the ProtoBuf definition.
+        pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+            match value {
+                "Ignore" => Some(Self::Ignore),
+                "Coerce" => Some(Self::Coerce),
+                "Strict" => Some(Self::Strict),
+                _ => None,
+            }
+        }
+    }
+}
+/// rules to infer the type of a new field when schema is schemaless
+#[derive(serde::Serialize, serde::Deserialize)]
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct DynamicOption {
+    /// strings of at least this chars are text, else string, 0 means 64
+    #[prost(uint32, tag = "1")]
+    pub text_min_chars: u32,
+    /// numbers are float, so ints and floats of a key are in one field
+    #[prost(bool, tag = "2")]
+    pub int_as_float: bool,
 }
 #[derive(serde::Serialize, serde::Deserialize)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -104,6 +171,10 @@ pub struct Record {
     pub data: ::prost::alloc::vec::Vec<u8>,
     #[prost(message, repeated, tag = "4")]
     pub vectors: ::prost::alloc::vec::Vec<Vector>,
+    /// stored with the record as it is, not parsed or indexed. so large blobs need
+    /// not be in json data
+    #[prost(bytes = "vec", tag = "5")]
+    pub payload: ::prost::alloc::vec::Vec<u8>,
 }
 #[derive(serde::Serialize, serde::Deserialize)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -112,8 +183,21 @@ pub struct Field {
     pub name: ::prost::alloc::string::String,
     #[prost(enumeration = "field::Type", tag = "2")]
     pub r#type: i32,
-    #[prost(oneof = "field::Option", tags = "3, 4")]
+    #[prost(oneof = "field::Option", tags = "3, 4, 5, 6, 7")]
     pub option: ::core::option::Option<field::Option>,
+    /// path of the value indexed by the field, empty is the name. so `title.raw` of
+    /// source `title` is another index of the same value
+    #[prost(string, tag = "8")]
+    #[serde(default)]
+    pub source: ::prost::alloc::string::String,
+    /// values must be an array, every element is checked by the type
+    #[prost(bool, tag = "9")]
+    #[serde(default)]
+    pub array: bool,
+    /// increased by every alter of the field, its index is `name@version` if it is not 0
+    #[prost(uint32, tag = "10")]
+    #[serde(default)]
+    pub version: u32,
 }
 /// Nested message and enum types in `Field`.
 pub mod field {
@@ -189,6 +273,79 @@ pub mod field {
         pub stopwords: ::core::option::Option<super::Dict>,
         #[prost(message, optional, tag = "4")]
         pub synonyms: ::core::option::Option<super::Dict>,
+        #[prost(enumeration = "fulltext_option::SynonymExpand", tag = "5")]
+        #[serde(default)]
+        pub synonym_expand: i32,
+    }
+    /// fields of objects in a nested field, names are relative to it
+    #[derive(serde::Serialize, serde::Deserialize)]
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct NestedOption {
+        #[prost(message, repeated, tag = "1")]
+        pub fields: ::prost::alloc::vec::Vec<super::Field>,
+    }
+    /// formats like `%Y/%m/%d %H:%M:%S` are tried before rfc3339 and epoch millis,
+    /// time_zone like `+08:00` is used for values without zone, default is utc
+    #[derive(serde::Serialize, serde::Deserialize)]
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct DateOption {
+        #[prost(string, repeated, tag = "1")]
+        pub formats: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+        #[prost(string, tag = "2")]
+        pub time_zone: ::prost::alloc::string::String,
+    }
+    /// normalizers of a string field, applied in order to values and query literals
+    #[derive(serde::Serialize, serde::Deserialize)]
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct KeywordOption {
+        #[prost(enumeration = "keyword_option::Normalizer", repeated, tag = "1")]
+        pub normalizers: ::prost::alloc::vec::Vec<i32>,
+    }
+    /// Nested message and enum types in `KeywordOption`.
+    pub mod keyword_option {
+        #[derive(serde::Serialize, serde::Deserialize)]
+        #[derive(
+            Clone,
+            Copy,
+            Debug,
+            PartialEq,
+            Eq,
+            Hash,
+            PartialOrd,
+            Ord,
+            ::prost::Enumeration
+        )]
+        #[repr(i32)]
+        pub enum Normalizer {
+            Lowercase = 0,
+            AsciiFolding = 1,
+            Trim = 2,
+            Nfkc = 3,
+        }
+        impl Normalizer {
+            /// String value of the enum field names used in the ProtoBuf definition.
+            ///
+            /// The values are not transformed in any way and thus are considered stable
+            /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+            pub fn as_str_name(&self) -> &'static str {
+                match self {
+                    Self::Lowercase => "Lowercase",
+                    Self::AsciiFolding => "AsciiFolding",
+                    Self::Trim => "Trim",
+                    Self::Nfkc => "Nfkc",
+                }
+            }
+            /// Creates an enum from field names used in the ProtoBuf definition.
+            pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+                match value {
+                    "Lowercase" => Some(Self::Lowercase),
+                    "AsciiFolding" => Some(Self::AsciiFolding),
+                    "Trim" => Some(Self::Trim),
+                    "Nfkc" => Some(Self::Nfkc),
+                    _ => None,
+                }
+            }
+        }
     }
     /// Nested message and enum types in `FulltextOption`.
     pub mod fulltext_option {
@@ -266,6 +423,44 @@ pub mod field {
                 }
             }
         }
+        /// when synonyms are expanded, at query time (default) or at index time
+        #[derive(serde::Serialize, serde::Deserialize)]
+        #[derive(
+            Clone,
+            Copy,
+            Debug,
+            PartialEq,
+            Eq,
+            Hash,
+            PartialOrd,
+            Ord,
+            ::prost::Enumeration
+        )]
+        #[repr(i32)]
+        pub enum SynonymExpand {
+            Query = 0,
+            Index = 1,
+        }
+        impl SynonymExpand {
+            /// String value of the enum field names used in the ProtoBuf definition.
+            ///
+            /// The values are not transformed in any way and thus are considered stable
+            /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+            pub fn as_str_name(&self) -> &'static str {
+                match self {
+                    Self::Query => "Query",
+                    Self::Index => "Index",
+                }
+            }
+            /// Creates an enum from field names used in the ProtoBuf definition.
+            pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+                match value {
+                    "Query" => Some(Self::Query),
+                    "Index" => Some(Self::Index),
+                    _ => None,
+                }
+            }
+        }
     }
     #[derive(serde::Serialize, serde::Deserialize)]
     #[derive(
@@ -288,6 +483,12 @@ pub mod field {
         Text = 4,
         Geo = 5,
         Vector = 6,
+        /// array of objects, every object is a hidden child of the record
+        Nested = 7,
+        /// milliseconds since unix epoch
+        Date = 8,
+        /// base64 string in json, bytes_value in proto. indexed as exact terms
+        Bytes = 9,
     }
     impl Type {
         /// String value of the enum field names used in the ProtoBuf definition.
@@ -303,6 +504,9 @@ pub mod field {
                 Self::Text => "Text",
                 Self::Geo => "Geo",
                 Self::Vector => "Vector",
+                Self::Nested => "Nested",
+                Self::Date => "Date",
+                Self::Bytes => "Bytes",
             }
         }
         /// Creates an enum from field names used in the ProtoBuf definition.
@@ -315,6 +519,9 @@ pub mod field {
                 "Text" => Some(Self::Text),
                 "Geo" => Some(Self::Geo),
                 "Vector" => Some(Self::Vector),
+                "Nested" => Some(Self::Nested),
+                "Date" => Some(Self::Date),
+                "Bytes" => Some(Self::Bytes),
                 _ => None,
             }
         }
@@ -326,6 +533,12 @@ pub mod field {
         Embedding(EmbeddingOption),
         #[prost(message, tag = "4")]
         Fulltext(FulltextOption),
+        #[prost(message, tag = "5")]
+        Nested(NestedOption),
+        #[prost(message, tag = "6")]
+        Date(DateOption),
+        #[prost(message, tag = "7")]
+        Keyword(KeywordOption),
     }
 }
 #[derive(serde::Serialize, serde::Deserialize)]
@@ -337,6 +550,10 @@ pub struct Dict {
     pub protocol: i32,
     #[prost(string, tag = "3")]
     pub value: ::prost::alloc::string::String,
+    /// poll Api and File dict every n seconds and reload it when changed, 0 means never
+    #[prost(uint32, tag = "4")]
+    #[serde(default)]
+    pub reload_interval_secs: u32,
 }
 /// Nested message and enum types in `Dict`.
 pub mod dict {
@@ -397,6 +614,77 @@ pub struct Query {
     pub group_by: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
     #[prost(string, repeated, tag = "7")]
     pub fields: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    #[prost(message, repeated, tag = "8")]
+    pub highlight: ::prost::alloc::vec::Vec<HighlightOption>,
+    /// explain score of hits and profile the query
+    #[prost(bool, tag = "9")]
+    pub explain: bool,
+    /// filters are not got from or put to the filter cache
+    #[prost(bool, tag = "10")]
+    pub disable_cache: bool,
+    /// count hits up to this number, total_hits is a lower bound if more are matched, 0 means exact
+    #[prost(uint32, tag = "11")]
+    pub track_total_hits: u32,
+    /// only the best hit of every distinct value of a field is returned
+    #[prost(message, optional, tag = "12")]
+    pub collapse: ::core::option::Option<CollapseOption>,
+    /// count matched records by buckets of a date field
+    #[prost(message, optional, tag = "13")]
+    pub date_histogram: ::core::option::Option<DateHistogram>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DateHistogram {
+    #[prost(string, tag = "1")]
+    pub field: ::prost::alloc::string::String,
+    /// like 1h, 1d, 1w, 1M, 1y
+    #[prost(string, tag = "2")]
+    pub interval: ::prost::alloc::string::String,
+    /// like +08:00, default is utc
+    #[prost(string, tag = "3")]
+    pub time_zone: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Bucket {
+    /// start of the bucket in millis
+    #[prost(int64, tag = "1")]
+    pub key: i64,
+    #[prost(string, tag = "2")]
+    pub key_as_string: ::prost::alloc::string::String,
+    #[prost(uint64, tag = "3")]
+    pub doc_count: u64,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CollapseOption {
+    #[prost(string, tag = "1")]
+    pub field: ::prost::alloc::string::String,
+    /// top hits of the group in inner_hits of the best hit, 0 means none
+    #[prost(uint32, tag = "2")]
+    pub inner_hits: u32,
+}
+/// highlight matched tokens of text field
+#[derive(serde::Serialize, serde::Deserialize)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct HighlightOption {
+    #[prost(string, tag = "1")]
+    pub field: ::prost::alloc::string::String,
+    /// default is <em>
+    #[prost(string, tag = "2")]
+    pub pre_tag: ::prost::alloc::string::String,
+    /// default is </em>
+    #[prost(string, tag = "3")]
+    pub post_tag: ::prost::alloc::string::String,
+    /// bytes of a fragment, 0 means the whole text
+    #[prost(uint32, tag = "4")]
+    pub fragment_size: u32,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Highlight {
+    #[prost(string, repeated, tag = "1")]
+    pub fragments: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
 }
 #[derive(serde::Serialize, serde::Deserialize)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -409,6 +697,13 @@ pub struct Hit {
     pub record: ::core::option::Option<Record>,
     #[prost(bytes = "vec", repeated, tag = "4")]
     pub sort: ::prost::alloc::vec::Vec<::prost::alloc::vec::Vec<u8>>,
+    #[prost(map = "string, message", tag = "5")]
+    pub highlights: ::std::collections::HashMap<::prost::alloc::string::String, Highlight>,
+    #[prost(message, optional, tag = "6")]
+    pub explanation: ::core::option::Option<Explanation>,
+    /// top hits of the collapsed group
+    #[prost(message, repeated, tag = "7")]
+    pub inner_hits: ::prost::alloc::vec::Vec<Hit>,
 }
 #[derive(serde::Serialize, serde::Deserialize)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -417,4 +712,84 @@ pub struct QueryResult {
     pub hits: ::prost::alloc::vec::Vec<Hit>,
     #[prost(uint64, tag = "2")]
     pub total_hits: u64,
+    #[prost(message, optional, tag = "3")]
+    pub profile: ::core::option::Option<Profile>,
+    #[prost(enumeration = "query_result::Relation", tag = "4")]
+    pub total_hits_relation: i32,
+    #[prost(message, repeated, tag = "5")]
+    pub buckets: ::prost::alloc::vec::Vec<Bucket>,
+}
+/// Nested message and enum types in `QueryResult`.
+pub mod query_result {
+    /// total_hits is exact or a lower bound of track_total_hits
+    #[derive(serde::Serialize, serde::Deserialize)]
+    #[derive(
+        Clone,
+        Copy,
+        Debug,
+        PartialEq,
+        Eq,
+        Hash,
+        PartialOrd,
+        Ord,
+        ::prost::Enumeration
+    )]
+    #[repr(i32)]
+    pub enum Relation {
+        Eq = 0,
+        Gte = 1,
+    }
+    impl Relation {
+        /// String value of the enum field names used in the ProtoBuf definition.
+        ///
+        /// The values are not transformed in any way and thus are considered stable
+        /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+        pub fn as_str_name(&self) -> &'static str {
+            match self {
+                Self::Eq => "Eq",
+                Self::Gte => "Gte",
+            }
+        }
+        /// Creates an enum from field names used in the ProtoBuf definition.
+        pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+            match value {
+                "Eq" => Some(Self::Eq),
+                "Gte" => Some(Self::Gte),
+                _ => None,
+            }
+        }
+    }
+}
+/// how a score is made, or how a plan is made with value of its cardinality
+#[derive(serde::Serialize, serde::Deserialize)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Explanation {
+    #[prost(float, tag = "1")]
+    pub value: f32,
+    #[prost(string, tag = "2")]
+    pub description: ::prost::alloc::string::String,
+    #[prost(message, repeated, tag = "3")]
+    pub details: ::prost::alloc::vec::Vec<Explanation>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Profile {
+    #[prost(message, repeated, tag = "1")]
+    pub segments: ::prost::alloc::vec::Vec<SegmentProfile>,
+    /// micros of phases: plan, filter, stream, topn, fetch
+    #[prost(map = "string, uint64", tag = "2")]
+    pub timings: ::std::collections::HashMap<::prost::alloc::string::String, u64>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SegmentProfile {
+    #[prost(uint64, tag = "1")]
+    pub start: u64,
+    #[prost(message, optional, tag = "2")]
+    pub plan: ::core::option::Option<Explanation>,
+    /// bitmaps reused from segment context
+    #[prost(uint32, tag = "3")]
+    pub cache_hits: u32,
+    #[prost(uint32, tag = "4")]
+    pub cache_misses: u32,
 }
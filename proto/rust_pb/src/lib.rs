@@ -1,5 +1,5 @@
-use core::{value::Kind, ObjectValue, Value};
-use std::cmp::Ordering;
+use core::{value::Kind, ListValue, ObjectValue, Value};
+use std::{borrow::Cow, cmp::Ordering};
 
 pub mod calmserver;
 pub mod core;
@@ -20,6 +20,134 @@ impl Value {
     }
 }
 
+/// objects and lists of values, in which values are found by dotted paths
+pub trait DottedPath: Clone {
+    /// value of the key if it is an object
+    fn child(&self, key: &str) -> Option<&Self>;
+
+    fn child_mut(&mut self, key: &str) -> Option<&mut Self>;
+
+    /// items if it is a list
+    fn items(&self) -> Option<&[Self]>;
+
+    fn items_mut(&mut self) -> Option<&mut [Self]>;
+
+    fn from_items(items: Vec<Self>) -> Self;
+
+    /// value of a dotted path like `author.name`, a key with dots is matched first.
+    /// values of every object in a list are collected to a list
+    fn path(&self, path: &str) -> Option<Cow<'_, Self>> {
+        if let Some(items) = self.items() {
+            let mut values = Vec::new();
+            for v in items.iter().filter_map(|v| v.path(path)) {
+                match v.items() {
+                    Some(l) => values.extend_from_slice(l),
+                    None => values.push(v.into_owned()),
+                }
+            }
+            return (!values.is_empty()).then(|| Cow::Owned(Self::from_items(values)));
+        }
+
+        if let Some(v) = self.child(path) {
+            return Some(Cow::Borrowed(v));
+        }
+        path.match_indices('.')
+            .find_map(|(i, _)| self.child(&path[..i]).and_then(|v| v.path(&path[i + 1..])))
+    }
+
+    /// change the values found by `path` in place, a value in every object of a list
+    /// is changed one by one
+    fn path_mut(&mut self, path: &str, f: &mut dyn FnMut(&mut Self)) {
+        if let Some(items) = self.items_mut() {
+            for v in items.iter_mut() {
+                v.path_mut(path, f);
+            }
+            return;
+        }
+
+        if let Some(v) = self.child_mut(path) {
+            return f(v);
+        }
+        let split = path.match_indices('.').map(|(i, _)| i).find(|i| {
+            self.child(&path[..*i])
+                .is_some_and(|v| v.path(&path[i + 1..]).is_some())
+        });
+        if let Some(v) = split.and_then(|i| self.child_mut(&path[..i]).map(|v| (v, i))) {
+            v.0.path_mut(&path[v.1 + 1..], f);
+        }
+    }
+}
+
+impl DottedPath for Value {
+    fn child(&self, key: &str) -> Option<&Self> {
+        match &self.kind {
+            Some(Kind::ObjectValue(obj)) => obj.fields.get(key),
+            _ => None,
+        }
+    }
+
+    fn child_mut(&mut self, key: &str) -> Option<&mut Self> {
+        match &mut self.kind {
+            Some(Kind::ObjectValue(obj)) => obj.fields.get_mut(key),
+            _ => None,
+        }
+    }
+
+    fn items(&self) -> Option<&[Self]> {
+        match &self.kind {
+            Some(Kind::ListValue(list)) => Some(&list.values),
+            _ => None,
+        }
+    }
+
+    fn items_mut(&mut self) -> Option<&mut [Self]> {
+        match &mut self.kind {
+            Some(Kind::ListValue(list)) => Some(&mut list.values),
+            _ => None,
+        }
+    }
+
+    fn from_items(values: Vec<Self>) -> Self {
+        Value {
+            kind: Some(Kind::ListValue(ListValue { values })),
+        }
+    }
+}
+
+impl DottedPath for serde_json::Value {
+    fn child(&self, key: &str) -> Option<&Self> {
+        match self {
+            serde_json::Value::Object(obj) => obj.get(key),
+            _ => None,
+        }
+    }
+
+    fn child_mut(&mut self, key: &str) -> Option<&mut Self> {
+        match self {
+            serde_json::Value::Object(obj) => obj.get_mut(key),
+            _ => None,
+        }
+    }
+
+    fn items(&self) -> Option<&[Self]> {
+        match self {
+            serde_json::Value::Array(arr) => Some(arr),
+            _ => None,
+        }
+    }
+
+    fn items_mut(&mut self) -> Option<&mut [Self]> {
+        match self {
+            serde_json::Value::Array(arr) => Some(arr),
+            _ => None,
+        }
+    }
+
+    fn from_items(items: Vec<Self>) -> Self {
+        serde_json::Value::Array(items)
+    }
+}
+
 impl Eq for core::Hit {}
 
 impl PartialOrd for core::Hit {
@@ -45,6 +173,26 @@ impl Ord for core::Hit {
     }
 }
 
+impl core::Field {
+    /// path of the value indexed by the field, multi-fields like `title.raw` have
+    /// the source `title`, others are their names
+    pub fn source_path(&self) -> &str {
+        match self.source.as_str() {
+            "" => &self.name,
+            source => source,
+        }
+    }
+
+    /// name of the index of the field in segments, an altered field is indexed again
+    /// by its new version while queries use the old one
+    pub fn index_name(&self) -> Cow<'_, str> {
+        match self.version {
+            0 => Cow::Borrowed(&self.name),
+            version => Cow::Owned(format!("{}@{}", self.name, version)),
+        }
+    }
+}
+
 impl core::Record {
     pub fn to_wrapper(self) -> result_wrapper::RecordWrapper {
         result_wrapper::RecordWrapper::new(self)
@@ -62,6 +210,7 @@ pub mod result_wrapper {
 
     use crate::calmserver::*;
     use crate::core::*;
+    use base64::{engine::general_purpose::STANDARD, Engine as _};
     use serde_json::json;
 
     #[derive(Debug, serde::Serialize, serde::Deserialize)]
@@ -75,6 +224,11 @@ pub mod result_wrapper {
     pub struct QueryResultWrapper {
         pub hits: ::prost::alloc::vec::Vec<HitWrapper>,
         pub total_hits: u64,
+        pub total_hits_relation: query_result::Relation,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub profile: Option<Profile>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        pub buckets: Vec<Bucket>,
     }
 
     impl Debug for QueryResultWrapper {
@@ -90,6 +244,10 @@ pub mod result_wrapper {
         pub id: u64,
         pub score: f32,
         pub record: Option<RecordWrapper>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub explanation: Option<Explanation>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        pub inner_hits: Vec<HitWrapper>,
     }
 
     impl Debug for HitWrapper {
@@ -105,6 +263,9 @@ pub mod result_wrapper {
         pub name: String,
         pub data: serde_json::Value,
         pub vectors: Vec<Vector>,
+        // base64 of the binary payload
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub payload: Option<String>,
     }
 
     impl Debug for RecordWrapper {
@@ -122,6 +283,7 @@ pub mod result_wrapper {
                 name: record.name,
                 data,
                 vectors: record.vectors,
+                payload: (!record.payload.is_empty()).then(|| STANDARD.encode(&record.payload)),
             }
         }
     }
@@ -132,15 +294,21 @@ pub mod result_wrapper {
                 id: hit.id,
                 score: hit.score,
                 record: hit.record.map(RecordWrapper::new),
+                explanation: hit.explanation,
+                inner_hits: hit.inner_hits.into_iter().map(HitWrapper::new).collect(),
             }
         }
     }
 
     impl QueryResultWrapper {
         pub fn new(result: QueryResult) -> Self {
+            let total_hits_relation = result.total_hits_relation();
             Self {
                 hits: result.hits.into_iter().map(HitWrapper::new).collect(),
                 total_hits: result.total_hits,
+                total_hits_relation,
+                profile: result.profile,
+                buckets: result.buckets,
             }
         }
     }
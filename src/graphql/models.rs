@@ -1,7 +1,12 @@
 use async_graphql::*;
 use calmcore::util::{CoreError, CoreResult};
 use proto::core::{
-    field::{self, embedding_option::Metric, fulltext_option::Tokenizer, FulltextOption, Type},
+    field::{
+        self,
+        embedding_option::Metric,
+        fulltext_option::{SynonymExpand, Tokenizer},
+        FulltextOption, Type,
+    },
     Dict, Field,
 };
 use serde::Serialize;
@@ -12,6 +17,10 @@ pub struct GqlField {
     pub field_type: GqlType,
     pub text_option: Option<GqlFulltextOption>,
     pub embedding_option: Option<GqlEmbeddingOption>,
+    // path of the indexed value if it is not the name, like `title` of `title.raw`
+    pub source: Option<String>,
+    // values must be an array of the type
+    pub array: Option<bool>,
 }
 
 impl TryInto<Field> for GqlField {
@@ -22,6 +31,9 @@ impl TryInto<Field> for GqlField {
             name: self.name,
             r#type: 0,
             option: None,
+            source: self.source.unwrap_or_default(),
+            array: self.array.unwrap_or_default(),
+            version: 0,
         };
 
         match self.field_type {
@@ -37,9 +49,14 @@ impl TryInto<Field> for GqlField {
                     filters: vec![],
                     stopwords: o.stopwords.map(|gd| gd.into()),
                     synonyms: o.synonyms.map(|gd| gd.into()),
+                    synonym_expand: match o.synonym_expand.unwrap_or_default() {
+                        GqlSynonymExpand::Query => SynonymExpand::Query as i32,
+                        GqlSynonymExpand::Index => SynonymExpand::Index as i32,
+                    },
                 }));
             }
             GqlType::Geo => field.set_type(Type::Geo),
+            GqlType::Bytes => field.set_type(Type::Bytes),
             GqlType::Embedding => {
                 field.set_type(Type::Vector);
                 let o: GqlEmbeddingOption = self.embedding_option.unwrap_or_default();
@@ -96,9 +113,17 @@ pub struct GqlFulltextOption {
     pub lowercase: Option<bool>,
     pub stopwords: Option<GqlDict>,
     pub synonyms: Option<GqlDict>,
+    pub synonym_expand: Option<GqlSynonymExpand>,
     pub keywrods: Option<GqlDict>,
 }
 
+#[derive(Default, Enum, Copy, Clone, Eq, PartialEq, Serialize)]
+pub enum GqlSynonymExpand {
+    #[default]
+    Query,
+    Index,
+}
+
 #[derive(Default, Enum, Copy, Clone, Eq, PartialEq, Serialize)]
 pub enum GqlProtocol {
     #[default]
@@ -112,6 +137,7 @@ pub struct GqlDict {
     pub name: String,
     pub protocol: GqlProtocol,
     pub value: String,
+    pub reload_interval_secs: Option<u32>,
 }
 
 impl From<GqlDict> for Dict {
@@ -124,6 +150,7 @@ impl From<GqlDict> for Dict {
                 GqlProtocol::File => 3,
             },
             value: d.value,
+            reload_interval_secs: d.reload_interval_secs.unwrap_or(0),
         }
     }
 }
@@ -153,9 +180,13 @@ pub enum GqlType {
     Text,
     Geo,
     Embedding,
+    Bytes,
 }
 
 pub mod result_wrapper {
+    use std::collections::HashMap;
+
+    use base64::{engine::general_purpose::STANDARD, Engine as _};
     use proto::calmserver::SearchResponse;
     use serde_json::json;
 
@@ -170,6 +201,9 @@ pub mod result_wrapper {
     pub struct QueryResultWrapper {
         pub hits: ::prost::alloc::vec::Vec<HitWrapper>,
         pub total_hits: u64,
+        pub total_hits_relation: proto::core::query_result::Relation,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub profile: Option<proto::core::Profile>,
     }
 
     #[derive(serde::Serialize, serde::Deserialize)]
@@ -177,6 +211,11 @@ pub mod result_wrapper {
         pub id: u64,
         pub score: f32,
         pub record: Option<RecordWrapper>,
+        pub highlights: HashMap<String, Vec<String>>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub explanation: Option<proto::core::Explanation>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        pub inner_hits: Vec<HitWrapper>,
     }
 
     #[derive(serde::Serialize, serde::Deserialize)]
@@ -184,6 +223,9 @@ pub mod result_wrapper {
         pub name: String,
         pub data: serde_json::Value,
         pub vectors: Vec<proto::core::Vector>,
+        // base64 of the binary payload
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub payload: Option<String>,
     }
 
     impl RecordWrapper {
@@ -193,6 +235,7 @@ pub mod result_wrapper {
                 name: record.name,
                 data,
                 vectors: record.vectors,
+                payload: (!record.payload.is_empty()).then(|| STANDARD.encode(&record.payload)),
             }
         }
     }
@@ -203,15 +246,25 @@ pub mod result_wrapper {
                 id: hit.id,
                 score: hit.score,
                 record: hit.record.map(RecordWrapper::new),
+                highlights: hit
+                    .highlights
+                    .into_iter()
+                    .map(|(k, v)| (k, v.fragments))
+                    .collect(),
+                explanation: hit.explanation,
+                inner_hits: hit.inner_hits.into_iter().map(HitWrapper::new).collect(),
             }
         }
     }
 
     impl QueryResultWrapper {
         pub fn new(result: proto::core::QueryResult) -> Self {
+            let total_hits_relation = result.total_hits_relation();
             Self {
                 hits: result.hits.into_iter().map(HitWrapper::new).collect(),
                 total_hits: result.total_hits,
+                total_hits_relation,
+                profile: result.profile,
             }
         }
     }
@@ -39,6 +39,8 @@ impl QueryRoot {
         let _ = core.create_engine(Schema {
             name: engine_name.clone(),
             schemaless: false,
+            dynamic: None,
+            validation: 0,
             fields,
             metadata,
         })?;
@@ -113,7 +113,7 @@ impl proto::calmserver::server_server::Server for GrpcServer {
         let query = result!(result, SearchResponse);
 
         let result = match query {
-            search_request::Query::Cql(cql) => engine.search(cql),
+            search_request::Query::Cql(cql) => engine.search(*cql),
             search_request::Query::Sql(sql) => engine.sql(&sql),
         };
 
@@ -126,6 +126,31 @@ impl proto::calmserver::server_server::Server for GrpcServer {
         }))
     }
 
+    async fn count(
+        &self,
+        request: tonic::Request<CountRequest>,
+    ) -> Result<tonic::Response<CountResponse>, tonic::Status> {
+        let req = request.into_inner();
+        let engine = get_engine!(self, &req.name, CountResponse);
+
+        let result = req
+            .query
+            .ok_or_else(|| CoreError::InvalidParam("query is required".to_string()));
+        let query = result!(result, CountResponse);
+
+        let result = if req.exists {
+            engine.exists(query).map(|e| e as u64)
+        } else {
+            engine.count(query)
+        };
+        let count = result!(result, CountResponse);
+
+        Ok(tonic::Response::new(CountResponse {
+            status: status(&CoreError::Ok),
+            count,
+        }))
+    }
+
     async fn create_engine(
         &self,
         request: tonic::Request<CreateEngineRequest>,